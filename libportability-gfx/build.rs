@@ -0,0 +1,394 @@
+//! Generates the entry point tables of the `gfx*` implementations from `vk.xml`:
+//!   - `entry_points.rs`: perfect hash map from the `vk*` names to the
+//!     dispatch level, the extension and the address of the implementation.
+//!   - `exports.rs`: the `vk_exports!` macro defining the `vk*` symbols,
+//!     with the signatures of the registry.
+//!
+//! Only the names of the implementations are taken from `impls.rs`. The parameter
+//! types of the aliased commands are resolved to the names bound in `lib.rs`,
+//! e.g. `VkPhysicalDeviceFeatures2KHR` for `vkGetPhysicalDeviceFeatures2KHR`.
+
+extern crate phf_codegen;
+extern crate roxmltree;
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+const IMPLS_PATH: &str = "src/impls.rs";
+const BINDINGS_PATH: &str = "src/lib.rs";
+/// Overrides the location of `vk.xml`.
+const REGISTRY_ENV: &str = "VULKAN_REGISTRY";
+const REGISTRY_PATHS: &[&str] = &[
+    "../modules/vulkan-docs/xml/vk.xml",
+    "../modules/vulkan-docs/src/spec/vk.xml",
+];
+
+struct Param {
+    name: String,
+    ty: String,
+    /// Type name without the pointer levels, which `ty` ends with.
+    base: String,
+}
+
+struct Command {
+    params: Vec<Param>,
+    ret: Option<String>,
+    /// Extension introducing the command, `None` for the core ones.
+    extension: Option<String>,
+}
+
+/// Stops the build with a message, instead of a panic backtrace.
+fn fail(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
+
+/// Names of the implemented commands, without the `gfx` prefix.
+fn implemented_commands(source: &str) -> Vec<&str> {
+    const PREFIX: &str = "pub extern \"C\" fn gfx";
+    source
+        .match_indices(PREFIX)
+        .map(|(pos, _)| {
+            let rest = &source[pos + PREFIX.len() ..];
+            let end = rest.find('(').unwrap_or(rest.len());
+            rest[.. end].trim()
+        })
+        .collect()
+}
+
+/// Skips the elements specific to other APIs than Vulkan, like Vulkan SC.
+fn is_vulkan(node: &roxmltree::Node) -> bool {
+    node.attribute("api")
+        .map_or(true, |api| api.split(',').any(|api| api == "vulkan"))
+}
+
+fn map_c_type(ty: &str) -> String {
+    match ty {
+        "void" => "::std::os::raw::c_void",
+        "char" => "::std::os::raw::c_char",
+        "int" => "::std::os::raw::c_int",
+        "float" => "f32",
+        "double" => "f64",
+        "uint8_t" => "u8",
+        "uint16_t" => "u16",
+        "uint32_t" => "u32",
+        "uint64_t" => "u64",
+        "int32_t" => "i32",
+        "int64_t" => "i64",
+        "size_t" => "usize",
+        other => other,
+    }.to_string()
+}
+
+/// Converts a `<param>` declaration, like `const char* const* ppNames`
+/// or `const float blendConstants[4]`, to its Rust form.
+fn parse_param(node: &roxmltree::Node) -> Param {
+    let (mut before, mut between, mut after) = (String::new(), String::new(), String::new());
+    let (mut ty, mut name) = (None, None);
+    for child in node.children() {
+        if child.has_tag_name("type") {
+            ty = child.text();
+        } else if child.has_tag_name("name") {
+            name = child.text();
+        } else if child.is_text() {
+            let text = child.text().unwrap_or("");
+            match (ty, name) {
+                (None, _) => before.push_str(text),
+                (Some(_), None) => between.push_str(text),
+                (Some(_), Some(_)) => after.push_str(text),
+            }
+        }
+    }
+    let (ty, name) = match (ty, name) {
+        (Some(ty), Some(name)) => (ty, name),
+        _ => fail(&format!("Unexpected command parameter in vk.xml: {:?}", node)),
+    };
+
+    let pointee_const = before.contains("const");
+    // the qualifiers of each pointer level precede its star
+    let segments = between.split('*').collect::<Vec<_>>();
+    let mut rust = map_c_type(ty);
+    for level in 0 .. segments.len() - 1 {
+        let is_const = if level == 0 { pointee_const } else { segments[level].contains("const") };
+        rust = format!("*{} {}", if is_const { "const" } else { "mut" }, rust);
+    }
+    // array parameters decay to pointers
+    if after.contains('[') {
+        rust = format!("*{} {}", if pointee_const { "const" } else { "mut" }, rust);
+    }
+
+    Param {
+        name: name.to_string(),
+        ty: rust,
+        base: map_c_type(ty),
+    }
+}
+
+/// Collects the commands of the registry, with their signature and the extension introducing them.
+fn parse_registry(path: &Path) -> HashMap<String, Command> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) => fail(&format!("Unable to read {}: {}", path.display(), err)),
+    };
+    let doc = match roxmltree::Document::parse(&text) {
+        Ok(doc) => doc,
+        Err(err) => fail(&format!("Invalid registry {}: {}", path.display(), err)),
+    };
+    let root = doc.root_element();
+
+    let mut commands = HashMap::new();
+    let mut aliases = Vec::new();
+    let mut core = HashSet::new();
+    let mut extensions = Vec::new();
+
+    for section in root.children().filter(|n| n.is_element() && is_vulkan(n)) {
+        match section.tag_name().name() {
+            "commands" => {
+                for command in section.children().filter(|n| n.has_tag_name("command") && is_vulkan(n)) {
+                    if let Some(alias) = command.attribute("alias") {
+                        aliases.push((command.attribute("name").unwrap().to_string(), alias.to_string()));
+                        continue;
+                    }
+                    let proto = command.children().find(|n| n.has_tag_name("proto")).unwrap();
+                    let child_text = |tag: &str| {
+                        proto.children().find(|n| n.has_tag_name(tag)).and_then(|n| n.text()).unwrap()
+                    };
+                    let ret = match child_text("type") {
+                        "void" => None,
+                        ty => Some(map_c_type(ty)),
+                    };
+                    let params = command
+                        .children()
+                        .filter(|n| n.has_tag_name("param") && is_vulkan(n))
+                        .map(|n| parse_param(&n))
+                        .collect();
+                    commands.insert(child_text("name").to_string(), Command {
+                        params,
+                        ret,
+                        extension: None,
+                    });
+                }
+            }
+            "feature" => {
+                let required = section
+                    .descendants()
+                    .filter(|n| n.has_tag_name("command"))
+                    .filter_map(|n| n.attribute("name"));
+                core.extend(required.map(String::from));
+            }
+            "extensions" => {
+                for extension in section.children().filter(|n| n.has_tag_name("extension")) {
+                    let supported = extension.attribute("supported").unwrap_or("");
+                    if !supported.split(',').any(|api| api == "vulkan") {
+                        continue;
+                    }
+                    let ext_name = extension.attribute("name").unwrap();
+                    let required = extension
+                        .descendants()
+                        .filter(|n| n.has_tag_name("command"))
+                        .filter_map(|n| n.attribute("name"));
+                    for command in required {
+                        extensions.push((command.to_string(), ext_name.to_string()));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for (name, alias) in aliases {
+        let command = match commands.get(&alias) {
+            Some(target) => Command {
+                params: target.params
+                    .iter()
+                    .map(|p| Param { name: p.name.clone(), ty: p.ty.clone(), base: p.base.clone() })
+                    .collect(),
+                ret: target.ret.clone(),
+                extension: None,
+            },
+            None => continue,
+        };
+        commands.insert(name, command);
+    }
+    // extensions may also list core commands they interact with
+    for (name, extension) in extensions {
+        if core.contains(&name) {
+            continue;
+        }
+        if let Some(command) = commands.get_mut(&name) {
+            if command.extension.is_none() {
+                command.extension = Some(extension);
+            }
+        }
+    }
+
+    commands
+}
+
+/// Names of the types bound in `lib.rs`.
+fn bound_types(source: &str) -> HashSet<&str> {
+    const PREFIXES: &[&str] = &["pub type ", "pub struct ", "pub enum "];
+    source
+        .lines()
+        .filter_map(|line| PREFIXES.iter().find(|p| line.starts_with(*p)).map(|p| &line[p.len() ..]))
+        .map(|rest| {
+            let end = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+            &rest[.. end]
+        })
+        .collect()
+}
+
+/// Replaces the registry types of the parameters by the bound ones.
+///
+/// The registry gives the aliased commands the types of their target,
+/// like `VkPhysicalDeviceFeatures2`, while the bindings predate the promotion
+/// and only have the aliases with the vendor suffix of the command.
+fn resolve_types(vk_name: &str, command: &Command, bound: &HashSet<&str>) -> Vec<Param> {
+    let suffix_start = vk_name
+        .rfind(|c: char| !c.is_ascii_uppercase())
+        .map_or(0, |pos| pos + 1);
+    let suffix = &vk_name[suffix_start ..];
+    command.params
+        .iter()
+        .map(|param| {
+            if !param.base.starts_with("Vk") && !param.base.starts_with("PFN_") {
+                return Param { name: param.name.clone(), ty: param.ty.clone(), base: param.base.clone() };
+            }
+            let suffixed = format!("{}{}", param.base, suffix);
+            let base = if !suffix.is_empty() && bound.contains(suffixed.as_str()) {
+                suffixed
+            } else if bound.contains(param.base.as_str()) {
+                param.base.clone()
+            } else {
+                fail(&format!(
+                    "Parameter {} of {} has the type {}, which {} doesn't bind",
+                    param.name, vk_name, param.base, BINDINGS_PATH,
+                ))
+            };
+            let pointers = &param.ty[.. param.ty.len() - param.base.len()];
+            Param {
+                name: param.name.clone(),
+                ty: format!("{}{}", pointers, base),
+                base,
+            }
+        })
+        .collect()
+}
+
+fn level(name: &str, command: &Command) -> &'static str {
+    // the instance is optional when querying the global commands
+    if name == "vkGetInstanceProcAddr" {
+        return "Level::Global";
+    }
+    match command.params.first().map(|p| p.ty.as_str()) {
+        Some("VkInstance") => "Level::Instance",
+        Some("VkPhysicalDevice") => "Level::PhysicalDevice",
+        Some("VkDevice") | Some("VkQueue") | Some("VkCommandBuffer") => "Level::Device",
+        _ => "Level::Global",
+    }
+}
+
+fn find_registry() -> PathBuf {
+    println!("cargo:rerun-if-env-changed={}", REGISTRY_ENV);
+    if let Some(path) = env::var_os(REGISTRY_ENV) {
+        return PathBuf::from(path);
+    }
+    match REGISTRY_PATHS.iter().map(Path::new).find(|path| path.exists()) {
+        Some(path) => path.to_path_buf(),
+        None => fail(&format!(
+            "The Vulkan registry wasn't found at {}. Set {} to the path of a `vk.xml` \
+            (1.3.234 or newer), or run `make registry` to check it out.",
+            REGISTRY_PATHS[0], REGISTRY_ENV,
+        )),
+    }
+}
+
+fn main() {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    println!("cargo:rerun-if-changed={}", IMPLS_PATH);
+    let source = match fs::read_to_string(IMPLS_PATH) {
+        Ok(source) => source,
+        Err(err) => fail(&format!("Unable to read {}: {}", IMPLS_PATH, err)),
+    };
+
+    println!("cargo:rerun-if-changed={}", BINDINGS_PATH);
+    let bindings = match fs::read_to_string(BINDINGS_PATH) {
+        Ok(bindings) => bindings,
+        Err(err) => fail(&format!("Unable to read {}: {}", BINDINGS_PATH, err)),
+    };
+    let bound = bound_types(&bindings);
+
+    let registry_path = find_registry();
+    println!("cargo:rerun-if-changed={}", registry_path.display());
+    let registry = parse_registry(&registry_path);
+
+    let commands = implemented_commands(&source)
+        .into_iter()
+        .map(|name| {
+            let vk_name = format!("vk{}", name);
+            match registry.get(&vk_name) {
+                Some(command) => (name, vk_name, command),
+                None => fail(&format!(
+                    "gfx{} doesn't implement a Vulkan command of {}, which may predate its extension",
+                    name,
+                    registry_path.display(),
+                )),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut entry_points = phf_codegen::Map::new();
+    let mut addresses = String::new();
+    for (index, &(name, ref vk_name, command)) in commands.iter().enumerate() {
+        entry_points.entry(
+            vk_name.clone(),
+            &format!(
+                "EntryPoint {{ level: {}, extension: {:?}, index: {} }}",
+                level(vk_name, command),
+                command.extension,
+                index,
+            ),
+        );
+        addresses.push_str(&format!(
+            "        {} => gfx{} as *const (),\n",
+            index, name,
+        ));
+    }
+
+    let mut file = BufWriter::new(File::create(out_dir.join("entry_points.rs")).unwrap());
+    write!(file, "pub static ENTRY_POINTS: ::phf::Map<&'static str, EntryPoint> = ").unwrap();
+    entry_points.build(&mut file).unwrap();
+    writeln!(file, ";\n").unwrap();
+    writeln!(file, "fn entry_point_addr(index: usize) -> *const () {{").unwrap();
+    writeln!(file, "    match index {{\n{}        _ => unreachable!(),\n    }}\n}}", addresses).unwrap();
+
+    let mut file = BufWriter::new(File::create(out_dir.join("exports.rs")).unwrap());
+    writeln!(file, "#[macro_export]\nmacro_rules! vk_exports {{\n    () => {{").unwrap();
+    for &(name, ref vk_name, command) in &commands {
+        let resolved = resolve_types(vk_name, command, &bound);
+        let params = resolved
+            .iter()
+            .map(|p| format!("{}: {}", p.name, p.ty))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let args = resolved
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let ret = match command.ret {
+            Some(ref ret) => format!(" -> {}", ret),
+            None => String::new(),
+        };
+        writeln!(file, "        #[no_mangle]").unwrap();
+        writeln!(file, "        pub extern \"C\" fn {}({}){} {{", vk_name, params, ret).unwrap();
+        writeln!(file, "            gfx{}({})", name, args).unwrap();
+        writeln!(file, "        }}").unwrap();
+    }
+    writeln!(file, "    }};\n}}").unwrap();
+}
@@ -0,0 +1,565 @@
+//! Driver configuration.
+//!
+//! Settings are read from `portability.toml` in the working directory,
+//! or from the file pointed to by `GFX_CONFIG`. Top-level keys apply to
+//! every application, while `[[application]]` sections only apply when
+//! their `name`/`engine` match the `VkApplicationInfo` of the instance,
+//! ignoring case. Environment variables override the file.
+//!
+//! ```toml
+//! threaded_submit = true
+//!
+//! [[application]]
+//! name = "dota"
+//! recording = "deferred"
+//! hide_features = ["geometryShader"]
+//! disable_extensions = ["VK_KHR_maintenance1"]
+//!
+//! [application.clamp_limits]
+//! maxBoundDescriptorSets = 4
+//! ```
+//!
+//! The `device_*` and `override_limits` settings change what the physical
+//! devices report, in order to pose as a different, usually more restrictive,
+//! device. Devices then reject the usage going beyond the reported limits.
+//!
+//! `adapters` lists the physical devices to expose, in order of preference:
+//!
+//! ```toml
+//! adapters = ["discrete", "radeon", 0]
+//! ```
+
+use std::{env, fs, io, mem};
+use std::ffi::CStr;
+
+use super::*;
+
+const DEFAULT_PATH: &str = "portability.toml";
+
+const ENV_OVERRIDES: &[(&str, &str)] = &[
+    ("GFX_THREADED_SUBMIT", "threaded_submit"),
+    ("GFX_EMULATE_TRIANGLE_FANS", "emulate_triangle_fans"),
+    ("GFX_METAL_RECORDING", "recording"),
+    ("GFX_METAL_STITCHING", "stitching"),
+    ("GFX_METAL_ACQUIRING", "acquiring"),
+    ("GFX_METAL_SIGNPOSTS", "signposts"),
+    ("GFX_HIDE_FEATURES", "hide_features"),
+    ("GFX_CLAMP_LIMITS", "clamp_limits"),
+    ("GFX_OVERRIDE_LIMITS", "override_limits"),
+    ("GFX_DISABLE_EXTENSIONS", "disable_extensions"),
+    ("GFX_DEVICE_NAME", "device_name"),
+    ("GFX_VENDOR_ID", "vendor_id"),
+    ("GFX_DEVICE_ID", "device_id"),
+    ("GFX_DEVICE_TYPE", "device_type"),
+    ("GFX_ADAPTER", "adapters"),
+];
+
+const DEVICE_TYPE_NAMES: &[(&str, VkPhysicalDeviceType)] = &[
+    ("other", VkPhysicalDeviceType::VK_PHYSICAL_DEVICE_TYPE_OTHER),
+    ("integrated", VkPhysicalDeviceType::VK_PHYSICAL_DEVICE_TYPE_INTEGRATED_GPU),
+    ("discrete", VkPhysicalDeviceType::VK_PHYSICAL_DEVICE_TYPE_DISCRETE_GPU),
+    ("virtual", VkPhysicalDeviceType::VK_PHYSICAL_DEVICE_TYPE_VIRTUAL_GPU),
+    ("cpu", VkPhysicalDeviceType::VK_PHYSICAL_DEVICE_TYPE_CPU),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Recording {
+    Immediate,
+    Deferred,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Acquiring {
+    Wait,
+    Oldest,
+}
+
+/// Matches physical devices, as listed by the backend.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AdapterSelector {
+    /// Position in the backend order.
+    Index(usize),
+    Type(VkPhysicalDeviceType),
+    /// Case insensitive substring of the name.
+    Name(String),
+}
+
+impl AdapterSelector {
+    fn parse(value: &toml::Value) -> Result<Self, String> {
+        match *value {
+            toml::Value::Integer(index) if index >= 0 => Ok(AdapterSelector::Index(index as usize)),
+            toml::Value::String(ref name) => Ok(match name.trim().parse::<usize>() {
+                Ok(index) => AdapterSelector::Index(index),
+                Err(_) => match parse_name(value, DEVICE_TYPE_NAMES) {
+                    Ok(ty) => AdapterSelector::Type(ty),
+                    Err(_) => AdapterSelector::Name(name.to_lowercase()),
+                },
+            }),
+            ref other => Err(format!("expected an index, a device type or a name, got {}", other)),
+        }
+    }
+
+    pub fn matches(&self, index: usize, info: &hal::AdapterInfo) -> bool {
+        match *self {
+            AdapterSelector::Index(i) => i == index,
+            AdapterSelector::Type(ty) => ty == conv::device_type_from_hal(&info.device_type),
+            AdapterSelector::Name(ref name) => info.name.to_lowercase().contains(name),
+        }
+    }
+}
+
+/// Driver knobs. Backends ignore the ones they have no use for.
+#[derive(Clone, Debug)]
+pub struct Settings {
+    /// Submit and present from a dedicated thread per queue.
+    pub threaded_submit: Option<bool>,
+    /// Report triangle fans as supported on backends without them, see `fan.rs`.
+    pub emulate_triangle_fans: Option<bool>,
+    /// Command buffer recording mode (Metal).
+    pub recording: Option<Recording>,
+    /// Stitching of deferred command buffers on submission (Metal).
+    pub stitching: Option<bool>,
+    /// Swapchain image acquisition mode (Metal).
+    pub acquiring: Option<Acquiring>,
+    /// Signposts for profiling (Metal).
+    pub signposts: Option<bool>,
+    /// Features reported as unsupported.
+    pub hidden_features: hal::Features,
+    /// Bounds of the reported limits, by Vulkan name, only ever making them stricter.
+    pub limit_clamps: Vec<(String, f64)>,
+    /// Values of the reported limits, by Vulkan name.
+    pub limit_overrides: Vec<(String, f64)>,
+    /// Device extensions neither advertised nor accepted.
+    pub disabled_extensions: Vec<String>,
+    /// Reported `deviceName`.
+    pub device_name: Option<String>,
+    /// Reported `vendorID`.
+    pub vendor_id: Option<u32>,
+    /// Reported `deviceID`.
+    pub device_id: Option<u32>,
+    /// Reported `deviceType`.
+    pub device_type: Option<VkPhysicalDeviceType>,
+    /// Physical devices to expose, in order of preference. All of them if empty.
+    pub adapters: Vec<AdapterSelector>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            threaded_submit: None,
+            emulate_triangle_fans: None,
+            recording: None,
+            stitching: None,
+            acquiring: None,
+            signposts: None,
+            hidden_features: hal::Features::empty(),
+            limit_clamps: Vec::new(),
+            limit_overrides: Vec::new(),
+            disabled_extensions: Vec::new(),
+            device_name: None,
+            vendor_id: None,
+            device_id: None,
+            device_type: None,
+            adapters: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    fn set(&mut self, key: &str, value: &toml::Value) -> Result<(), String> {
+        match key {
+            "threaded_submit" => self.threaded_submit = Some(parse_bool(value)?),
+            "emulate_triangle_fans" => self.emulate_triangle_fans = Some(parse_bool(value)?),
+            "recording" => self.recording = Some(parse_name(value, &[
+                ("immediate", Recording::Immediate),
+                ("deferred", Recording::Deferred),
+            ])?),
+            "stitching" => self.stitching = Some(parse_bool(value)?),
+            "acquiring" => self.acquiring = Some(parse_name(value, &[
+                ("wait", Acquiring::Wait),
+                ("oldest", Acquiring::Oldest),
+            ])?),
+            "signposts" => self.signposts = Some(parse_bool(value)?),
+            "hide_features" => {
+                for name in parse_strings(value)? {
+                    self.hidden_features |= conv::FEATURE_NAMES
+                        .iter()
+                        .find(|&&(feature, _)| feature == name)
+                        .map(|&(_, features)| features)
+                        .ok_or_else(|| format!("unknown feature `{}`", name))?;
+                }
+            }
+            "clamp_limits" => self.limit_clamps.extend(parse_limits(value)?),
+            "override_limits" => self.limit_overrides.extend(parse_limits(value)?),
+            "disable_extensions" => {
+                self.disabled_extensions.extend(parse_strings(value)?);
+            }
+            "device_name" => {
+                let name = value
+                    .as_str()
+                    .ok_or_else(|| format!("expected a string, got {}", value))?;
+                // `deviceName` is a fixed array, keeping the terminator
+                if name.len() >= VK_MAX_PHYSICAL_DEVICE_NAME_SIZE as usize {
+                    return Err(format!("`{}` is too long", name));
+                }
+                self.device_name = Some(name.to_string());
+            }
+            "vendor_id" => self.vendor_id = Some(parse_u32(value)?),
+            "device_id" => self.device_id = Some(parse_u32(value)?),
+            "device_type" => self.device_type = Some(parse_name(value, DEVICE_TYPE_NAMES)?),
+            "adapters" => {
+                let values = match *value {
+                    // a single selector, or a comma separated list from the environment
+                    toml::Value::String(ref list) => list
+                        .split(',')
+                        .map(|s| toml::Value::String(s.trim().to_string()))
+                        .collect(),
+                    toml::Value::Array(ref array) => array.clone(),
+                    ref other => vec![other.clone()],
+                };
+                self.adapters = values
+                    .iter()
+                    .map(AdapterSelector::parse)
+                    .collect::<Result<_, _>>()?;
+            }
+            other => return Err(format!("unknown setting `{}`", other)),
+        }
+        Ok(())
+    }
+
+    /// Takes the values set in `other`, keeping ours for the rest.
+    fn merge(&mut self, other: &Settings) {
+        self.threaded_submit = other.threaded_submit.or(self.threaded_submit);
+        self.emulate_triangle_fans = other.emulate_triangle_fans.or(self.emulate_triangle_fans);
+        self.recording = other.recording.or(self.recording);
+        self.stitching = other.stitching.or(self.stitching);
+        self.acquiring = other.acquiring.or(self.acquiring);
+        self.signposts = other.signposts.or(self.signposts);
+        self.hidden_features |= other.hidden_features;
+        self.limit_clamps.extend(other.limit_clamps.iter().cloned());
+        self.limit_overrides.extend(other.limit_overrides.iter().cloned());
+        self.disabled_extensions.extend(other.disabled_extensions.iter().cloned());
+        self.device_name = other.device_name.clone().or(self.device_name.take());
+        self.vendor_id = other.vendor_id.or(self.vendor_id);
+        self.device_id = other.device_id.or(self.device_id);
+        self.device_type = other.device_type.or(self.device_type);
+        if !other.adapters.is_empty() {
+            self.adapters = other.adapters.clone();
+        }
+    }
+
+    /// Applies `limit_clamps`, then `limit_overrides` to the given limits.
+    pub fn apply_limits(&self, limits: &mut VkPhysicalDeviceLimits) {
+        for &(ref name, value) in &self.limit_clamps {
+            if let Some((bound, limit)) = conv::limit_mut(limits, name) {
+                limit.clamp(bound, value);
+            }
+        }
+        for &(ref name, value) in &self.limit_overrides {
+            if let Some((bound, limit)) = conv::limit_mut(limits, name) {
+                if bound.is_looser(value, limit.get()) {
+                    warn!("Overriding {} beyond the hardware limit, usage won't be checked", name);
+                }
+                limit.set(value);
+            }
+        }
+    }
+
+    pub fn is_extension_disabled(&self, name: &str) -> bool {
+        self.disabled_extensions.iter().any(|ext| ext == name)
+    }
+
+    fn has_metal_settings(&self) -> bool {
+        self.recording.is_some() ||
+        self.stitching.is_some() ||
+        self.acquiring.is_some() ||
+        self.signposts.is_some()
+    }
+}
+
+fn parse_bool(value: &toml::Value) -> Result<bool, String> {
+    match *value {
+        toml::Value::Boolean(b) => Ok(b),
+        // environment values, where a set variable without a value enables the setting
+        toml::Value::String(ref s) => match s.trim().to_lowercase().as_str() {
+            "" | "1" | "yes" | "true" | "on" => Ok(true),
+            "0" | "no" | "false" | "off" => Ok(false),
+            _ => Err(format!("expected a boolean, got `{}`", s)),
+        },
+        ref other => Err(format!("expected a boolean, got {}", other)),
+    }
+}
+
+fn parse_name<T: Copy>(value: &toml::Value, options: &[(&str, T)]) -> Result<T, String> {
+    let name = value.as_str().map(|s| s.to_lowercase());
+    options
+        .iter()
+        .find(|&&(option, _)| name.as_ref().map(String::as_str) == Some(option))
+        .map(|&(_, v)| v)
+        .ok_or_else(|| {
+            let names = options.iter().map(|&(name, _)| name).collect::<Vec<_>>();
+            format!("expected one of {:?}, got {}", names, value)
+        })
+}
+
+fn parse_u32(value: &toml::Value) -> Result<u32, String> {
+    let parsed = match *value {
+        toml::Value::Integer(v) => Some(v as u64),
+        // environment values, possibly in hex
+        toml::Value::String(ref s) if s.starts_with("0x") => u64::from_str_radix(&s[2..], 16).ok(),
+        toml::Value::String(ref s) => s.parse().ok(),
+        _ => None,
+    };
+    match parsed {
+        Some(v) if v <= u32::max_value() as u64 => Ok(v as u32),
+        _ => Err(format!("expected a 32-bit unsigned integer, got {}", value)),
+    }
+}
+
+/// Parses an array of strings, or a comma separated list coming from the environment.
+fn parse_strings(value: &toml::Value) -> Result<Vec<String>, String> {
+    if let Some(list) = value.as_str() {
+        return Ok(list.split(',').map(|s| s.trim().to_string()).collect());
+    }
+    let array = value
+        .as_array()
+        .ok_or_else(|| format!("expected an array of strings, got {}", value))?;
+    array
+        .iter()
+        .map(|v| v.as_str().map(String::from).ok_or_else(|| format!("expected a string, got {}", v)))
+        .collect()
+}
+
+/// Parses a table of limits, or a comma separated list of `name=value`
+/// coming from the environment.
+fn parse_limits(value: &toml::Value) -> Result<Vec<(String, f64)>, String> {
+    let entries = match *value {
+        toml::Value::Table(ref table) => table
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect::<Vec<_>>(),
+        toml::Value::String(ref list) => list
+            .split(',')
+            .map(|entry| {
+                let mut parts = entry.splitn(2, '=');
+                let name = parts.next().unwrap().trim().to_string();
+                let value = parts
+                    .next()
+                    .and_then(|v| v.trim().parse::<f64>().ok())
+                    .ok_or_else(|| format!("expected `name=value`, got `{}`", entry))?;
+                Ok((name, toml::Value::Float(value)))
+            })
+            .collect::<Result<Vec<_>, String>>()?,
+        ref other => return Err(format!("expected a table, got {}", other)),
+    };
+
+    let mut limits = unsafe { mem::zeroed::<VkPhysicalDeviceLimits>() };
+    entries
+        .into_iter()
+        .map(|(name, value)| {
+            if conv::limit_mut(&mut limits, &name).is_none() {
+                return Err(format!("unknown limit `{}`", name));
+            }
+            let value = match value {
+                toml::Value::Integer(v) => v as f64,
+                toml::Value::Float(v) => v,
+                other => return Err(format!("expected a number for `{}`, got {}", name, other)),
+            };
+            Ok((name, value))
+        })
+        .collect()
+}
+
+/// Names identifying the application, as given to `vkCreateInstance`.
+#[derive(Clone, Debug, Default)]
+pub struct Application {
+    pub name: Option<String>,
+    pub engine: Option<String>,
+}
+
+impl Application {
+    pub fn new(info: Option<&VkApplicationInfo>) -> Self {
+        fn name(ptr: *const ::std::os::raw::c_char) -> Option<String> {
+            if ptr.is_null() {
+                None
+            } else {
+                Some(unsafe { CStr::from_ptr(ptr) }.to_string_lossy().into_owned())
+            }
+        }
+        match info {
+            Some(info) => Application {
+                name: name(info.pApplicationName),
+                engine: name(info.pEngineName),
+            },
+            None => Application::default(),
+        }
+    }
+}
+
+fn parse_table(table: &toml::value::Table, settings: &mut Settings, location: &str) {
+    for (key, value) in table {
+        if let Err(e) = settings.set(key, value) {
+            error!("Ignoring {} `{}`: {}", location, key, e);
+        }
+    }
+}
+
+/// Parses the application sections of the file, returning the settings
+/// of the matching ones on top of the global settings.
+fn parse_file(text: &str, app: &Application, path: &str) -> Settings {
+    let mut settings = Settings::default();
+    let mut table = match toml::from_str::<toml::value::Table>(text) {
+        Ok(table) => table,
+        Err(e) => {
+            error!("Ignoring invalid config {}: {}", path, e);
+            return settings;
+        }
+    };
+
+    let sections = table.remove("application");
+    parse_table(&table, &mut settings, path);
+
+    let sections = match sections {
+        Some(toml::Value::Array(sections)) => sections,
+        Some(other) => {
+            error!("Ignoring {} `application`: expected an array of tables, got {}", path, other);
+            Vec::new()
+        }
+        None => Vec::new(),
+    };
+    for section in sections {
+        let mut section = match section {
+            toml::Value::Table(table) => table,
+            other => {
+                error!("Ignoring {} application: expected a table, got {}", path, other);
+                continue;
+            }
+        };
+        let name = section.remove("name");
+        let engine = section.remove("engine");
+        let matches = |pattern: Option<toml::Value>, actual: &Option<String>| match pattern {
+            None => true,
+            Some(toml::Value::String(ref pattern)) => actual
+                .as_ref()
+                .map_or(false, |actual| actual.to_lowercase().contains(&pattern.to_lowercase())),
+            Some(other) => {
+                error!("Ignoring {} application matching on {}", path, other);
+                false
+            }
+        };
+        if matches(name, &app.name) & matches(engine, &app.engine) {
+            let mut app_settings = Settings::default();
+            parse_table(&section, &mut app_settings, path);
+            settings.merge(&app_settings);
+        }
+    }
+
+    settings
+}
+
+/// Resolves the settings of an application: the file first, then the environment.
+pub fn load(app: &Application) -> Settings {
+    let mut settings = Settings::default();
+
+    let (path, explicit) = match env::var("GFX_CONFIG") {
+        Ok(path) => (path, true),
+        Err(_) => (DEFAULT_PATH.to_string(), false),
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(text) => {
+            info!("Loading config {}", path);
+            settings.merge(&parse_file(&text, app, &path));
+        }
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound && !explicit => {}
+        Err(e) => error!("Unable to read config {}: {}", path, e),
+    }
+
+    for &(var, key) in ENV_OVERRIDES {
+        if let Ok(value) = env::var(var) {
+            // kept as strings, the settings parse them according to their type
+            match settings.set(key, &toml::Value::String(value.clone())) {
+                Ok(()) => info!("GFX: {} override {:?}", key, value),
+                Err(e) => error!("Ignoring {}: {}", var, e),
+            }
+        }
+    }
+
+    if !cfg!(feature = "gfx-backend-metal") && settings.has_metal_settings() {
+        warn!("Metal specific settings are ignored by this backend");
+    }
+
+    settings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(value: &str) -> toml::Value {
+        toml::Value::String(value.to_string())
+    }
+
+    #[test]
+    fn env_adapters() {
+        let mut settings = Settings::default();
+        settings.set("adapters", &env("0")).unwrap();
+        assert_eq!(settings.adapters, vec![AdapterSelector::Index(0)]);
+
+        settings.set("adapters", &env("1, discrete,Radeon")).unwrap();
+        assert_eq!(settings.adapters, vec![
+            AdapterSelector::Index(1),
+            AdapterSelector::Type(VkPhysicalDeviceType::VK_PHYSICAL_DEVICE_TYPE_DISCRETE_GPU),
+            AdapterSelector::Name("radeon".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn env_numbers() {
+        let mut settings = Settings::default();
+        settings.set("vendor_id", &env("1")).unwrap();
+        settings.set("device_id", &env("0x10de")).unwrap();
+        settings.set("override_limits", &env("maxBoundDescriptorSets=1")).unwrap();
+        assert_eq!(settings.vendor_id, Some(1));
+        assert_eq!(settings.device_id, Some(0x10de));
+        assert_eq!(settings.limit_overrides, vec![("maxBoundDescriptorSets".to_string(), 1.0)]);
+    }
+
+    #[test]
+    fn env_booleans() {
+        let mut settings = Settings::default();
+        settings.set("threaded_submit", &env("")).unwrap();
+        assert_eq!(settings.threaded_submit, Some(true));
+        settings.set("threaded_submit", &env("0")).unwrap();
+        assert_eq!(settings.threaded_submit, Some(false));
+        assert!(settings.set("threaded_submit", &env("sometimes")).is_err());
+        settings.set("emulate_triangle_fans", &env("on")).unwrap();
+        assert_eq!(settings.emulate_triangle_fans, Some(true));
+    }
+
+    #[test]
+    fn application_sections() {
+        let text = r#"
+            threaded_submit = true
+            vendor_id = 1
+
+            [[application]]
+            name = "dota"
+            vendor_id = 2
+
+            [[application]]
+            engine = "unreal"
+            vendor_id = 3
+        "#;
+        let app = Application {
+            name: Some("Dota 2".to_string()),
+            engine: Some("Source 2".to_string()),
+        };
+        let settings = parse_file(text, &app, "test");
+        assert_eq!(settings.threaded_submit, Some(true));
+        assert_eq!(settings.vendor_id, Some(2));
+
+        let settings = parse_file(text, &Application::default(), "test");
+        assert_eq!(settings.vendor_id, Some(1));
+    }
+}
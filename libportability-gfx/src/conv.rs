@@ -1,7 +1,8 @@
-use hal::{buffer, command, error, format, image, memory, pass, pso, query, window};
+use hal::{buffer, command, error, format, image, memory, pass, pso, query, queue, window};
 use hal::{IndexType, Features, Limits, PatchSize, Primitive};
 
 use std::mem;
+use std::ops::Range;
 
 use super::*;
 
@@ -113,6 +114,311 @@ pub fn features_from_hal(features: Features) -> VkPhysicalDeviceFeatures {
     }
 }
 
+pub fn map_features(features: &VkPhysicalDeviceFeatures) -> Features {
+    fn feat(on: VkBool32, flag: Features) -> Features {
+        if on != 0 { flag } else { Features::empty() }
+    }
+    feat(features.robustBufferAccess, Features::ROBUST_BUFFER_ACCESS) |
+    feat(features.fullDrawIndexUint32, Features::FULL_DRAW_INDEX_U32) |
+    feat(features.imageCubeArray, Features::IMAGE_CUBE_ARRAY) |
+    feat(features.independentBlend, Features::INDEPENDENT_BLENDING) |
+    feat(features.geometryShader, Features::GEOMETRY_SHADER) |
+    feat(features.tessellationShader, Features::TESSELLATION_SHADER) |
+    feat(features.sampleRateShading, Features::SAMPLE_RATE_SHADING) |
+    feat(features.dualSrcBlend, Features::DUAL_SRC_BLENDING) |
+    feat(features.logicOp, Features::LOGIC_OP) |
+    feat(features.multiDrawIndirect, Features::MULTI_DRAW_INDIRECT) |
+    feat(features.drawIndirectFirstInstance, Features::DRAW_INDIRECT_FIRST_INSTANCE) |
+    feat(features.depthClamp, Features::DEPTH_CLAMP) |
+    feat(features.depthBiasClamp, Features::DEPTH_BIAS_CLAMP) |
+    feat(features.fillModeNonSolid, Features::NON_FILL_POLYGON_MODE) |
+    feat(features.depthBounds, Features::DEPTH_BOUNDS) |
+    feat(features.wideLines, Features::LINE_WIDTH) |
+    feat(features.largePoints, Features::POINT_SIZE) |
+    feat(features.alphaToOne, Features::ALPHA_TO_ONE) |
+    feat(features.multiViewport, Features::MULTI_VIEWPORTS) |
+    feat(features.samplerAnisotropy, Features::SAMPLER_ANISOTROPY) |
+    feat(features.textureCompressionETC2, Features::FORMAT_ETC2) |
+    feat(features.textureCompressionASTC_LDR, Features::FORMAT_ASTC_LDR) |
+    feat(features.textureCompressionBC, Features::FORMAT_BC) |
+    feat(features.occlusionQueryPrecise, Features::PRECISE_OCCLUSION_QUERY) |
+    feat(features.pipelineStatisticsQuery, Features::PIPELINE_STATISTICS_QUERY) |
+    feat(features.vertexPipelineStoresAndAtomics, Features::VERTEX_STORES_AND_ATOMICS) |
+    feat(features.fragmentStoresAndAtomics, Features::FRAGMENT_STORES_AND_ATOMICS) |
+    feat(features.shaderTessellationAndGeometryPointSize, Features::SHADER_TESSELLATION_AND_GEOMETRY_POINT_SIZE) |
+    feat(features.shaderImageGatherExtended, Features::SHADER_IMAGE_GATHER_EXTENDED) |
+    feat(features.shaderStorageImageExtendedFormats, Features::SHADER_STORAGE_IMAGE_EXTENDED_FORMATS) |
+    feat(features.shaderStorageImageMultisample, Features::SHADER_STORAGE_IMAGE_MULTISAMPLE) |
+    feat(features.shaderStorageImageReadWithoutFormat, Features::SHADER_STORAGE_IMAGE_READ_WITHOUT_FORMAT) |
+    feat(features.shaderStorageImageWriteWithoutFormat, Features::SHADER_STORAGE_IMAGE_WRITE_WITHOUT_FORMAT) |
+    feat(features.shaderUniformBufferArrayDynamicIndexing, Features::SHADER_UNIFORM_BUFFER_ARRAY_DYNAMIC_INDEXING) |
+    feat(features.shaderSampledImageArrayDynamicIndexing, Features::SHADER_SAMPLED_IMAGE_ARRAY_DYNAMIC_INDEXING) |
+    feat(features.shaderStorageBufferArrayDynamicIndexing, Features::SHADER_STORAGE_BUFFER_ARRAY_DYNAMIC_INDEXING) |
+    feat(features.shaderStorageImageArrayDynamicIndexing, Features::SHADER_STORAGE_IMAGE_ARRAY_DYNAMIC_INDEXING) |
+    feat(features.shaderClipDistance, Features::SHADER_CLIP_DISTANCE) |
+    feat(features.shaderCullDistance, Features::SHADER_CULL_DISTANCE) |
+    feat(features.shaderFloat64, Features::SHADER_FLOAT64) |
+    feat(features.shaderInt64, Features::SHADER_INT64) |
+    feat(features.shaderInt16, Features::SHADER_INT16) |
+    feat(features.shaderResourceResidency, Features::SHADER_RESOURCE_RESIDENCY) |
+    feat(features.shaderResourceMinLod, Features::SHADER_RESOURCE_MIN_LOD) |
+    feat(features.sparseBinding, Features::SPARSE_BINDING) |
+    feat(features.sparseResidencyBuffer, Features::SPARSE_RESIDENCY_BUFFER) |
+    feat(features.sparseResidencyImage2D, Features::SPARSE_RESIDENCY_IMAGE_2D) |
+    feat(features.sparseResidencyImage3D, Features::SPARSE_RESIDENCY_IMAGE_3D) |
+    feat(features.sparseResidency2Samples, Features::SPARSE_RESIDENCY_2_SAMPLES) |
+    feat(features.sparseResidency4Samples, Features::SPARSE_RESIDENCY_4_SAMPLES) |
+    feat(features.sparseResidency8Samples, Features::SPARSE_RESIDENCY_8_SAMPLES) |
+    feat(features.sparseResidency16Samples, Features::SPARSE_RESIDENCY_16_SAMPLES) |
+    feat(features.sparseResidencyAliased, Features::SPARSE_RESIDENCY_ALIASED) |
+    feat(features.variableMultisampleRate, Features::VARIABLE_MULTISAMPLE_RATE) |
+    feat(features.inheritedQueries, Features::INHERITED_QUERIES)
+}
+
+/// Vulkan names of the features, including the portability subset ones.
+pub const FEATURE_NAMES: &[(&str, Features)] = &[
+    ("robustBufferAccess", Features::ROBUST_BUFFER_ACCESS),
+    ("fullDrawIndexUint32", Features::FULL_DRAW_INDEX_U32),
+    ("imageCubeArray", Features::IMAGE_CUBE_ARRAY),
+    ("independentBlend", Features::INDEPENDENT_BLENDING),
+    ("geometryShader", Features::GEOMETRY_SHADER),
+    ("tessellationShader", Features::TESSELLATION_SHADER),
+    ("sampleRateShading", Features::SAMPLE_RATE_SHADING),
+    ("dualSrcBlend", Features::DUAL_SRC_BLENDING),
+    ("logicOp", Features::LOGIC_OP),
+    ("multiDrawIndirect", Features::MULTI_DRAW_INDIRECT),
+    ("drawIndirectFirstInstance", Features::DRAW_INDIRECT_FIRST_INSTANCE),
+    ("depthClamp", Features::DEPTH_CLAMP),
+    ("depthBiasClamp", Features::DEPTH_BIAS_CLAMP),
+    ("fillModeNonSolid", Features::NON_FILL_POLYGON_MODE),
+    ("depthBounds", Features::DEPTH_BOUNDS),
+    ("wideLines", Features::LINE_WIDTH),
+    ("largePoints", Features::POINT_SIZE),
+    ("alphaToOne", Features::ALPHA_TO_ONE),
+    ("multiViewport", Features::MULTI_VIEWPORTS),
+    ("samplerAnisotropy", Features::SAMPLER_ANISOTROPY),
+    ("textureCompressionETC2", Features::FORMAT_ETC2),
+    ("textureCompressionASTC_LDR", Features::FORMAT_ASTC_LDR),
+    ("textureCompressionBC", Features::FORMAT_BC),
+    ("occlusionQueryPrecise", Features::PRECISE_OCCLUSION_QUERY),
+    ("pipelineStatisticsQuery", Features::PIPELINE_STATISTICS_QUERY),
+    ("vertexPipelineStoresAndAtomics", Features::VERTEX_STORES_AND_ATOMICS),
+    ("fragmentStoresAndAtomics", Features::FRAGMENT_STORES_AND_ATOMICS),
+    ("shaderTessellationAndGeometryPointSize", Features::SHADER_TESSELLATION_AND_GEOMETRY_POINT_SIZE),
+    ("shaderImageGatherExtended", Features::SHADER_IMAGE_GATHER_EXTENDED),
+    ("shaderStorageImageExtendedFormats", Features::SHADER_STORAGE_IMAGE_EXTENDED_FORMATS),
+    ("shaderStorageImageMultisample", Features::SHADER_STORAGE_IMAGE_MULTISAMPLE),
+    ("shaderStorageImageReadWithoutFormat", Features::SHADER_STORAGE_IMAGE_READ_WITHOUT_FORMAT),
+    ("shaderStorageImageWriteWithoutFormat", Features::SHADER_STORAGE_IMAGE_WRITE_WITHOUT_FORMAT),
+    ("shaderUniformBufferArrayDynamicIndexing", Features::SHADER_UNIFORM_BUFFER_ARRAY_DYNAMIC_INDEXING),
+    ("shaderSampledImageArrayDynamicIndexing", Features::SHADER_SAMPLED_IMAGE_ARRAY_DYNAMIC_INDEXING),
+    ("shaderStorageBufferArrayDynamicIndexing", Features::SHADER_STORAGE_BUFFER_ARRAY_DYNAMIC_INDEXING),
+    ("shaderStorageImageArrayDynamicIndexing", Features::SHADER_STORAGE_IMAGE_ARRAY_DYNAMIC_INDEXING),
+    ("shaderClipDistance", Features::SHADER_CLIP_DISTANCE),
+    ("shaderCullDistance", Features::SHADER_CULL_DISTANCE),
+    ("shaderFloat64", Features::SHADER_FLOAT64),
+    ("shaderInt64", Features::SHADER_INT64),
+    ("shaderInt16", Features::SHADER_INT16),
+    ("shaderResourceResidency", Features::SHADER_RESOURCE_RESIDENCY),
+    ("shaderResourceMinLod", Features::SHADER_RESOURCE_MIN_LOD),
+    ("sparseBinding", Features::SPARSE_BINDING),
+    ("sparseResidencyBuffer", Features::SPARSE_RESIDENCY_BUFFER),
+    ("sparseResidencyImage2D", Features::SPARSE_RESIDENCY_IMAGE_2D),
+    ("sparseResidencyImage3D", Features::SPARSE_RESIDENCY_IMAGE_3D),
+    ("sparseResidency2Samples", Features::SPARSE_RESIDENCY_2_SAMPLES),
+    ("sparseResidency4Samples", Features::SPARSE_RESIDENCY_4_SAMPLES),
+    ("sparseResidency8Samples", Features::SPARSE_RESIDENCY_8_SAMPLES),
+    ("sparseResidency16Samples", Features::SPARSE_RESIDENCY_16_SAMPLES),
+    ("sparseResidencyAliased", Features::SPARSE_RESIDENCY_ALIASED),
+    ("variableMultisampleRate", Features::VARIABLE_MULTISAMPLE_RATE),
+    ("inheritedQueries", Features::INHERITED_QUERIES),
+    ("triangleFans", Features::TRIANGLE_FAN),
+    ("separateStencilMaskRef", Features::SEPARATE_STENCIL_REF_VALUES),
+    ("samplerMipLodBias", Features::SAMPLER_MIP_LOD_BIAS),
+];
+
+/// Direction in which a limit restricts the usage.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bound {
+    /// Values up to the limit are supported, e.g. `maxImageDimension2D`.
+    Max,
+    /// Values down to the limit are supported, e.g. `minTexelOffset`,
+    /// or multiples of it, e.g. `minUniformBufferOffsetAlignment`.
+    Min,
+    /// Bitmask of the supported values, e.g. `framebufferColorSampleCounts`.
+    Mask,
+}
+
+impl Bound {
+    /// Returns true if `value` supports more than `other`.
+    pub fn is_looser(&self, value: f64, other: f64) -> bool {
+        match *self {
+            Bound::Max => value > other,
+            Bound::Min => value < other,
+            Bound::Mask => value as u64 & !(other as u64) != 0,
+        }
+    }
+}
+
+/// Mutable access to a scalar limit.
+pub enum LimitMut<'a> {
+    U32(&'a mut u32),
+    I32(&'a mut i32),
+    U64(&'a mut u64),
+    Usize(&'a mut usize),
+    F32(&'a mut f32),
+}
+
+/// Returns the scalar limit with the given Vulkan name, if any, along with its direction.
+pub fn limit_mut<'a>(limits: &'a mut VkPhysicalDeviceLimits, name: &str) -> Option<(Bound, LimitMut<'a>)> {
+    Some(match name {
+        "maxImageDimension1D" => (Bound::Max, LimitMut::U32(&mut limits.maxImageDimension1D)),
+        "maxImageDimension2D" => (Bound::Max, LimitMut::U32(&mut limits.maxImageDimension2D)),
+        "maxImageDimension3D" => (Bound::Max, LimitMut::U32(&mut limits.maxImageDimension3D)),
+        "maxImageDimensionCube" => (Bound::Max, LimitMut::U32(&mut limits.maxImageDimensionCube)),
+        "maxImageArrayLayers" => (Bound::Max, LimitMut::U32(&mut limits.maxImageArrayLayers)),
+        "maxTexelBufferElements" => (Bound::Max, LimitMut::U32(&mut limits.maxTexelBufferElements)),
+        "maxUniformBufferRange" => (Bound::Max, LimitMut::U32(&mut limits.maxUniformBufferRange)),
+        "maxStorageBufferRange" => (Bound::Max, LimitMut::U32(&mut limits.maxStorageBufferRange)),
+        "maxPushConstantsSize" => (Bound::Max, LimitMut::U32(&mut limits.maxPushConstantsSize)),
+        "maxMemoryAllocationCount" => (Bound::Max, LimitMut::U32(&mut limits.maxMemoryAllocationCount)),
+        "maxSamplerAllocationCount" => (Bound::Max, LimitMut::U32(&mut limits.maxSamplerAllocationCount)),
+        "maxBoundDescriptorSets" => (Bound::Max, LimitMut::U32(&mut limits.maxBoundDescriptorSets)),
+        "maxPerStageDescriptorSamplers" => (Bound::Max, LimitMut::U32(&mut limits.maxPerStageDescriptorSamplers)),
+        "maxPerStageDescriptorUniformBuffers" => (Bound::Max, LimitMut::U32(&mut limits.maxPerStageDescriptorUniformBuffers)),
+        "maxPerStageDescriptorStorageBuffers" => (Bound::Max, LimitMut::U32(&mut limits.maxPerStageDescriptorStorageBuffers)),
+        "maxPerStageDescriptorSampledImages" => (Bound::Max, LimitMut::U32(&mut limits.maxPerStageDescriptorSampledImages)),
+        "maxPerStageDescriptorStorageImages" => (Bound::Max, LimitMut::U32(&mut limits.maxPerStageDescriptorStorageImages)),
+        "maxPerStageDescriptorInputAttachments" => (Bound::Max, LimitMut::U32(&mut limits.maxPerStageDescriptorInputAttachments)),
+        "maxPerStageResources" => (Bound::Max, LimitMut::U32(&mut limits.maxPerStageResources)),
+        "maxDescriptorSetSamplers" => (Bound::Max, LimitMut::U32(&mut limits.maxDescriptorSetSamplers)),
+        "maxDescriptorSetUniformBuffers" => (Bound::Max, LimitMut::U32(&mut limits.maxDescriptorSetUniformBuffers)),
+        "maxDescriptorSetUniformBuffersDynamic" => (Bound::Max, LimitMut::U32(&mut limits.maxDescriptorSetUniformBuffersDynamic)),
+        "maxDescriptorSetStorageBuffers" => (Bound::Max, LimitMut::U32(&mut limits.maxDescriptorSetStorageBuffers)),
+        "maxDescriptorSetStorageBuffersDynamic" => (Bound::Max, LimitMut::U32(&mut limits.maxDescriptorSetStorageBuffersDynamic)),
+        "maxDescriptorSetSampledImages" => (Bound::Max, LimitMut::U32(&mut limits.maxDescriptorSetSampledImages)),
+        "maxDescriptorSetStorageImages" => (Bound::Max, LimitMut::U32(&mut limits.maxDescriptorSetStorageImages)),
+        "maxDescriptorSetInputAttachments" => (Bound::Max, LimitMut::U32(&mut limits.maxDescriptorSetInputAttachments)),
+        "maxVertexInputAttributes" => (Bound::Max, LimitMut::U32(&mut limits.maxVertexInputAttributes)),
+        "maxVertexInputBindings" => (Bound::Max, LimitMut::U32(&mut limits.maxVertexInputBindings)),
+        "maxVertexInputAttributeOffset" => (Bound::Max, LimitMut::U32(&mut limits.maxVertexInputAttributeOffset)),
+        "maxVertexInputBindingStride" => (Bound::Max, LimitMut::U32(&mut limits.maxVertexInputBindingStride)),
+        "maxVertexOutputComponents" => (Bound::Max, LimitMut::U32(&mut limits.maxVertexOutputComponents)),
+        "maxTessellationGenerationLevel" => (Bound::Max, LimitMut::U32(&mut limits.maxTessellationGenerationLevel)),
+        "maxTessellationPatchSize" => (Bound::Max, LimitMut::U32(&mut limits.maxTessellationPatchSize)),
+        "maxTessellationControlPerVertexInputComponents" => (Bound::Max, LimitMut::U32(&mut limits.maxTessellationControlPerVertexInputComponents)),
+        "maxTessellationControlPerVertexOutputComponents" => (Bound::Max, LimitMut::U32(&mut limits.maxTessellationControlPerVertexOutputComponents)),
+        "maxTessellationControlPerPatchOutputComponents" => (Bound::Max, LimitMut::U32(&mut limits.maxTessellationControlPerPatchOutputComponents)),
+        "maxTessellationControlTotalOutputComponents" => (Bound::Max, LimitMut::U32(&mut limits.maxTessellationControlTotalOutputComponents)),
+        "maxTessellationEvaluationInputComponents" => (Bound::Max, LimitMut::U32(&mut limits.maxTessellationEvaluationInputComponents)),
+        "maxTessellationEvaluationOutputComponents" => (Bound::Max, LimitMut::U32(&mut limits.maxTessellationEvaluationOutputComponents)),
+        "maxGeometryShaderInvocations" => (Bound::Max, LimitMut::U32(&mut limits.maxGeometryShaderInvocations)),
+        "maxGeometryInputComponents" => (Bound::Max, LimitMut::U32(&mut limits.maxGeometryInputComponents)),
+        "maxGeometryOutputComponents" => (Bound::Max, LimitMut::U32(&mut limits.maxGeometryOutputComponents)),
+        "maxGeometryOutputVertices" => (Bound::Max, LimitMut::U32(&mut limits.maxGeometryOutputVertices)),
+        "maxGeometryTotalOutputComponents" => (Bound::Max, LimitMut::U32(&mut limits.maxGeometryTotalOutputComponents)),
+        "maxFragmentInputComponents" => (Bound::Max, LimitMut::U32(&mut limits.maxFragmentInputComponents)),
+        "maxFragmentOutputAttachments" => (Bound::Max, LimitMut::U32(&mut limits.maxFragmentOutputAttachments)),
+        "maxFragmentDualSrcAttachments" => (Bound::Max, LimitMut::U32(&mut limits.maxFragmentDualSrcAttachments)),
+        "maxFragmentCombinedOutputResources" => (Bound::Max, LimitMut::U32(&mut limits.maxFragmentCombinedOutputResources)),
+        "maxComputeSharedMemorySize" => (Bound::Max, LimitMut::U32(&mut limits.maxComputeSharedMemorySize)),
+        "maxComputeWorkGroupInvocations" => (Bound::Max, LimitMut::U32(&mut limits.maxComputeWorkGroupInvocations)),
+        "subPixelPrecisionBits" => (Bound::Max, LimitMut::U32(&mut limits.subPixelPrecisionBits)),
+        "subTexelPrecisionBits" => (Bound::Max, LimitMut::U32(&mut limits.subTexelPrecisionBits)),
+        "mipmapPrecisionBits" => (Bound::Max, LimitMut::U32(&mut limits.mipmapPrecisionBits)),
+        "maxDrawIndexedIndexValue" => (Bound::Max, LimitMut::U32(&mut limits.maxDrawIndexedIndexValue)),
+        "maxDrawIndirectCount" => (Bound::Max, LimitMut::U32(&mut limits.maxDrawIndirectCount)),
+        "maxViewports" => (Bound::Max, LimitMut::U32(&mut limits.maxViewports)),
+        "viewportSubPixelBits" => (Bound::Max, LimitMut::U32(&mut limits.viewportSubPixelBits)),
+        "maxTexelOffset" => (Bound::Max, LimitMut::U32(&mut limits.maxTexelOffset)),
+        "maxTexelGatherOffset" => (Bound::Max, LimitMut::U32(&mut limits.maxTexelGatherOffset)),
+        "subPixelInterpolationOffsetBits" => (Bound::Max, LimitMut::U32(&mut limits.subPixelInterpolationOffsetBits)),
+        "maxFramebufferWidth" => (Bound::Max, LimitMut::U32(&mut limits.maxFramebufferWidth)),
+        "maxFramebufferHeight" => (Bound::Max, LimitMut::U32(&mut limits.maxFramebufferHeight)),
+        "maxFramebufferLayers" => (Bound::Max, LimitMut::U32(&mut limits.maxFramebufferLayers)),
+        "framebufferColorSampleCounts" => (Bound::Mask, LimitMut::U32(&mut limits.framebufferColorSampleCounts)),
+        "framebufferDepthSampleCounts" => (Bound::Mask, LimitMut::U32(&mut limits.framebufferDepthSampleCounts)),
+        "framebufferStencilSampleCounts" => (Bound::Mask, LimitMut::U32(&mut limits.framebufferStencilSampleCounts)),
+        "framebufferNoAttachmentsSampleCounts" => (Bound::Mask, LimitMut::U32(&mut limits.framebufferNoAttachmentsSampleCounts)),
+        "maxColorAttachments" => (Bound::Max, LimitMut::U32(&mut limits.maxColorAttachments)),
+        "sampledImageColorSampleCounts" => (Bound::Mask, LimitMut::U32(&mut limits.sampledImageColorSampleCounts)),
+        "sampledImageIntegerSampleCounts" => (Bound::Mask, LimitMut::U32(&mut limits.sampledImageIntegerSampleCounts)),
+        "sampledImageDepthSampleCounts" => (Bound::Mask, LimitMut::U32(&mut limits.sampledImageDepthSampleCounts)),
+        "sampledImageStencilSampleCounts" => (Bound::Mask, LimitMut::U32(&mut limits.sampledImageStencilSampleCounts)),
+        "storageImageSampleCounts" => (Bound::Mask, LimitMut::U32(&mut limits.storageImageSampleCounts)),
+        "maxSampleMaskWords" => (Bound::Max, LimitMut::U32(&mut limits.maxSampleMaskWords)),
+        "timestampComputeAndGraphics" => (Bound::Max, LimitMut::U32(&mut limits.timestampComputeAndGraphics)),
+        "maxClipDistances" => (Bound::Max, LimitMut::U32(&mut limits.maxClipDistances)),
+        "maxCullDistances" => (Bound::Max, LimitMut::U32(&mut limits.maxCullDistances)),
+        "maxCombinedClipAndCullDistances" => (Bound::Max, LimitMut::U32(&mut limits.maxCombinedClipAndCullDistances)),
+        "discreteQueuePriorities" => (Bound::Max, LimitMut::U32(&mut limits.discreteQueuePriorities)),
+        "strictLines" => (Bound::Max, LimitMut::U32(&mut limits.strictLines)),
+        "standardSampleLocations" => (Bound::Max, LimitMut::U32(&mut limits.standardSampleLocations)),
+        "minTexelOffset" => (Bound::Min, LimitMut::I32(&mut limits.minTexelOffset)),
+        "minTexelGatherOffset" => (Bound::Min, LimitMut::I32(&mut limits.minTexelGatherOffset)),
+        "bufferImageGranularity" => (Bound::Min, LimitMut::U64(&mut limits.bufferImageGranularity)),
+        "sparseAddressSpaceSize" => (Bound::Max, LimitMut::U64(&mut limits.sparseAddressSpaceSize)),
+        "minTexelBufferOffsetAlignment" => (Bound::Min, LimitMut::U64(&mut limits.minTexelBufferOffsetAlignment)),
+        "minUniformBufferOffsetAlignment" => (Bound::Min, LimitMut::U64(&mut limits.minUniformBufferOffsetAlignment)),
+        "minStorageBufferOffsetAlignment" => (Bound::Min, LimitMut::U64(&mut limits.minStorageBufferOffsetAlignment)),
+        "optimalBufferCopyOffsetAlignment" => (Bound::Min, LimitMut::U64(&mut limits.optimalBufferCopyOffsetAlignment)),
+        "optimalBufferCopyRowPitchAlignment" => (Bound::Min, LimitMut::U64(&mut limits.optimalBufferCopyRowPitchAlignment)),
+        "nonCoherentAtomSize" => (Bound::Min, LimitMut::U64(&mut limits.nonCoherentAtomSize)),
+        "minMemoryMapAlignment" => (Bound::Min, LimitMut::Usize(&mut limits.minMemoryMapAlignment)),
+        "maxSamplerLodBias" => (Bound::Max, LimitMut::F32(&mut limits.maxSamplerLodBias)),
+        "maxSamplerAnisotropy" => (Bound::Max, LimitMut::F32(&mut limits.maxSamplerAnisotropy)),
+        "minInterpolationOffset" => (Bound::Min, LimitMut::F32(&mut limits.minInterpolationOffset)),
+        "maxInterpolationOffset" => (Bound::Max, LimitMut::F32(&mut limits.maxInterpolationOffset)),
+        "timestampPeriod" => (Bound::Min, LimitMut::F32(&mut limits.timestampPeriod)),
+        "pointSizeGranularity" => (Bound::Min, LimitMut::F32(&mut limits.pointSizeGranularity)),
+        "lineWidthGranularity" => (Bound::Min, LimitMut::F32(&mut limits.lineWidthGranularity)),
+        _ => return None,
+    })
+}
+
+impl<'a> LimitMut<'a> {
+    /// Restricts the limit to `value`, if it's more permissive.
+    pub fn clamp(self, bound: Bound, value: f64) {
+        let current = self.get();
+        match bound {
+            Bound::Mask => self.set((current as u64 & value as u64) as f64),
+            _ if bound.is_looser(value, current) => {}
+            _ => self.set(value),
+        }
+    }
+
+    pub fn get(&self) -> f64 {
+        match *self {
+            LimitMut::U32(ref limit) => **limit as f64,
+            LimitMut::I32(ref limit) => **limit as f64,
+            LimitMut::U64(ref limit) => **limit as f64,
+            LimitMut::Usize(ref limit) => **limit as f64,
+            LimitMut::F32(ref limit) => **limit as f64,
+        }
+    }
+
+    pub fn set(self, value: f64) {
+        match self {
+            LimitMut::U32(limit) => *limit = value as u32,
+            LimitMut::I32(limit) => *limit = value as i32,
+            LimitMut::U64(limit) => *limit = value as u64,
+            LimitMut::Usize(limit) => *limit = value as usize,
+            LimitMut::F32(limit) => *limit = value as f32,
+        }
+    }
+}
+
+pub fn device_type_from_hal(device_type: &hal::adapter::DeviceType) -> VkPhysicalDeviceType {
+    use hal::adapter::DeviceType;
+    match *device_type {
+        DeviceType::IntegratedGpu => VkPhysicalDeviceType::VK_PHYSICAL_DEVICE_TYPE_INTEGRATED_GPU,
+        DeviceType::DiscreteGpu => VkPhysicalDeviceType::VK_PHYSICAL_DEVICE_TYPE_DISCRETE_GPU,
+        DeviceType::VirtualGpu => VkPhysicalDeviceType::VK_PHYSICAL_DEVICE_TYPE_VIRTUAL_GPU,
+        DeviceType::Other => VkPhysicalDeviceType::VK_PHYSICAL_DEVICE_TYPE_OTHER,
+        DeviceType::Cpu => VkPhysicalDeviceType::VK_PHYSICAL_DEVICE_TYPE_CPU,
+    }
+}
+
 #[inline]
 pub fn format_from_hal(format: format::Format) -> VkFormat {
     // HAL formats have the same numeric representation as Vulkan formats
@@ -190,15 +496,205 @@ fn buffer_features_from_hal(features: format::BufferFeature) -> VkFormatFeatureF
     flags
 }
 
+/// Maps a Vulkan format to the HAL one, if there is any.
+///
+/// Returns `None` for `VK_FORMAT_UNDEFINED` and for the formats HAL doesn't know,
+/// which are then reported as not supported.
 pub fn map_format(format: VkFormat) -> Option<format::Format> {
-    if format == VkFormat::VK_FORMAT_UNDEFINED {
-        None
-    } else if (format as usize) < format::NUM_FORMATS {
-        // HAL formats have the same numeric representation as Vulkan formats
-        Some(unsafe { mem::transmute(format) })
-    } else {
-        unimplemented!("Unknown format {:?}", format);
-    }
+    use super::VkFormat::*;
+    use hal::format::Format as F;
+
+    Some(match format {
+        VK_FORMAT_R4G4_UNORM_PACK8 => F::Rg4Unorm,
+        VK_FORMAT_R4G4B4A4_UNORM_PACK16 => F::Rgba4Unorm,
+        VK_FORMAT_B4G4R4A4_UNORM_PACK16 => F::Bgra4Unorm,
+        VK_FORMAT_R5G6B5_UNORM_PACK16 => F::R5g6b5Unorm,
+        VK_FORMAT_B5G6R5_UNORM_PACK16 => F::B5g6r5Unorm,
+        VK_FORMAT_R5G5B5A1_UNORM_PACK16 => F::R5g5b5a1Unorm,
+        VK_FORMAT_B5G5R5A1_UNORM_PACK16 => F::B5g5r5a1Unorm,
+        VK_FORMAT_A1R5G5B5_UNORM_PACK16 => F::A1r5g5b5Unorm,
+        VK_FORMAT_R8_UNORM => F::R8Unorm,
+        VK_FORMAT_R8_SNORM => F::R8Snorm,
+        VK_FORMAT_R8_USCALED => F::R8Uscaled,
+        VK_FORMAT_R8_SSCALED => F::R8Sscaled,
+        VK_FORMAT_R8_UINT => F::R8Uint,
+        VK_FORMAT_R8_SINT => F::R8Sint,
+        VK_FORMAT_R8_SRGB => F::R8Srgb,
+        VK_FORMAT_R8G8_UNORM => F::Rg8Unorm,
+        VK_FORMAT_R8G8_SNORM => F::Rg8Snorm,
+        VK_FORMAT_R8G8_USCALED => F::Rg8Uscaled,
+        VK_FORMAT_R8G8_SSCALED => F::Rg8Sscaled,
+        VK_FORMAT_R8G8_UINT => F::Rg8Uint,
+        VK_FORMAT_R8G8_SINT => F::Rg8Sint,
+        VK_FORMAT_R8G8_SRGB => F::Rg8Srgb,
+        VK_FORMAT_R8G8B8_UNORM => F::Rgb8Unorm,
+        VK_FORMAT_R8G8B8_SNORM => F::Rgb8Snorm,
+        VK_FORMAT_R8G8B8_USCALED => F::Rgb8Uscaled,
+        VK_FORMAT_R8G8B8_SSCALED => F::Rgb8Sscaled,
+        VK_FORMAT_R8G8B8_UINT => F::Rgb8Uint,
+        VK_FORMAT_R8G8B8_SINT => F::Rgb8Sint,
+        VK_FORMAT_R8G8B8_SRGB => F::Rgb8Srgb,
+        VK_FORMAT_B8G8R8_UNORM => F::Bgr8Unorm,
+        VK_FORMAT_B8G8R8_SNORM => F::Bgr8Snorm,
+        VK_FORMAT_B8G8R8_USCALED => F::Bgr8Uscaled,
+        VK_FORMAT_B8G8R8_SSCALED => F::Bgr8Sscaled,
+        VK_FORMAT_B8G8R8_UINT => F::Bgr8Uint,
+        VK_FORMAT_B8G8R8_SINT => F::Bgr8Sint,
+        VK_FORMAT_B8G8R8_SRGB => F::Bgr8Srgb,
+        VK_FORMAT_R8G8B8A8_UNORM => F::Rgba8Unorm,
+        VK_FORMAT_R8G8B8A8_SNORM => F::Rgba8Snorm,
+        VK_FORMAT_R8G8B8A8_USCALED => F::Rgba8Uscaled,
+        VK_FORMAT_R8G8B8A8_SSCALED => F::Rgba8Sscaled,
+        VK_FORMAT_R8G8B8A8_UINT => F::Rgba8Uint,
+        VK_FORMAT_R8G8B8A8_SINT => F::Rgba8Sint,
+        VK_FORMAT_R8G8B8A8_SRGB => F::Rgba8Srgb,
+        VK_FORMAT_B8G8R8A8_UNORM => F::Bgra8Unorm,
+        VK_FORMAT_B8G8R8A8_SNORM => F::Bgra8Snorm,
+        VK_FORMAT_B8G8R8A8_USCALED => F::Bgra8Uscaled,
+        VK_FORMAT_B8G8R8A8_SSCALED => F::Bgra8Sscaled,
+        VK_FORMAT_B8G8R8A8_UINT => F::Bgra8Uint,
+        VK_FORMAT_B8G8R8A8_SINT => F::Bgra8Sint,
+        VK_FORMAT_B8G8R8A8_SRGB => F::Bgra8Srgb,
+        VK_FORMAT_A8B8G8R8_UNORM_PACK32 => F::Abgr8Unorm,
+        VK_FORMAT_A8B8G8R8_SNORM_PACK32 => F::Abgr8Snorm,
+        VK_FORMAT_A8B8G8R8_USCALED_PACK32 => F::Abgr8Uscaled,
+        VK_FORMAT_A8B8G8R8_SSCALED_PACK32 => F::Abgr8Sscaled,
+        VK_FORMAT_A8B8G8R8_UINT_PACK32 => F::Abgr8Uint,
+        VK_FORMAT_A8B8G8R8_SINT_PACK32 => F::Abgr8Sint,
+        VK_FORMAT_A8B8G8R8_SRGB_PACK32 => F::Abgr8Srgb,
+        VK_FORMAT_A2R10G10B10_UNORM_PACK32 => F::A2r10g10b10Unorm,
+        VK_FORMAT_A2R10G10B10_SNORM_PACK32 => F::A2r10g10b10Snorm,
+        VK_FORMAT_A2R10G10B10_USCALED_PACK32 => F::A2r10g10b10Uscaled,
+        VK_FORMAT_A2R10G10B10_SSCALED_PACK32 => F::A2r10g10b10Sscaled,
+        VK_FORMAT_A2R10G10B10_UINT_PACK32 => F::A2r10g10b10Uint,
+        VK_FORMAT_A2R10G10B10_SINT_PACK32 => F::A2r10g10b10Sint,
+        VK_FORMAT_A2B10G10R10_UNORM_PACK32 => F::A2b10g10r10Unorm,
+        VK_FORMAT_A2B10G10R10_SNORM_PACK32 => F::A2b10g10r10Snorm,
+        VK_FORMAT_A2B10G10R10_USCALED_PACK32 => F::A2b10g10r10Uscaled,
+        VK_FORMAT_A2B10G10R10_SSCALED_PACK32 => F::A2b10g10r10Sscaled,
+        VK_FORMAT_A2B10G10R10_UINT_PACK32 => F::A2b10g10r10Uint,
+        VK_FORMAT_A2B10G10R10_SINT_PACK32 => F::A2b10g10r10Sint,
+        VK_FORMAT_R16_UNORM => F::R16Unorm,
+        VK_FORMAT_R16_SNORM => F::R16Snorm,
+        VK_FORMAT_R16_USCALED => F::R16Uscaled,
+        VK_FORMAT_R16_SSCALED => F::R16Sscaled,
+        VK_FORMAT_R16_UINT => F::R16Uint,
+        VK_FORMAT_R16_SINT => F::R16Sint,
+        VK_FORMAT_R16_SFLOAT => F::R16Sfloat,
+        VK_FORMAT_R16G16_UNORM => F::Rg16Unorm,
+        VK_FORMAT_R16G16_SNORM => F::Rg16Snorm,
+        VK_FORMAT_R16G16_USCALED => F::Rg16Uscaled,
+        VK_FORMAT_R16G16_SSCALED => F::Rg16Sscaled,
+        VK_FORMAT_R16G16_UINT => F::Rg16Uint,
+        VK_FORMAT_R16G16_SINT => F::Rg16Sint,
+        VK_FORMAT_R16G16_SFLOAT => F::Rg16Sfloat,
+        VK_FORMAT_R16G16B16_UNORM => F::Rgb16Unorm,
+        VK_FORMAT_R16G16B16_SNORM => F::Rgb16Snorm,
+        VK_FORMAT_R16G16B16_USCALED => F::Rgb16Uscaled,
+        VK_FORMAT_R16G16B16_SSCALED => F::Rgb16Sscaled,
+        VK_FORMAT_R16G16B16_UINT => F::Rgb16Uint,
+        VK_FORMAT_R16G16B16_SINT => F::Rgb16Sint,
+        VK_FORMAT_R16G16B16_SFLOAT => F::Rgb16Sfloat,
+        VK_FORMAT_R16G16B16A16_UNORM => F::Rgba16Unorm,
+        VK_FORMAT_R16G16B16A16_SNORM => F::Rgba16Snorm,
+        VK_FORMAT_R16G16B16A16_USCALED => F::Rgba16Uscaled,
+        VK_FORMAT_R16G16B16A16_SSCALED => F::Rgba16Sscaled,
+        VK_FORMAT_R16G16B16A16_UINT => F::Rgba16Uint,
+        VK_FORMAT_R16G16B16A16_SINT => F::Rgba16Sint,
+        VK_FORMAT_R16G16B16A16_SFLOAT => F::Rgba16Sfloat,
+        VK_FORMAT_R32_UINT => F::R32Uint,
+        VK_FORMAT_R32_SINT => F::R32Sint,
+        VK_FORMAT_R32_SFLOAT => F::R32Sfloat,
+        VK_FORMAT_R32G32_UINT => F::Rg32Uint,
+        VK_FORMAT_R32G32_SINT => F::Rg32Sint,
+        VK_FORMAT_R32G32_SFLOAT => F::Rg32Sfloat,
+        VK_FORMAT_R32G32B32_UINT => F::Rgb32Uint,
+        VK_FORMAT_R32G32B32_SINT => F::Rgb32Sint,
+        VK_FORMAT_R32G32B32_SFLOAT => F::Rgb32Sfloat,
+        VK_FORMAT_R32G32B32A32_UINT => F::Rgba32Uint,
+        VK_FORMAT_R32G32B32A32_SINT => F::Rgba32Sint,
+        VK_FORMAT_R32G32B32A32_SFLOAT => F::Rgba32Sfloat,
+        VK_FORMAT_R64_UINT => F::R64Uint,
+        VK_FORMAT_R64_SINT => F::R64Sint,
+        VK_FORMAT_R64_SFLOAT => F::R64Sfloat,
+        VK_FORMAT_R64G64_UINT => F::Rg64Uint,
+        VK_FORMAT_R64G64_SINT => F::Rg64Sint,
+        VK_FORMAT_R64G64_SFLOAT => F::Rg64Sfloat,
+        VK_FORMAT_R64G64B64_UINT => F::Rgb64Uint,
+        VK_FORMAT_R64G64B64_SINT => F::Rgb64Sint,
+        VK_FORMAT_R64G64B64_SFLOAT => F::Rgb64Sfloat,
+        VK_FORMAT_R64G64B64A64_UINT => F::Rgba64Uint,
+        VK_FORMAT_R64G64B64A64_SINT => F::Rgba64Sint,
+        VK_FORMAT_R64G64B64A64_SFLOAT => F::Rgba64Sfloat,
+        VK_FORMAT_B10G11R11_UFLOAT_PACK32 => F::B10g11r11Ufloat,
+        VK_FORMAT_E5B9G9R9_UFLOAT_PACK32 => F::E5b9g9r9Ufloat,
+        VK_FORMAT_D16_UNORM => F::D16Unorm,
+        VK_FORMAT_X8_D24_UNORM_PACK32 => F::X8D24Unorm,
+        VK_FORMAT_D32_SFLOAT => F::D32Sfloat,
+        VK_FORMAT_S8_UINT => F::S8Uint,
+        VK_FORMAT_D16_UNORM_S8_UINT => F::D16UnormS8Uint,
+        VK_FORMAT_D24_UNORM_S8_UINT => F::D24UnormS8Uint,
+        VK_FORMAT_D32_SFLOAT_S8_UINT => F::D32SfloatS8Uint,
+        VK_FORMAT_BC1_RGB_UNORM_BLOCK => F::Bc1RgbUnorm,
+        VK_FORMAT_BC1_RGB_SRGB_BLOCK => F::Bc1RgbSrgb,
+        VK_FORMAT_BC1_RGBA_UNORM_BLOCK => F::Bc1RgbaUnorm,
+        VK_FORMAT_BC1_RGBA_SRGB_BLOCK => F::Bc1RgbaSrgb,
+        VK_FORMAT_BC2_UNORM_BLOCK => F::Bc2Unorm,
+        VK_FORMAT_BC2_SRGB_BLOCK => F::Bc2Srgb,
+        VK_FORMAT_BC3_UNORM_BLOCK => F::Bc3Unorm,
+        VK_FORMAT_BC3_SRGB_BLOCK => F::Bc3Srgb,
+        VK_FORMAT_BC4_UNORM_BLOCK => F::Bc4Unorm,
+        VK_FORMAT_BC4_SNORM_BLOCK => F::Bc4Snorm,
+        VK_FORMAT_BC5_UNORM_BLOCK => F::Bc5Unorm,
+        VK_FORMAT_BC5_SNORM_BLOCK => F::Bc5Snorm,
+        VK_FORMAT_BC6H_UFLOAT_BLOCK => F::Bc6hUfloat,
+        VK_FORMAT_BC6H_SFLOAT_BLOCK => F::Bc6hSfloat,
+        VK_FORMAT_BC7_UNORM_BLOCK => F::Bc7Unorm,
+        VK_FORMAT_BC7_SRGB_BLOCK => F::Bc7Srgb,
+        VK_FORMAT_ETC2_R8G8B8_UNORM_BLOCK => F::Etc2R8g8b8Unorm,
+        VK_FORMAT_ETC2_R8G8B8_SRGB_BLOCK => F::Etc2R8g8b8Srgb,
+        VK_FORMAT_ETC2_R8G8B8A1_UNORM_BLOCK => F::Etc2R8g8b8a1Unorm,
+        VK_FORMAT_ETC2_R8G8B8A1_SRGB_BLOCK => F::Etc2R8g8b8a1Srgb,
+        VK_FORMAT_ETC2_R8G8B8A8_UNORM_BLOCK => F::Etc2R8g8b8a8Unorm,
+        VK_FORMAT_ETC2_R8G8B8A8_SRGB_BLOCK => F::Etc2R8g8b8a8Srgb,
+        VK_FORMAT_EAC_R11_UNORM_BLOCK => F::EacR11Unorm,
+        VK_FORMAT_EAC_R11_SNORM_BLOCK => F::EacR11Snorm,
+        VK_FORMAT_EAC_R11G11_UNORM_BLOCK => F::EacR11g11Unorm,
+        VK_FORMAT_EAC_R11G11_SNORM_BLOCK => F::EacR11g11Snorm,
+        VK_FORMAT_ASTC_4x4_UNORM_BLOCK => F::Astc4x4Unorm,
+        VK_FORMAT_ASTC_4x4_SRGB_BLOCK => F::Astc4x4Srgb,
+        VK_FORMAT_ASTC_5x4_UNORM_BLOCK => F::Astc5x4Unorm,
+        VK_FORMAT_ASTC_5x4_SRGB_BLOCK => F::Astc5x4Srgb,
+        VK_FORMAT_ASTC_5x5_UNORM_BLOCK => F::Astc5x5Unorm,
+        VK_FORMAT_ASTC_5x5_SRGB_BLOCK => F::Astc5x5Srgb,
+        VK_FORMAT_ASTC_6x5_UNORM_BLOCK => F::Astc6x5Unorm,
+        VK_FORMAT_ASTC_6x5_SRGB_BLOCK => F::Astc6x5Srgb,
+        VK_FORMAT_ASTC_6x6_UNORM_BLOCK => F::Astc6x6Unorm,
+        VK_FORMAT_ASTC_6x6_SRGB_BLOCK => F::Astc6x6Srgb,
+        VK_FORMAT_ASTC_8x5_UNORM_BLOCK => F::Astc8x5Unorm,
+        VK_FORMAT_ASTC_8x5_SRGB_BLOCK => F::Astc8x5Srgb,
+        VK_FORMAT_ASTC_8x6_UNORM_BLOCK => F::Astc8x6Unorm,
+        VK_FORMAT_ASTC_8x6_SRGB_BLOCK => F::Astc8x6Srgb,
+        VK_FORMAT_ASTC_8x8_UNORM_BLOCK => F::Astc8x8Unorm,
+        VK_FORMAT_ASTC_8x8_SRGB_BLOCK => F::Astc8x8Srgb,
+        VK_FORMAT_ASTC_10x5_UNORM_BLOCK => F::Astc10x5Unorm,
+        VK_FORMAT_ASTC_10x5_SRGB_BLOCK => F::Astc10x5Srgb,
+        VK_FORMAT_ASTC_10x6_UNORM_BLOCK => F::Astc10x6Unorm,
+        VK_FORMAT_ASTC_10x6_SRGB_BLOCK => F::Astc10x6Srgb,
+        VK_FORMAT_ASTC_10x8_UNORM_BLOCK => F::Astc10x8Unorm,
+        VK_FORMAT_ASTC_10x8_SRGB_BLOCK => F::Astc10x8Srgb,
+        VK_FORMAT_ASTC_10x10_UNORM_BLOCK => F::Astc10x10Unorm,
+        VK_FORMAT_ASTC_10x10_SRGB_BLOCK => F::Astc10x10Srgb,
+        VK_FORMAT_ASTC_12x10_UNORM_BLOCK => F::Astc12x10Unorm,
+        VK_FORMAT_ASTC_12x10_SRGB_BLOCK => F::Astc12x10Srgb,
+        VK_FORMAT_ASTC_12x12_UNORM_BLOCK => F::Astc12x12Unorm,
+        VK_FORMAT_ASTC_12x12_SRGB_BLOCK => F::Astc12x12Srgb,
+        VK_FORMAT_UNDEFINED => return None,
+        _ => {
+            debug!("Format {} is not supported", format as u32);
+            return None;
+        }
+    })
 }
 
 pub fn extent2d_from_hal(extent: window::Extent2D) -> VkExtent2D {
@@ -523,6 +1019,28 @@ pub fn map_pipeline_stage_flags(stages: VkPipelineStageFlags) -> pso::PipelineSt
     }
 }
 
+/// Returns the ownership transfer described by a barrier, if any.
+pub fn map_queue_family_transfer(src: u32, dst: u32) -> Option<Range<queue::QueueFamilyId>> {
+    let ignored = VK_QUEUE_FAMILY_IGNORED as u32;
+    if src == ignored || dst == ignored || src == dst {
+        None
+    } else {
+        Some(queue::QueueFamilyId(src as _) .. queue::QueueFamilyId(dst as _))
+    }
+}
+
+pub fn map_present_error(err: window::PresentError) -> VkResult {
+    use hal::device::OutOfMemory::{OutOfDeviceMemory, OutOfHostMemory};
+
+    match err {
+        window::PresentError::OutOfMemory(OutOfHostMemory) => VkResult::VK_ERROR_OUT_OF_HOST_MEMORY,
+        window::PresentError::OutOfMemory(OutOfDeviceMemory) => VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY,
+        window::PresentError::OutOfDate => VkResult::VK_ERROR_OUT_OF_DATE_KHR,
+        window::PresentError::SurfaceLost(_) => VkResult::VK_ERROR_SURFACE_LOST_KHR,
+        window::PresentError::DeviceLost(_) => VkResult::VK_ERROR_DEVICE_LOST,
+    }
+}
+
 pub fn map_err_device_creation(err: error::DeviceCreationError) -> VkResult {
     use hal::error::DeviceCreationError::*;
 
@@ -600,6 +1118,18 @@ pub fn map_front_face(face: VkFrontFace) -> pso::FrontFace {
     }
 }
 
+/// Combines the 32-bit words of a sample mask, covering all samples if there is none.
+pub fn map_sample_mask(samples: VkSampleCountFlagBits, mask: *const VkSampleMask) -> pso::SampleMask {
+    if mask.is_null() {
+        return !0;
+    }
+    let words = (samples as usize + 31) / 32;
+    unsafe { slice::from_raw_parts(mask, words) }
+        .iter()
+        .enumerate()
+        .fold(0, |sample_mask, (i, &word)| sample_mask | (word as pso::SampleMask) << (32 * i))
+}
+
 pub fn map_primitive_topology(topology: VkPrimitiveTopology, patch_size: PatchSize) -> Option<Primitive> {
     use super::VkPrimitiveTopology::*;
 
@@ -609,7 +1139,7 @@ pub fn map_primitive_topology(topology: VkPrimitiveTopology, patch_size: PatchSi
         VK_PRIMITIVE_TOPOLOGY_LINE_STRIP => Primitive::LineStrip,
         VK_PRIMITIVE_TOPOLOGY_TRIANGLE_LIST => Primitive::TriangleList,
         VK_PRIMITIVE_TOPOLOGY_TRIANGLE_STRIP => Primitive::TriangleStrip,
-        VK_PRIMITIVE_TOPOLOGY_TRIANGLE_FAN => return None,
+        VK_PRIMITIVE_TOPOLOGY_TRIANGLE_FAN => Primitive::TriangleFan,
         VK_PRIMITIVE_TOPOLOGY_LINE_LIST_WITH_ADJACENCY => Primitive::LineListAdjacency,
         VK_PRIMITIVE_TOPOLOGY_LINE_STRIP_WITH_ADJACENCY => Primitive::LineStripAdjacency,
         VK_PRIMITIVE_TOPOLOGY_TRIANGLE_LIST_WITH_ADJACENCY => Primitive::TriangleListAdjacency,
@@ -724,18 +1254,33 @@ pub fn map_mipmap_filter(mode: VkSamplerMipmapMode) -> image::Filter {
     }
 }
 
-pub fn map_wrap_mode(mode: VkSamplerAddressMode) -> image::WrapMode {
+/// Returns `None` for `VK_SAMPLER_ADDRESS_MODE_MIRROR_CLAMP_TO_EDGE`, which gfx-hal
+/// has no wrap mode for, so `VK_KHR_sampler_mirror_clamp_to_edge` isn't exposed.
+pub fn map_wrap_mode(mode: VkSamplerAddressMode) -> Option<image::WrapMode> {
     use super::VkSamplerAddressMode::*;
-    match mode {
+    Some(match mode {
         VK_SAMPLER_ADDRESS_MODE_REPEAT => image::WrapMode::Tile,
         VK_SAMPLER_ADDRESS_MODE_MIRRORED_REPEAT => image::WrapMode::Mirror,
         VK_SAMPLER_ADDRESS_MODE_CLAMP_TO_EDGE => image::WrapMode::Clamp,
         VK_SAMPLER_ADDRESS_MODE_CLAMP_TO_BORDER => image::WrapMode::Border,
-        _ => {
-            warn!("Non-covered sampler address mode: {:?}", mode);
-            image::WrapMode::Clamp
-        }
-    }
+        _ => return None,
+    })
+}
+
+/// Border colors are packed the same way for float and integer formats, so
+/// the integer variants map to the same values. Returns `None` for unknown colors.
+pub fn map_border_color(border_color: VkBorderColor) -> Option<image::PackedColor> {
+    use super::VkBorderColor::*;
+    let color = match border_color {
+        VK_BORDER_COLOR_FLOAT_TRANSPARENT_BLACK |
+        VK_BORDER_COLOR_INT_TRANSPARENT_BLACK => [0.0, 0.0, 0.0, 0.0],
+        VK_BORDER_COLOR_FLOAT_OPAQUE_BLACK |
+        VK_BORDER_COLOR_INT_OPAQUE_BLACK => [0.0, 0.0, 0.0, 1.0],
+        VK_BORDER_COLOR_FLOAT_OPAQUE_WHITE |
+        VK_BORDER_COLOR_INT_OPAQUE_WHITE => [1.0, 1.0, 1.0, 1.0],
+        _ => return None,
+    };
+    Some(color.into())
 }
 
 pub fn map_offset(extent: VkOffset3D) -> image::Offset {
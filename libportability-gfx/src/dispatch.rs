@@ -0,0 +1,66 @@
+//! Entry point tables, generated by the build script from the `gfx*`
+//! implementations of `impls.rs` and the commands of `vk.xml`.
+
+use std::ffi::CStr;
+use std::mem;
+use std::os::raw::c_char;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use super::*;
+
+include!(concat!(env!("OUT_DIR"), "/entry_points.rs"));
+include!(concat!(env!("OUT_DIR"), "/exports.rs"));
+
+/// Interface version negotiated with the Vulkan loader, zero when linked directly.
+static LOADER_INTERFACE_VERSION: AtomicUsize = AtomicUsize::new(0);
+
+pub fn set_loader_interface_version(version: u32) {
+    LOADER_INTERFACE_VERSION.store(version as usize, Ordering::Release);
+}
+
+pub fn loader_interface_version() -> u32 {
+    LOADER_INTERFACE_VERSION.load(Ordering::Acquire) as u32
+}
+
+/// Dispatchable object a command is called on.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Level {
+    /// No object, or the instance is optional.
+    Global,
+    Instance,
+    PhysicalDevice,
+    /// Device, queue or command buffer.
+    Device,
+}
+
+pub struct EntryPoint {
+    pub level: Level,
+    /// Extension introducing the command, `None` for the core ones.
+    pub extension: Option<&'static str>,
+    index: usize,
+}
+
+impl EntryPoint {
+    pub fn addr(&self) -> PFN_vkVoidFunction {
+        Some(unsafe { mem::transmute::<*const (), unsafe extern "C" fn()>(entry_point_addr(self.index)) })
+    }
+}
+
+pub fn lookup(name: *const c_char) -> Option<&'static EntryPoint> {
+    let name = unsafe { CStr::from_ptr(name) };
+    name.to_str().ok().and_then(|name| ENTRY_POINTS.get(name))
+}
+
+/// Resolves the physical device commands, for `vk_icdGetPhysicalDeviceProcAddr`.
+pub fn physical_device_proc_addr(instance: VkInstance, name: *const c_char) -> PFN_vkVoidFunction {
+    let settings = instance.as_ref().map(|instance| &*instance.settings);
+    match lookup(name) {
+        Some(entry) if entry.level == Level::PhysicalDevice => {
+            match entry.extension {
+                Some(extension) if !impls::is_extension_supported(extension, settings) => None,
+                _ => entry.addr(),
+            }
+        }
+        _ => None,
+    }
+}
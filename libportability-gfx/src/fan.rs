@@ -0,0 +1,509 @@
+//! Triangle fan emulation, for the backends without native fans.
+//!
+//! It is incomplete, so fans are only reported as supported when the
+//! `emulate_triangle_fans` setting is enabled.
+//!
+//! Fan pipelines are created with triangle lists, and their draws go through
+//! index buffers owned by the driver:
+//!   - non-indexed draws use the `0, i, i + 1` pattern shared by the device,
+//!     shifted to the first vertex by the vertex offset,
+//!   - indexed draws reserve their indices in a ring of buffers owned by the
+//!     command buffer, filled by `gfxQueueSubmit` from the application indices,
+//!     which have to be in host mapped memory by then,
+//!   - indirect draws are skipped, their parameters being unknown to the CPU.
+//!
+//! Strips split at the restart indices are drawn the same way, see `restart.rs`.
+//! The driver-owned buffers count against `maxMemoryAllocationCount`.
+
+use hal::{buffer, memory, Device, IndexType};
+use hal::buffer::IndexBufferView;
+use hal::command::RawCommandBuffer;
+
+use std::{mem, ptr};
+use std::collections::hash_map::Entry;
+use std::ops::Range;
+use std::sync::atomic::Ordering;
+
+use super::*;
+
+/// Indices of the first buffer of a ring, larger draws get their own size.
+const RING_CHUNK_INDICES: u32 = 1 << 16;
+
+/// Primitive topology drawn as a list, with indices generated by the driver.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Shape {
+    Fan,
+    Strip(restart::Strip),
+}
+
+impl Shape {
+    /// Length of the list drawing `count` indices, without restarts.
+    fn list_len(&self, count: u32) -> u32 {
+        match *self {
+            Shape::Strip(restart::Strip::Line) => count.saturating_sub(1) * 2,
+            Shape::Fan | Shape::Strip(restart::Strip::Triangle) => count.saturating_sub(2) * 3,
+        }
+    }
+
+    /// Converts the indices to a list, starting a new primitive at each `restart` index.
+    fn expand(&self, indices: &[u32], restart: Option<u32>) -> Vec<u32> {
+        let mut list = Vec::with_capacity(self.list_len(indices.len() as u32) as usize);
+        for part in indices.split(|&index| Some(index) == restart) {
+            match *self {
+                Shape::Fan => for i in 1 .. part.len().saturating_sub(1) {
+                    list.extend_from_slice(&[part[0], part[i], part[i + 1]]);
+                },
+                Shape::Strip(restart::Strip::Line) => for line in part.windows(2) {
+                    list.extend_from_slice(line);
+                },
+                Shape::Strip(restart::Strip::Triangle) => for (i, triangle) in part.windows(3).enumerate() {
+                    // odd triangles are flipped to keep the winding
+                    if i % 2 == 0 {
+                        list.extend_from_slice(triangle);
+                    } else {
+                        list.extend_from_slice(&[triangle[1], triangle[0], triangle[2]]);
+                    }
+                },
+            }
+        }
+        list
+    }
+}
+
+/// Index buffer owned by the driver, mapped for its whole lifetime.
+pub struct IndexBuffer<B: hal::Backend> {
+    raw: B::Buffer,
+    memory: B::Memory,
+    data: *mut u32,
+    capacity: u32,
+}
+
+impl IndexBuffer<B> {
+    fn new(gpu: &Gpu<B>, capacity: u32) -> Option<Self> {
+        let size = capacity as u64 * mem::size_of::<u32>() as u64;
+        unsafe {
+            let mut raw = gpu.device.create_buffer(size, buffer::Usage::INDEX).ok()?;
+            let requirements = gpu.device.get_buffer_requirements(&raw);
+            let host_visible = memory::Properties::CPU_VISIBLE | memory::Properties::COHERENT;
+            let type_id = gpu.memory_types
+                .iter()
+                .enumerate()
+                .position(|(id, ty)| {
+                    requirements.type_mask & (1 << id) != 0 && ty.properties.contains(host_visible)
+                });
+            let type_id = match type_id {
+                Some(id) => hal::MemoryTypeId(id),
+                None => {
+                    gpu.device.destroy_buffer(raw);
+                    return None;
+                }
+            };
+            let memory = match gpu.device.allocate_memory(type_id, requirements.size) {
+                Ok(memory) => memory,
+                Err(_) => {
+                    gpu.device.destroy_buffer(raw);
+                    return None;
+                }
+            };
+            if gpu.device.bind_buffer_memory(&memory, 0, &mut raw).is_ok() {
+                if let Ok(data) = gpu.device.map_memory(&memory, 0 .. size) {
+                    gpu.allocation_count.fetch_add(1, Ordering::Relaxed);
+                    return Some(IndexBuffer {
+                        raw,
+                        memory,
+                        data: data as *mut u32,
+                        capacity,
+                    });
+                }
+            }
+            gpu.device.destroy_buffer(raw);
+            gpu.device.free_memory(memory);
+            None
+        }
+    }
+
+    /// Writes indices starting at the index `first`.
+    fn write(&self, first: u32, indices: &[u32]) {
+        assert!(first as usize + indices.len() <= self.capacity as usize);
+        unsafe {
+            ptr::copy_nonoverlapping(indices.as_ptr(), self.data.add(first as usize), indices.len());
+        }
+    }
+
+    /// View of the indices starting at the index `first`.
+    fn view(&self, first: u32) -> IndexBufferView<B> {
+        IndexBufferView {
+            buffer: &self.raw,
+            offset: first as u64 * mem::size_of::<u32>() as u64,
+            index_type: IndexType::U32,
+        }
+    }
+
+    fn destroy(self, gpu: &Gpu<B>) {
+        unsafe {
+            gpu.device.unmap_memory(&self.memory);
+            gpu.device.destroy_buffer(self.raw);
+            gpu.device.free_memory(self.memory);
+        }
+        gpu.allocation_count.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Device-wide index patterns of the shapes, like `0, i, i + 1` for fans, grown on demand.
+pub struct Pattern<B: hal::Backend> {
+    current: HashMap<Shape, (IndexBuffer<B>, u32)>,
+    /// Smaller patterns, possibly still used by recorded command buffers.
+    retired: Vec<IndexBuffer<B>>,
+}
+
+impl Pattern<B> {
+    pub fn new() -> Self {
+        Pattern {
+            current: HashMap::new(),
+            retired: Vec::new(),
+        }
+    }
+
+    /// Returns a buffer covering shapes of `vertex_count` vertices.
+    fn get(&mut self, gpu: &Gpu<B>, shape: Shape, vertex_count: u32) -> Option<&IndexBuffer<B>> {
+        let large_enough = match self.current.get(&shape) {
+            Some(&(_, max_vertices)) => max_vertices >= vertex_count,
+            None => false,
+        };
+        if !large_enough {
+            let max_vertices = vertex_count.next_power_of_two().max(64);
+            let indices = shape.expand(&(0 .. max_vertices).collect::<Vec<_>>(), None);
+            let buffer = IndexBuffer::new(gpu, indices.len() as u32)?;
+            buffer.write(0, &indices);
+            match self.current.entry(shape) {
+                Entry::Occupied(mut entry) => {
+                    let (old, _) = entry.insert((buffer, max_vertices));
+                    self.retired.push(old);
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert((buffer, max_vertices));
+                }
+            }
+        }
+        self.current.get(&shape).map(|&(ref buffer, _)| buffer)
+    }
+
+    pub fn destroy(&mut self, gpu: &Gpu<B>) {
+        let current = self.current.drain().map(|(_, (buffer, _))| buffer).collect::<Vec<_>>();
+        for buffer in current.into_iter().chain(self.retired.drain(..)) {
+            buffer.destroy(gpu);
+        }
+    }
+}
+
+/// Index buffers of a command buffer, filled in order and rewound once it is reset.
+struct Ring<B: hal::Backend> {
+    chunks: Vec<IndexBuffer<B>>,
+    /// Current chunk, and its first free index.
+    position: (usize, u32),
+}
+
+impl Ring<B> {
+    fn new() -> Self {
+        Ring {
+            chunks: Vec::new(),
+            position: (0, 0),
+        }
+    }
+
+    /// Reserves `count` indices, returns their chunk and first index.
+    fn allocate(&mut self, gpu: &Gpu<B>, count: u32) -> Option<(usize, u32)> {
+        let (mut chunk, mut first) = self.position;
+        while let Some(buffer) = self.chunks.get(chunk) {
+            if buffer.capacity - first >= count {
+                self.position = (chunk, first + count);
+                return Some((chunk, first));
+            }
+            chunk += 1;
+            first = 0;
+        }
+        let capacity = count.next_power_of_two().max(RING_CHUNK_INDICES);
+        self.chunks.push(IndexBuffer::new(gpu, capacity)?);
+        self.position = (chunk, count);
+        Some((chunk, 0))
+    }
+
+    fn release(&mut self, gpu: &Gpu<B>) {
+        for buffer in self.chunks.drain(..) {
+            buffer.destroy(gpu);
+        }
+        self.position = (0, 0);
+    }
+}
+
+/// Indexed draw expanded at submission, from the indices the application has written by then.
+struct Expansion {
+    shape: Shape,
+    /// Application index buffer, its offset and index type.
+    source: (VkBuffer, VkDeviceSize, VkIndexType),
+    first_index: u32,
+    index_count: u32,
+    /// Chunk and first index of the expanded indices in the ring.
+    target: (usize, u32),
+}
+
+/// Emulation state of a command buffer.
+pub struct State<B: hal::Backend> {
+    /// The bound graphics pipeline draws fans.
+    pub pipeline: bool,
+    /// The bound graphics pipeline splits its strips, see `restart.rs`.
+    pub restart: Option<restart::Strip>,
+    /// Graphics pipeline bound by the application.
+    pub bound_pipeline: Option<VkPipeline>,
+    /// Index buffer bound by the application.
+    pub index_buffer: Option<(VkBuffer, VkDeviceSize, VkIndexType)>,
+    /// Secondary command buffers executed by this one, expanded along with it.
+    pub secondaries: Vec<VkCommandBuffer>,
+    ring: Ring<B>,
+    expansions: Vec<Expansion>,
+}
+
+impl State<B> {
+    pub fn new() -> Self {
+        State {
+            pipeline: false,
+            restart: None,
+            bound_pipeline: None,
+            index_buffer: None,
+            secondaries: Vec::new(),
+            ring: Ring::new(),
+            expansions: Vec::new(),
+        }
+    }
+
+    /// Forgets the recorded draws once the command buffer is reset, keeping the ring for reuse.
+    pub fn reset(&mut self) {
+        self.pipeline = false;
+        self.restart = None;
+        self.bound_pipeline = None;
+        self.index_buffer = None;
+        self.secondaries.clear();
+        self.expansions.clear();
+        self.ring.position = (0, 0);
+    }
+
+    /// Shape drawn as a list by the bound pipeline, if any.
+    pub fn shape(&self) -> Option<Shape> {
+        match self.restart {
+            Some(strip) => Some(Shape::Strip(strip)),
+            None if self.pipeline => Some(Shape::Fan),
+            None => None,
+        }
+    }
+
+    /// Resets the state and frees the ring.
+    pub fn release(&mut self, gpu: &Gpu<B>) {
+        self.reset();
+        self.ring.release(gpu);
+    }
+}
+
+/// Host address of `size` bytes of a buffer, if they are in currently mapped memory.
+fn host_data(
+    gpu: &Gpu<B>,
+    buffer: VkBuffer,
+    offset: VkDeviceSize,
+    size: VkDeviceSize,
+) -> Option<*const u8> {
+    let (memory, bind_offset) = *gpu.buffer_memory.lock().unwrap().get(&buffer.key())?;
+    let (map_offset, map_end, data) = *gpu.mapped_memory.lock().unwrap().get(&memory)?;
+    let start = bind_offset + offset;
+    if start < map_offset || start + size > map_end {
+        return None;
+    }
+    Some((data + (start - map_offset) as usize) as *const u8)
+}
+
+fn restore_index_buffer(cmd_buf: &mut CommandBuffer<B>) {
+    if let Some((buffer, offset, index_type)) = cmd_buf.fan.index_buffer {
+        unsafe {
+            cmd_buf.raw.bind_index_buffer(IndexBufferView {
+                buffer: &*buffer,
+                offset,
+                index_type: conv::map_index_type(index_type),
+            });
+        }
+    }
+}
+
+pub fn draw(
+    cmd_buf: &mut CommandBuffer<B>,
+    shape: Shape,
+    vertex_count: u32,
+    instances: Range<u32>,
+    first_vertex: u32,
+) {
+    let count = shape.list_len(vertex_count);
+    if count == 0 {
+        return;
+    }
+    let gpu = cmd_buf.device;
+    {
+        let mut pattern = gpu.pattern.lock().unwrap();
+        let buffer = match pattern.get(&gpu, shape, vertex_count) {
+            Some(buffer) => buffer,
+            None => {
+                error!("Unable to allocate the {:?} indices", shape);
+                return;
+            }
+        };
+        unsafe {
+            cmd_buf.raw.bind_index_buffer(buffer.view(0));
+            cmd_buf.raw.draw_indexed(0 .. count, first_vertex as _, instances);
+        }
+    }
+    restore_index_buffer(cmd_buf);
+}
+
+pub fn draw_indexed(
+    cmd_buf: &mut CommandBuffer<B>,
+    shape: Shape,
+    index_count: u32,
+    instances: Range<u32>,
+    first_index: u32,
+    vertex_offset: i32,
+) {
+    // restarts only shorten the list
+    let count = shape.list_len(index_count);
+    if count == 0 {
+        return;
+    }
+    let source = match cmd_buf.fan.index_buffer {
+        Some(source) => source,
+        None => {
+            error!("Indexed draw emulated without an index buffer");
+            return;
+        }
+    };
+    let gpu = cmd_buf.device;
+    let target = match cmd_buf.fan.ring.allocate(&gpu, count) {
+        Some(target) => target,
+        None => {
+            error!("Unable to allocate the expanded indices");
+            return;
+        }
+    };
+    unsafe {
+        cmd_buf.raw.bind_index_buffer(cmd_buf.fan.ring.chunks[target.0].view(target.1));
+        cmd_buf.raw.draw_indexed(0 .. count, vertex_offset, instances);
+    }
+    cmd_buf.fan.expansions.push(Expansion {
+        shape,
+        source,
+        first_index,
+        index_count,
+        target,
+    });
+    restore_index_buffer(cmd_buf);
+}
+
+pub fn skip_indirect(shape: Shape) {
+    error!("Indirect draws of {:?} can't be emulated, skipping the draw", shape);
+}
+
+/// Fills the indices of the emulated indexed draws, right before the command buffer is submitted.
+///
+/// The ring is shared by the submissions of a command buffer, so the ones recorded with
+/// `VK_COMMAND_BUFFER_USAGE_SIMULTANEOUS_USE_BIT` draw the indices of the latest submission.
+pub fn expand(cmd_buf: &CommandBuffer<B>) {
+    let gpu = cmd_buf.device;
+    for expansion in &cmd_buf.fan.expansions {
+        let (buffer, offset, index_type) = expansion.source;
+        let restart = match expansion.shape {
+            Shape::Strip(_) => Some(restart::restart_index(index_type)),
+            Shape::Fan => None,
+        };
+        let mut list = match read_indices(&gpu, expansion.source, expansion.first_index, expansion.index_count) {
+            Some(indices) => expansion.shape.expand(&indices, restart),
+            None => {
+                error!("Emulated indexed draws require their indices in host mapped memory \
+                    when submitted, skipping the draw of {:?} at offset {} of buffer {:?}",
+                    expansion.shape, offset, buffer);
+                Vec::new()
+            }
+        };
+        // the draw was recorded without restarts, the rest is degenerate primitives
+        let last = list.last().cloned().unwrap_or(0);
+        list.resize(expansion.shape.list_len(expansion.index_count) as usize, last);
+
+        let (chunk, first) = expansion.target;
+        cmd_buf.fan.ring.chunks[chunk].write(first, &list);
+    }
+    for secondary in &cmd_buf.fan.secondaries {
+        expand(secondary);
+    }
+}
+
+/// Reads indices of an application index buffer, if they are all in host mapped memory.
+fn read_indices(
+    gpu: &Gpu<B>,
+    (buffer, offset, index_type): (VkBuffer, VkDeviceSize, VkIndexType),
+    first_index: u32,
+    index_count: u32,
+) -> Option<Vec<u32>> {
+    let index_size = match index_type {
+        VkIndexType::VK_INDEX_TYPE_UINT16 => mem::size_of::<u16>(),
+        _ => mem::size_of::<u32>(),
+    } as VkDeviceSize;
+    let start = offset + first_index as VkDeviceSize * index_size;
+    let data = host_data(gpu, buffer, start, index_count as VkDeviceSize * index_size)?;
+
+    let indices = unsafe {
+        match index_type {
+            VkIndexType::VK_INDEX_TYPE_UINT16 => {
+                let data = data as *const u16;
+                (0 .. index_count as usize)
+                    .map(|i| ptr::read_unaligned(data.add(i)) as u32)
+                    .collect::<Vec<_>>()
+            }
+            _ => {
+                let data = data as *const u32;
+                (0 .. index_count as usize)
+                    .map(|i| ptr::read_unaligned(data.add(i)))
+                    .collect::<Vec<_>>()
+            }
+        }
+    };
+    Some(indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Shape;
+    use restart::Strip;
+
+    #[test]
+    fn list_lengths() {
+        assert_eq!(Shape::Fan.list_len(0), 0);
+        assert_eq!(Shape::Fan.list_len(2), 0);
+        assert_eq!(Shape::Fan.list_len(5), 9);
+        assert_eq!(Shape::Strip(Strip::Triangle).list_len(4), 6);
+        assert_eq!(Shape::Strip(Strip::Line).list_len(1), 0);
+        assert_eq!(Shape::Strip(Strip::Line).list_len(3), 4);
+    }
+
+    #[test]
+    fn fans() {
+        assert_eq!(Shape::Fan.expand(&[0, 1, 2, 3], None), vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(Shape::Fan.expand(&[4, 5], None), Vec::<u32>::new());
+    }
+
+    #[test]
+    fn strips() {
+        assert_eq!(Shape::Strip(Strip::Triangle).expand(&[0, 1, 2, 3], None), vec![0, 1, 2, 2, 1, 3]);
+        assert_eq!(Shape::Strip(Strip::Line).expand(&[0, 1, 2], None), vec![0, 1, 1, 2]);
+    }
+
+    #[test]
+    fn restarts() {
+        let restart = Some(!0);
+        assert_eq!(Shape::Fan.expand(&[0, 1, 2, !0, 3, 4, 5, 6], restart), vec![0, 1, 2, 3, 4, 5, 3, 5, 6]);
+        assert_eq!(Shape::Strip(Strip::Triangle).expand(&[0, 1, 2, !0, 3, 4, 5], restart), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(Shape::Strip(Strip::Line).expand(&[0, 1, !0, !0, 2, 3], restart), vec![0, 1, 2, 3]);
+    }
+}
@@ -1,49 +1,218 @@
 use VK_NULL_HANDLE;
-use std::{borrow, cmp, fmt, ops};
-#[cfg(feature = "nightly")]
-use std::sync::{Arc, Mutex};
-
-#[cfg(feature = "nightly")]
-use hal::backend::FastHashMap;
+use std::{borrow, cmp, fmt, mem, ops, ptr};
 
 use copyless::{BoxAllocation, BoxHelper};
 
+#[cfg(feature = "leak-tracking")]
+pub use self::registry::report_leaks;
+
+/// Registry of the handles, keyed by address.
+///
+/// With `checked-handles`, destroyed objects stay in the registry and keep
+/// their memory, so that their address is never handed out again and any
+/// later use of the stale handle is caught.
+#[cfg(feature = "leak-tracking")]
+mod registry {
+    use hal::backend::FastHashMap;
+    use std::any::type_name;
+    use std::sync::Mutex;
+
+    #[cfg(feature = "leak-backtrace")]
+    use backtrace::Backtrace;
+
+    struct Record {
+        name: &'static str,
+        /// Address of the owning device or instance, if any.
+        owner: Option<usize>,
+        destroyed: bool,
+        #[cfg(feature = "leak-backtrace")]
+        backtrace: Backtrace,
+    }
 
-#[cfg(feature = "nightly")]
-lazy_static! {
-    static ref REGISTRY: Arc<Mutex<FastHashMap<usize, &'static str>>> = Arc::new(Mutex::new(FastHashMap::default()));
-}
+    lazy_static! {
+        static ref REGISTRY: Mutex<FastHashMap<usize, Record>> = Mutex::new(FastHashMap::default());
+    }
 
-#[repr(C)]
-pub struct Handle<T>(*mut T);
+    pub fn register<T>(key: usize) {
+        let record = Record {
+            name: type_name::<T>(),
+            owner: None,
+            destroyed: false,
+            #[cfg(feature = "leak-backtrace")]
+            backtrace: Backtrace::new_unresolved(),
+        };
+        REGISTRY.lock().unwrap().insert(key, record);
+    }
 
-#[cfg(feature = "nightly")]
-impl Handle<()> {
-    pub fn report_leaks() {
-        println!("Leaked handles:");
-        let mut map = REGISTRY.lock().unwrap();
-        for (_, type_id) in map.drain() {
-            println!("\t{:?}", type_id);
+    /// Forgets a handle being destroyed. Returns `true` if its memory can be released.
+    pub fn unregister<T>(key: usize) -> bool {
+        check::<T>(key);
+        let mut registry = REGISTRY.lock().unwrap();
+        if cfg!(feature = "checked-handles") {
+            registry.get_mut(&key).unwrap().destroyed = true;
+            false
+        } else {
+            registry.remove(&key);
+            true
+        }
+    }
+
+    pub fn set_owner(key: usize, owner: usize) {
+        if let Some(record) = REGISTRY.lock().unwrap().get_mut(&key) {
+            record.owner = Some(owner);
+        }
+    }
+
+    /// Makes sure the handle refers to a live object of type `T`.
+    pub fn check<T>(key: usize) {
+        let expected = type_name::<T>();
+        let message = match REGISTRY.lock().unwrap().get(&key) {
+            None => format!("invalid {} handle {:#x}", expected, key),
+            Some(record) if record.destroyed => {
+                format!("{} {:#x} used after being destroyed", record.name, key)
+            }
+            Some(record) if record.name != expected => {
+                format!("{:#x} is a {}, expected a {}", key, record.name, expected)
+            }
+            Some(_) => return,
+        };
+        fail(message);
+    }
+
+    /// Makes sure the handle belongs to the given device.
+    pub fn check_owner(key: usize, device: usize) {
+        let message = match REGISTRY.lock().unwrap().get(&key) {
+            Some(&Record { owner: Some(owner), name, .. }) if owner != device => format!(
+                "{} {:#x} belongs to device {:#x}, not {:#x}",
+                name, key, owner, device,
+            ),
+            _ => return,
+        };
+        fail(message);
+    }
+
+    fn fail(message: String) -> ! {
+        let message = format!("{}: {}", calling_function(), message);
+        error!("{}", message);
+        panic!("{}", message);
+    }
+
+    /// Name of the `gfx*` entry point being executed, if the symbols are available.
+    #[cfg(feature = "backtrace")]
+    fn calling_function() -> String {
+        let mut function = None;
+        ::backtrace::trace(|frame| {
+            ::backtrace::resolve_frame(frame, |symbol| {
+                let name = symbol.name().map(|name| format!("{:#}", name));
+                let entry = name.as_ref().and_then(|name| {
+                    name.split("::").find(|part| part.starts_with("gfx") && !part.contains('_'))
+                });
+                if let Some(entry) = entry {
+                    function = Some(entry.to_string());
+                }
+            });
+            function.is_none()
+        });
+        function.unwrap_or_else(|| "<unknown>".to_string())
+    }
+
+    #[cfg(not(feature = "backtrace"))]
+    fn calling_function() -> String {
+        "<unknown>".to_string()
+    }
+
+    /// Logs and forgets the live handles owned by the given device or instance,
+    /// also passing each message to `report` along with the leaked handle.
+    pub fn report_leaks<F: FnMut(usize, &str)>(owner: usize, mut report: F) {
+        let mut registry = REGISTRY.lock().unwrap();
+        let keys = registry
+            .iter()
+            .filter(|&(_, record)| !record.destroyed && record.owner == Some(owner))
+            .map(|(&key, _)| key)
+            .collect::<Vec<_>>();
+        if keys.is_empty() {
+            return;
+        }
+
+        let message = format!("{:#x} leaked {} handles", owner, keys.len());
+        error!("{}:", message);
+        report(owner, &message);
+        for key in keys {
+            #[allow(unused_mut)]
+            let mut record = registry.remove(&key).unwrap();
+            #[allow(unused_mut)]
+            let mut message = format!("Leaked {} {:#x}", record.name, key);
+            #[cfg(feature = "leak-backtrace")]
+            {
+                record.backtrace.resolve();
+                message = format!("{}, created at:\n{:?}", message, record.backtrace);
+            }
+            error!("\t{}", message);
+            report(key, &message);
         }
     }
 }
 
+#[repr(C)]
+pub struct Handle<T>(*mut T);
+
 pub struct HandleAllocation<T>(BoxAllocation<T>);
 
 impl<T> HandleAllocation<T> {
     #[inline(always)]
     pub fn init(self, value: T) -> Handle<T> {
         let ptr = Box::into_raw(self.0.init(value));
-        #[cfg(feature = "nightly")]
-        {
-            use std::intrinsics::type_name;
-            let name = unsafe { type_name::<T>() };
-            REGISTRY.lock().unwrap().insert(ptr as _, name);
-        }
+        #[cfg(feature = "leak-tracking")]
+        registry::register::<T>(ptr as _);
         Handle(ptr)
     }
 }
 
+/// Recycled storage for the handles of a pool, sparing the global
+/// allocator on high-churn objects. The slots are regular boxes, so
+/// handles can still be released with `unbox` and the other way around.
+pub struct HandleSlab<T> {
+    free: Vec<*mut T>,
+}
+
+impl<T> HandleSlab<T> {
+    pub fn new() -> Self {
+        HandleSlab { free: Vec::new() }
+    }
+
+    fn take(&mut self, value: T) -> *mut T {
+        match self.free.pop() {
+            Some(slot) => {
+                unsafe { ptr::write(slot, value) };
+                slot
+            }
+            None => Box::into_raw(Box::alloc().init(value)),
+        }
+    }
+
+    /// Moves the value out of the slot, keeping the storage for reuse.
+    fn give_back(&mut self, slot: *mut T) -> T {
+        let value = unsafe { ptr::read(slot) };
+        self.free.push(slot);
+        value
+    }
+}
+
+impl<T> Drop for HandleSlab<T> {
+    fn drop(&mut self) {
+        for slot in self.free.drain(..) {
+            // the slots are uninitialized, release the memory only
+            let _ = unsafe { Box::from_raw(slot as *mut mem::ManuallyDrop<T>) };
+        }
+    }
+}
+
+/// Slab fitting the storage of a `DispatchHandle<T>`.
+#[cfg(feature = "dispatch")]
+pub type DispatchHandleSlab<T> = HandleSlab<(u64, T)>;
+#[cfg(not(feature = "dispatch"))]
+pub type DispatchHandleSlab<T> = HandleSlab<T>;
+
 impl<T: 'static> Handle<T> {
     pub fn alloc() -> HandleAllocation<T> {
         HandleAllocation(Box::alloc())
@@ -62,14 +231,40 @@ impl<T: 'static> Handle<T> {
         if self.0 == VK_NULL_HANDLE as *mut T {
             None
         } else {
-            #[cfg(feature = "nightly")]
+            #[cfg(feature = "leak-tracking")]
             {
-                REGISTRY.lock().unwrap().remove(&(self.0 as _)).unwrap();
+                if !registry::unregister::<T>(self.0 as _) {
+                    // keep the memory, so that the address is not reused
+                    return Some(unsafe { ptr::read(self.0) });
+                }
             }
             Some(*unsafe { Box::from_raw(self.0) })
         }
     }
 
+    /// Same as `new`, with the storage coming from a slab.
+    pub fn new_in(value: T, slab: &mut HandleSlab<T>) -> Self {
+        let ptr = slab.take(value);
+        #[cfg(feature = "leak-tracking")]
+        registry::register::<T>(ptr as _);
+        Handle(ptr)
+    }
+
+    /// Same as `unbox`, with the storage going back to a slab.
+    pub fn unbox_into(self, slab: &mut HandleSlab<T>) -> Option<T> {
+        if self.0 == VK_NULL_HANDLE as *mut T {
+            None
+        } else {
+            #[cfg(feature = "leak-tracking")]
+            {
+                if !registry::unregister::<T>(self.0 as _) {
+                    return Some(unsafe { ptr::read(self.0) });
+                }
+            }
+            Some(slab.give_back(self.0))
+        }
+    }
+
     pub fn as_ref(&self) -> Option<&T> {
         unsafe { self.0.as_ref() }
     }
@@ -77,15 +272,42 @@ impl<T: 'static> Handle<T> {
     pub fn as_mut(&self) -> Option<&mut T> {
         unsafe { self.0.as_mut() }
     }
+
+    /// Attributes the object to a device or an instance, for the leak reports.
+    #[inline]
+    pub fn owned_by<D>(self, _owner: DispatchHandle<D>) -> Self {
+        #[cfg(feature = "leak-tracking")]
+        registry::set_owner(self.0 as _, _owner.key());
+        self
+    }
+
+    /// Makes sure a non-null handle belongs to the given device, with `checked-handles`.
+    #[inline]
+    pub fn check_owner<D>(self, _device: DispatchHandle<D>) -> Self {
+        #[cfg(feature = "checked-handles")]
+        {
+            if !self.0.is_null() {
+                registry::check_owner(self.0 as _, _device.key());
+            }
+        }
+        self
+    }
 }
 
 impl<T> Handle<T> {
-    #[cfg(feature = "nightly")]
+    /// Identity of the object, for diagnostics.
+    pub fn key(&self) -> usize {
+        self.0 as _
+    }
+
+    /// Catches stale and mistyped handles on every access. The owner needs
+    /// the device, so it's checked by the entry points with `check_owner`.
+    #[cfg(feature = "leak-tracking")]
     #[inline]
     fn check(&self) {
-        assert!(REGISTRY.lock().unwrap().contains_key(&(self.0 as _)));
+        registry::check::<T>(self.0 as _);
     }
-    #[cfg(not(feature = "nightly"))]
+    #[cfg(not(feature = "leak-tracking"))]
     #[inline]
     fn check(&self) {
         debug_assert!(!self.0.is_null());
@@ -143,7 +365,9 @@ pub type DispatchHandle<T> = Handle<T>;
 mod dispatch {
     use VK_NULL_HANDLE;
     use copyless::{BoxAllocation, BoxHelper};
-    use std::{borrow, cmp, fmt, ops};
+    use std::{borrow, cmp, fmt, ops, ptr};
+
+    use super::HandleSlab;
 
     const ICD_LOADER_MAGIC: u64 = 0x01CDC0DE;
 
@@ -156,6 +380,8 @@ mod dispatch {
         #[inline(always)]
         pub fn init(self, value: T) -> DispatchHandle<T> {
             let ptr = Box::into_raw(self.0.init((ICD_LOADER_MAGIC, value)));
+            #[cfg(feature = "leak-tracking")]
+            super::registry::register::<T>(ptr as _);
             DispatchHandle(ptr)
         }
     }
@@ -177,10 +403,40 @@ mod dispatch {
             if self.0 == VK_NULL_HANDLE as *mut (u64, T) {
                 None
             } else {
+                #[cfg(feature = "leak-tracking")]
+                {
+                    if !super::registry::unregister::<T>(self.0 as _) {
+                        // keep the memory, so that the address is not reused
+                        return Some(unsafe { ptr::read(&(*self.0).1) });
+                    }
+                }
                 Some(unsafe { Box::from_raw(self.0) }.1)
             }
         }
 
+        /// Same as `new`, with the storage coming from a slab.
+        pub fn new_in(value: T, slab: &mut HandleSlab<(u64, T)>) -> Self {
+            let ptr = slab.take((ICD_LOADER_MAGIC, value));
+            #[cfg(feature = "leak-tracking")]
+            super::registry::register::<T>(ptr as _);
+            DispatchHandle(ptr)
+        }
+
+        /// Same as `unbox`, with the storage going back to a slab.
+        pub fn unbox_into(self, slab: &mut HandleSlab<(u64, T)>) -> Option<T> {
+            if self.0 == VK_NULL_HANDLE as *mut (u64, T) {
+                None
+            } else {
+                #[cfg(feature = "leak-tracking")]
+                {
+                    if !super::registry::unregister::<T>(self.0 as _) {
+                        return Some(unsafe { ptr::read(&(*self.0).1) });
+                    }
+                }
+                Some(slab.give_back(self.0).1)
+            }
+        }
+
         pub fn as_ref(&self) -> Option<&T> {
             if self.0 == VK_NULL_HANDLE as *mut (u64, T) {
                 None
@@ -188,6 +444,48 @@ mod dispatch {
                 Some(unsafe { &(*self.0).1 })
             }
         }
+
+        /// Attributes the object to a device or an instance, for the leak reports.
+        #[inline]
+        pub fn owned_by<D>(self, _owner: DispatchHandle<D>) -> Self {
+            #[cfg(feature = "leak-tracking")]
+            super::registry::set_owner(self.0 as _, _owner.key());
+            self
+        }
+
+        /// Makes sure a non-null handle belongs to the given device, with `checked-handles`.
+        #[inline]
+        pub fn check_owner<D>(self, _device: DispatchHandle<D>) -> Self {
+            #[cfg(feature = "checked-handles")]
+            {
+                if !self.0.is_null() {
+                    super::registry::check_owner(self.0 as _, _device.key());
+                }
+            }
+            self
+        }
+
+        /// Identity of the object, for diagnostics.
+        pub fn key(&self) -> usize {
+            self.0 as _
+        }
+
+        #[inline]
+        fn check(&self) {
+            #[cfg(feature = "leak-tracking")]
+            super::registry::check::<T>(self.0 as _);
+            #[cfg(not(feature = "leak-tracking"))]
+            debug_assert!(!self.0.is_null());
+            // The loader replaces the magic with its dispatch table pointer,
+            // but never with zero.
+            debug_assert_ne!(unsafe { (*self.0).0 }, 0);
+        }
+
+        /// Checks that the handle starts with a dispatch word: the magic of the
+        /// objects created by an ICD, or the dispatch table the loader put there.
+        pub fn is_dispatchable(&self) -> bool {
+            !self.0.is_null() && unsafe { (*self.0).0 } != 0
+        }
     }
 
     impl<T> Clone for DispatchHandle<T> {
@@ -201,18 +499,21 @@ mod dispatch {
     impl<T> ops::Deref for DispatchHandle<T> {
         type Target = T;
         fn deref(&self) -> &T {
+            self.check();
             unsafe { &(*self.0).1 }
         }
     }
 
     impl<T> ops::DerefMut for DispatchHandle<T> {
         fn deref_mut(&mut self) -> &mut T {
+            self.check();
             unsafe { &mut (*self.0).1 }
         }
     }
 
     impl<T> borrow::Borrow<T> for DispatchHandle<T> {
         fn borrow(&self) -> &T {
+            self.check();
             unsafe { &(*self.0).1 }
         }
     }
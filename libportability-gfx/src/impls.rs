@@ -10,13 +10,11 @@ use hal::pool::RawCommandPool;
 use hal::queue::RawCommandQueue;
 
 use std::borrow::Cow;
-#[cfg(feature = "gfx-backend-metal")]
-use std::env;
 use std::ffi::{CStr, CString};
 use std::os::raw::c_int;
 #[cfg(feature = "renderdoc")]
 use std::os::raw::c_void;
-use std::{mem, ptr, str};
+use std::{mem, ptr};
 
 use super::*;
 
@@ -30,6 +28,17 @@ fn map_oom(oom: hal::device::OutOfMemory) -> VkResult {
     }
 }
 
+fn map_host_execution_error(lost: &LostState, error: hal::error::HostExecutionError) -> VkResult {
+    match error {
+        hal::error::HostExecutionError::OutOfHostMemory => VkResult::VK_ERROR_OUT_OF_HOST_MEMORY,
+        hal::error::HostExecutionError::OutOfDeviceMemory => VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY,
+        hal::error::HostExecutionError::DeviceLost => {
+            lost.mark("wait idle");
+            VkResult::VK_ERROR_DEVICE_LOST
+        }
+    }
+}
+
 fn map_alloc_error(alloc_error: hal::device::AllocationError) -> VkResult {
     match alloc_error {
         hal::device::AllocationError::OutOfMemory(oom) => map_oom(oom),
@@ -37,18 +46,186 @@ fn map_alloc_error(alloc_error: hal::device::AllocationError) -> VkResult {
     }
 }
 
-#[macro_export]
-macro_rules! proc_addr {
-    ($name:expr, $($vk:ident, $pfn_vk:ident => $gfx:expr,)*) => (
-        match $name {
-            $(
-                stringify!($vk) => unsafe {
-                    mem::transmute::<$pfn_vk, _>(Some(*&$gfx))
-                },
-            )*
-            _ => None
+/// Sparse resources have no gfx-hal API to create or bind them with,
+/// so these features are never exposed, regardless of the adapter.
+fn sparse_features() -> Features {
+    Features::SPARSE_BINDING |
+    Features::SPARSE_RESIDENCY_BUFFER |
+    Features::SPARSE_RESIDENCY_IMAGE_2D |
+    Features::SPARSE_RESIDENCY_IMAGE_3D |
+    Features::SPARSE_RESIDENCY_2_SAMPLES |
+    Features::SPARSE_RESIDENCY_4_SAMPLES |
+    Features::SPARSE_RESIDENCY_8_SAMPLES |
+    Features::SPARSE_RESIDENCY_16_SAMPLES |
+    Features::SPARSE_RESIDENCY_ALIASED
+}
+
+/// Features of the adapter that we can actually deliver to the application.
+fn adapter_features(adapter: VkPhysicalDevice) -> Features {
+    let mut features = adapter.raw.physical_device.features();
+    if adapter.settings.emulate_triangle_fans == Some(true) {
+        // drawn as lists when missing, see `fan.rs`
+        features |= Features::TRIANGLE_FAN;
+    }
+    features - sparse_features() - adapter.settings.hidden_features
+}
+
+/// Limits reported for the adapter, which its devices enforce.
+fn adapter_limits(adapter: VkPhysicalDevice) -> VkPhysicalDeviceLimits {
+    let mut limits = conv::limits_from_hal(adapter.raw.physical_device.limits());
+    adapter.settings.apply_limits(&mut limits);
+    limits
+}
+
+/// Logs usage going beyond one of the limits reported to the application.
+/// The creation then fails with `VK_ERROR_OUT_OF_DEVICE_MEMORY`, which all
+/// the creation commands may return.
+fn exceeds_limit(name: &str, value: u64, limit: u64) -> bool {
+    if value > limit {
+        error!("{} of {} exceeds the device limit of {}", name, value, limit);
+    }
+    value > limit
+}
+
+/// Logs invalid parents of a derivative pipeline. `batch_flags` are the flags
+/// of the pipelines created along, which `base_index` refers to.
+/// Derivatives are only a hint, so the pipeline is then created without a parent.
+fn invalid_pipeline_parent(
+    flags: VkPipelineCreateFlags,
+    base_handle: VkPipeline,
+    base_index: i32,
+    index: usize,
+    batch_flags: &[VkPipelineCreateFlags],
+    graphics: bool,
+) -> bool {
+    if flags & VkPipelineCreateFlagBits::VK_PIPELINE_CREATE_DERIVATIVE_BIT as u32 == 0 {
+        return false;
+    }
+    let allow_derivatives = match (base_handle.as_ref(), base_index) {
+        (Some(&Pipeline::Graphics { allow_derivatives, .. }), -1) if graphics => allow_derivatives,
+        (Some(&Pipeline::Compute { allow_derivatives, .. }), -1) if !graphics => allow_derivatives,
+        (Some(_), -1) => {
+            error!("Base pipeline {:?} of pipeline {} is of a different type", base_handle, index);
+            return true;
         }
-    );
+        (None, base) if base >= 0 && (base as usize) < index => {
+            batch_flags[base as usize] & VkPipelineCreateFlagBits::VK_PIPELINE_CREATE_ALLOW_DERIVATIVES_BIT as u32 != 0
+        }
+        (None, base) if base >= 0 => {
+            error!("Base pipeline index {} of pipeline {} doesn't precede it", base, index);
+            return true;
+        }
+        _ => {
+            error!("Derivative pipeline {} needs either a base pipeline handle or index", index);
+            return true;
+        }
+    };
+    if !allow_derivatives {
+        error!("Base pipeline of {} doesn't allow derivatives", index);
+    }
+    !allow_derivatives
+}
+
+/// Fails the creation of a batch of pipelines, setting all of them to null.
+fn fail_pipelines(pPipelines: *mut VkPipeline, count: u32, result: VkResult) -> VkResult {
+    let pipelines = unsafe { slice::from_raw_parts_mut(pPipelines, count as _) };
+    for pipeline in pipelines {
+        *pipeline = Handle::null();
+    }
+    result
+}
+
+/// Depth-stencil state of a graphics pipeline, which is ignored and may
+/// contain garbage if its subpass has no depth-stencil attachment.
+fn depth_stencil_state(info: &VkGraphicsPipelineCreateInfo) -> Option<&VkPipelineDepthStencilStateCreateInfo> {
+    let render_pass = &*info.renderPass;
+    if render_pass.depth_stencil.get(info.subpass as usize) == Some(&true) {
+        unsafe { info.pDepthStencilState.as_ref() }
+    } else {
+        None
+    }
+}
+
+/// Returns the elements of an array given by the application, which may be null if empty.
+unsafe fn make_slice<'a, T>(ptr: *const T, count: u32) -> &'a [T] {
+    if count == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(ptr, count as usize)
+    }
+}
+
+/// gfx-hal resources don't carry a sharing mode. Backends without the notion
+/// of queue family ownership treat every resource as concurrent, while the
+/// Vulkan backend creates them exclusive, relying on ownership transfers.
+/// Concurrent sharing across the families the device has queues of isn't
+/// supported there: the resource is still created, but only the barriers
+/// transferring its ownership make its contents visible to another family.
+fn check_sharing_mode(
+    gpu: &Gpu<B>,
+    mode: VkSharingMode,
+    family_count: u32,
+    pFamilyIndices: *const u32,
+) {
+    match mode {
+        VkSharingMode::VK_SHARING_MODE_EXCLUSIVE => {}
+        VkSharingMode::VK_SHARING_MODE_CONCURRENT => {
+            let families = unsafe { make_slice(pFamilyIndices, family_count) };
+            if families.len() < 2 {
+                warn!("Concurrent sharing mode expects at least 2 queue families, got {:?}", families);
+            }
+            let used_families = families
+                .iter()
+                .filter(|family| gpu.queues.contains_key(family))
+                .count();
+            if cfg!(feature = "gfx-backend-vulkan") && used_families > 1 {
+                error!("Concurrent sharing across families {:?} is not supported by the Vulkan backend, \
+                    the resource is exclusive to the family using it first", families);
+            }
+        }
+        other => warn!("Unexpected sharing mode {:?}", other),
+    }
+}
+
+/// Filters and orders the adapters by the first selector they match.
+/// Applications often take the first physical device, so the choice is logged.
+fn select_adapters(
+    adapters: Vec<hal::Adapter<B>>,
+    selectors: &[config::AdapterSelector],
+) -> Vec<hal::Adapter<B>> {
+    let mut ranked = adapters
+        .into_iter()
+        .enumerate()
+        .filter_map(|(index, adapter)| {
+            let rank = if selectors.is_empty() {
+                Some(0)
+            } else {
+                selectors.iter().position(|s| s.matches(index, &adapter.info))
+            };
+            match rank {
+                Some(rank) => Some((rank, index, adapter)),
+                None => {
+                    info!("Hiding adapter {}: {}", index, adapter.info.name);
+                    None
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    if ranked.is_empty() {
+        error!("No adapter matches {:?}", selectors);
+    }
+    // stable, keeping the backend order within a rank
+    ranked.sort_by_key(|&(rank, _, _)| rank);
+
+    ranked
+        .into_iter()
+        .enumerate()
+        .map(|(position, (_, index, adapter))| {
+            info!("Exposing adapter {} as physical device {}: {} ({:?})",
+                index, position, adapter.info.name, adapter.info.device_type);
+            adapter
+        })
+        .collect()
 }
 
 #[inline]
@@ -63,17 +240,14 @@ pub extern "C" fn gfxCreateInstance(
         let _ = env_logger::try_init();
     }
 
-    let backend = back::Instance::create("portability", 1);
-    let adapters = backend
-        .enumerate_adapters()
-        .into_iter()
-        .map(Handle::new)
-        .collect();
-
     let create_info = unsafe { &*pCreateInfo };
     let application_info = unsafe { create_info.pApplicationInfo.as_ref() };
 
-    if let Some(ai) = application_info {
+    let settings = Arc::new(config::load(&config::Application::new(application_info)));
+
+    // Starting with the loader interface 5, the loader is in charge of
+    // the API version compatibility with the application.
+    if let Some(ai) = application_info.filter(|_| dispatch::loader_interface_version() < 5) {
         // Compare major and minor parts of version only - patch is ignored
         let (supported_major, supported_minor, _) = VERSION;
         let requested_major_minor = ai.apiVersion >> 12;
@@ -98,16 +272,36 @@ pub extern "C" fn gfxCreateInstance(
                 .to_str()
                 .expect("Invalid extension name")
                 .to_owned();
+            if settings.is_extension_disabled(&owned) {
+                warn!("Extension {} is disabled by the config", owned);
+                return VkResult::VK_ERROR_EXTENSION_NOT_PRESENT;
+            }
             enabled_extensions.push(owned);
         }
     }
 
+    let backend = back::Instance::create("portability", 1);
+    let adapters = select_adapters(backend.enumerate_adapters(), &settings.adapters);
+    let mut instance = DispatchHandle::new(RawInstance {
+        backend,
+        adapters: Vec::new(),
+        enabled_extensions,
+        settings,
+        debug_reports: Arc::new(DebugReports::new()),
+    });
+    // the physical devices refer to the instance, so they are created once it's boxed
+    instance.adapters = adapters
+        .into_iter()
+        .map(|raw| DispatchHandle::new(PhysicalDevice {
+            raw,
+            instance,
+            settings: Arc::clone(&instance.settings),
+            debug_reports: Arc::clone(&instance.debug_reports),
+        }).owned_by(instance))
+        .collect();
+
     unsafe {
-        *pInstance = Handle::new(RawInstance {
-            backend,
-            adapters,
-            enabled_extensions,
-        });
+        *pInstance = instance;
     }
 
     VkResult::VK_SUCCESS
@@ -122,10 +316,15 @@ pub extern "C" fn gfxDestroyInstance(
         for adapter in i.adapters {
             let _ = adapter.unbox();
         }
-    }
-    #[cfg(feature = "nightly")]
-    {
-        Handle::report_leaks();
+        #[cfg(feature = "leak-tracking")]
+        handle::report_leaks(instance.key(), |object, message| {
+            i.debug_reports.message(
+                VkDebugReportFlagBitsEXT::VK_DEBUG_REPORT_ERROR_BIT_EXT,
+                VkDebugReportObjectTypeEXT::VK_DEBUG_REPORT_OBJECT_TYPE_UNKNOWN_EXT,
+                object as u64,
+                message,
+            )
+        });
     }
 }
 
@@ -163,7 +362,7 @@ pub extern "C" fn gfxGetPhysicalDeviceQueueFamilyProperties(
     pQueueFamilyPropertyCount: *mut u32,
     pQueueFamilyProperties: *mut VkQueueFamilyProperties,
 ) {
-    let families = &adapter.queue_families;
+    let families = &adapter.raw.queue_families;
 
     // If NULL, number of queue families is returned.
     if pQueueFamilyProperties.is_null() {
@@ -207,7 +406,7 @@ pub extern "C" fn gfxGetPhysicalDeviceFeatures(
     adapter: VkPhysicalDevice,
     pFeatures: *mut VkPhysicalDeviceFeatures,
 ) {
-    let features = adapter.physical_device.features();
+    let features = adapter_features(adapter);
     unsafe {
         *pFeatures = conv::features_from_hal(features);
     }
@@ -217,7 +416,8 @@ pub extern "C" fn gfxGetPhysicalDeviceFeatures2KHR(
     adapter: VkPhysicalDevice,
     pFeatures: *mut VkPhysicalDeviceFeatures2KHR,
 ) {
-    let features = adapter.physical_device.features();
+    let features = adapter_features(adapter);
+    let subset = portability::subset_from_hal(features, &adapter.raw.physical_device.limits());
     let mut ptr = pFeatures as *const VkStructureType;
     while !ptr.is_null() {
         ptr = match unsafe { *ptr } {
@@ -232,27 +432,14 @@ pub extern "C" fn gfxGetPhysicalDeviceFeatures2KHR(
                 let data = unsafe {
                     (ptr as *mut VkPhysicalDevicePortabilitySubsetFeaturesEXTX).as_mut().unwrap()
                 };
-                if features.contains(hal::Features::TRIANGLE_FAN) {
-                    data.triangleFans = VK_TRUE;
-                }
-                if features.contains(hal::Features::SEPARATE_STENCIL_REF_VALUES) {
-                    data.separateStencilMaskRef = VK_TRUE;
-                }
-                if features.contains(hal::Features::SAMPLER_MIP_LOD_BIAS) {
-                    data.samplerMipLodBias = VK_TRUE;
-                }
-                //TODO: turn this into a feature flag
-                if !cfg!(feature = "gfx-backend-metal") {
-                    data.standardImageViews = VK_TRUE;
-                }
+                subset.fill_features(data);
                 data.pNext
             }
             VkStructureType::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_PORTABILITY_SUBSET_PROPERTIES_EXTX => {
                 let data = unsafe {
                     (ptr as *mut VkPhysicalDevicePortabilitySubsetPropertiesEXTX).as_mut().unwrap()
                 };
-                let limits = adapter.physical_device.limits();
-                data.minVertexInputBindingStrideAlignment = limits.min_vertex_input_binding_stride_alignment as u32;
+                subset.fill_properties(data);
                 data.pNext
             }
             other => {
@@ -270,9 +457,18 @@ pub extern "C" fn gfxGetPhysicalDeviceFormatProperties(
     format: VkFormat,
     pFormatProperties: *mut VkFormatProperties,
 ) {
-    let properties = adapter.physical_device.format_properties(conv::map_format(format));
+    let properties = match conv::map_format(format) {
+        Some(format) => conv::format_properties_from_hal(
+            adapter.raw.physical_device.format_properties(Some(format)),
+        ),
+        None => VkFormatProperties {
+            linearTilingFeatures: 0,
+            optimalTilingFeatures: 0,
+            bufferFeatures: 0,
+        },
+    };
     unsafe {
-        *pFormatProperties = conv::format_properties_from_hal(properties);
+        *pFormatProperties = properties;
     }
 }
 
@@ -280,9 +476,9 @@ fn get_physical_device_image_format_properties(
     adapter: VkPhysicalDevice,
     info: &VkPhysicalDeviceImageFormatInfo2KHR,
 ) -> Option<VkImageFormatProperties> {
-    adapter.physical_device
+    adapter.raw.physical_device
         .image_format_properties(
-            conv::map_format(info.format).unwrap(),
+            conv::map_format(info.format)?,
             match info.type_ {
                 VkImageType::VK_IMAGE_TYPE_1D => 1,
                 VkImageType::VK_IMAGE_TYPE_2D => 2,
@@ -293,7 +489,22 @@ fn get_physical_device_image_format_properties(
             conv::map_image_usage(info.usage),
             conv::map_image_create_flags(info.flags),
         )
-        .map(conv::image_format_properties_from_hal)
+        .map(|properties| {
+            let mut properties = conv::image_format_properties_from_hal(properties);
+            // keep within the reported limits, which may be lower than the hardware ones
+            let limits = adapter_limits(adapter);
+            let max_dimension = match info.type_ {
+                VkImageType::VK_IMAGE_TYPE_1D => limits.maxImageDimension1D,
+                VkImageType::VK_IMAGE_TYPE_3D => limits.maxImageDimension3D,
+                _ => limits.maxImageDimension2D,
+            };
+            let extent = &mut properties.maxExtent;
+            extent.width = extent.width.min(max_dimension);
+            extent.height = extent.height.min(max_dimension);
+            extent.depth = extent.depth.min(max_dimension);
+            properties.maxArrayLayers = properties.maxArrayLayers.min(limits.maxImageArrayLayers);
+            properties
+        })
 }
 #[inline]
 pub extern "C" fn gfxGetPhysicalDeviceImageFormatProperties(
@@ -346,10 +557,12 @@ pub extern "C" fn gfxGetPhysicalDeviceImageFormatProperties2KHR(
                 };
                 #[cfg(feature = "gfx-backend-metal")]
                 {
-                    if !adapter.physical_device.supports_swizzle(
-                        conv::map_format(data.format).unwrap(),
-                        conv::map_swizzle(data.components),
-                    ) {
+                    let supported = match conv::map_format(data.format) {
+                        Some(format) => adapter.raw.physical_device
+                            .supports_swizzle(format, conv::map_swizzle(data.components)),
+                        None => false,
+                    };
+                    if !supported {
                         return VkResult::VK_ERROR_FORMAT_NOT_SUPPORTED;
                     }
                 }
@@ -377,13 +590,22 @@ pub extern "C" fn gfxGetPhysicalDeviceProperties(
     adapter: VkPhysicalDevice,
     pProperties: *mut VkPhysicalDeviceProperties,
 ) {
-    let adapter_info = &adapter.info;
-    let limits = conv::limits_from_hal(adapter.physical_device.limits());
-    let sparse_properties = unsafe { mem::zeroed() }; // TODO
+    let adapter_info = &adapter.raw.info;
+    let settings = &adapter.settings;
+    let limits = adapter_limits(adapter);
+    // Sparse binding is never exposed, so none of the residency guarantees apply.
+    let sparse_properties = VkPhysicalDeviceSparseProperties {
+        residencyStandard2DBlockShape: VK_FALSE,
+        residencyStandard2DMultisampleBlockShape: VK_FALSE,
+        residencyStandard3DBlockShape: VK_FALSE,
+        residencyAlignedMipSize: VK_FALSE,
+        residencyNonResidentStrict: VK_FALSE,
+    };
     let (major, minor, patch) = VERSION;
 
     let device_name = {
-        let c_string = CString::new(adapter_info.name.clone()).unwrap();
+        let name = settings.device_name.as_ref().unwrap_or(&adapter_info.name);
+        let c_string = CString::new(name.clone()).unwrap();
         let c_str = c_string.as_bytes_with_nul();
         let mut name = [0; VK_MAX_PHYSICAL_DEVICE_NAME_SIZE as _];
         let len = name.len().min(c_str.len()) - 1;
@@ -391,21 +613,15 @@ pub extern "C" fn gfxGetPhysicalDeviceProperties(
         unsafe { mem::transmute(name) }
     };
 
-    use hal::adapter::DeviceType;
-    let device_type = match adapter.info.device_type {
-        DeviceType::IntegratedGpu => VkPhysicalDeviceType::VK_PHYSICAL_DEVICE_TYPE_INTEGRATED_GPU,
-        DeviceType::DiscreteGpu => VkPhysicalDeviceType::VK_PHYSICAL_DEVICE_TYPE_DISCRETE_GPU,
-        DeviceType::VirtualGpu => VkPhysicalDeviceType::VK_PHYSICAL_DEVICE_TYPE_VIRTUAL_GPU,
-        DeviceType::Other => VkPhysicalDeviceType::VK_PHYSICAL_DEVICE_TYPE_OTHER,
-        DeviceType::Cpu => VkPhysicalDeviceType::VK_PHYSICAL_DEVICE_TYPE_CPU,
-    };
+    let device_type = settings.device_type
+        .unwrap_or(conv::device_type_from_hal(&adapter_info.device_type));
 
     unsafe {
         *pProperties = VkPhysicalDeviceProperties {
             apiVersion: (major << 22) | (minor << 12) | patch,
             driverVersion: DRIVER_VERSION,
-            vendorID: adapter_info.vendor as _,
-            deviceID: adapter_info.device as _,
+            vendorID: settings.vendor_id.unwrap_or(adapter_info.vendor as _),
+            deviceID: settings.device_id.unwrap_or(adapter_info.device as _),
             deviceType: device_type,
             deviceName: device_name,
             pipelineCacheUUID: [0; 16usize],
@@ -419,7 +635,7 @@ pub extern "C" fn gfxGetPhysicalDeviceMemoryProperties(
     adapter: VkPhysicalDevice,
     pMemoryProperties: *mut VkPhysicalDeviceMemoryProperties,
 ) {
-    let properties = adapter.physical_device.memory_properties();
+    let properties = adapter.raw.physical_device.memory_properties();
     let memory_properties = unsafe { &mut *pMemoryProperties };
 
     let num_types = properties.memory_types.len();
@@ -441,55 +657,50 @@ pub extern "C" fn gfxGetPhysicalDeviceMemoryProperties(
         };
     }
 }
+/// Checks if the extension is exposed by the instance or any of the devices,
+/// and not disabled by the settings of the instance, if known.
+pub(crate) fn is_extension_supported(name: &str, settings: Option<&config::Settings>) -> bool {
+    let exposed = INSTANCE_EXTENSION_NAMES
+        .iter()
+        .chain(DEVICE_EXTENSION_NAMES.iter())
+        .any(|ext| &ext[.. ext.len() - 1] == name.as_bytes());
+    exposed && !settings.map_or(false, |settings| settings.is_extension_disabled(name))
+}
+
 #[inline]
 pub extern "C" fn gfxGetInstanceProcAddr(
-    _instance: VkInstance,
+    instance: VkInstance,
     pName: *const ::std::os::raw::c_char,
 ) -> PFN_vkVoidFunction {
-    let name = unsafe { CStr::from_ptr(pName) };
-    let name = match name.to_str() {
-        Ok(name) => name,
-        Err(_) => return None,
+    let entry = match dispatch::lookup(pName) {
+        Some(entry) => entry,
+        None => return None,
     };
 
-    let device_addr = gfxGetDeviceProcAddr(DispatchHandle::null(), pName);
-    if device_addr.is_some() {
-        return device_addr;
-    }
-
-    proc_addr!{ name,
-        vkCreateInstance, PFN_vkCreateInstance => gfxCreateInstance,
-        vkDestroyInstance, PFN_vkDestroyInstance => gfxDestroyInstance,
-        vkCreateDevice, PFN_vkCreateDevice => gfxCreateDevice,
-        vkGetDeviceProcAddr, PFN_vkGetDeviceProcAddr => gfxGetDeviceProcAddr,
-
-        vkEnumeratePhysicalDevices, PFN_vkEnumeratePhysicalDevices => gfxEnumeratePhysicalDevices,
-        vkEnumerateInstanceLayerProperties, PFN_vkEnumerateInstanceLayerProperties => gfxEnumerateInstanceLayerProperties,
-        vkEnumerateInstanceExtensionProperties, PFN_vkEnumerateInstanceExtensionProperties => gfxEnumerateInstanceExtensionProperties,
-        vkEnumerateDeviceExtensionProperties, PFN_vkEnumerateDeviceExtensionProperties => gfxEnumerateDeviceExtensionProperties,
-        vkEnumerateDeviceLayerProperties, PFN_vkEnumerateDeviceLayerProperties => gfxEnumerateDeviceLayerProperties,
-
-        vkGetPhysicalDeviceFeatures, PFN_vkGetPhysicalDeviceFeatures => gfxGetPhysicalDeviceFeatures,
-        vkGetPhysicalDeviceFeatures2KHR, PFN_vkGetPhysicalDeviceFeatures2KHR => gfxGetPhysicalDeviceFeatures2KHR,
-        vkGetPhysicalDeviceProperties, PFN_vkGetPhysicalDeviceProperties => gfxGetPhysicalDeviceProperties,
-        vkGetPhysicalDeviceFormatProperties, PFN_vkGetPhysicalDeviceFormatProperties => gfxGetPhysicalDeviceFormatProperties,
-        vkGetPhysicalDeviceImageFormatProperties, PFN_vkGetPhysicalDeviceImageFormatProperties => gfxGetPhysicalDeviceImageFormatProperties,
-        vkGetPhysicalDeviceImageFormatProperties2KHR, PFN_vkGetPhysicalDeviceImageFormatProperties2KHR => gfxGetPhysicalDeviceImageFormatProperties2KHR,
-        vkGetPhysicalDeviceMemoryProperties, PFN_vkGetPhysicalDeviceMemoryProperties => gfxGetPhysicalDeviceMemoryProperties,
-        vkGetPhysicalDeviceQueueFamilyProperties, PFN_vkGetPhysicalDeviceQueueFamilyProperties => gfxGetPhysicalDeviceQueueFamilyProperties,
-        vkGetPhysicalDeviceSparseImageFormatProperties, PFN_vkGetPhysicalDeviceSparseImageFormatProperties => gfxGetPhysicalDeviceSparseImageFormatProperties,
-
-        vkGetPhysicalDeviceSurfaceSupportKHR, PFN_vkGetPhysicalDeviceSurfaceSupportKHR => gfxGetPhysicalDeviceSurfaceSupportKHR,
-        vkGetPhysicalDeviceSurfaceCapabilitiesKHR, PFN_vkGetPhysicalDeviceSurfaceCapabilitiesKHR => gfxGetPhysicalDeviceSurfaceCapabilitiesKHR,
-        vkGetPhysicalDeviceSurfaceFormatsKHR, PFN_vkGetPhysicalDeviceSurfaceFormatsKHR => gfxGetPhysicalDeviceSurfaceFormatsKHR,
-        vkGetPhysicalDeviceSurfacePresentModesKHR, PFN_vkGetPhysicalDeviceSurfacePresentModesKHR => gfxGetPhysicalDeviceSurfacePresentModesKHR,
-
-        vkCreateWin32SurfaceKHR, PFN_vkCreateWin32SurfaceKHR => gfxCreateWin32SurfaceKHR,
-        vkCreateMetalSurfaceEXT, PFN_vkCreateMetalSurfaceEXT => gfxCreateMetalSurfaceEXT,
-        vkCreateMacOSSurfaceMVK, PFN_vkCreateMacOSSurfaceMVK => gfxCreateMacOSSurfaceMVK,
+    let instance = match instance.as_ref() {
+        Some(instance) => instance,
+        // Only the global commands can be queried without an instance.
+        None if entry.level == dispatch::Level::Global => return entry.addr(),
+        None => return None,
+    };
 
-        vkDestroySurfaceKHR, PFN_vkDestroySurfaceKHR => gfxDestroySurfaceKHR,
+    if let Some(extension) = entry.extension {
+        // Device extension commands are returned as long as any device supports them,
+        // instance extension ones only if enabled on this instance.
+        let is_instance_extension = INSTANCE_EXTENSION_NAMES
+            .iter()
+            .any(|ext| &ext[.. ext.len() - 1] == extension.as_bytes());
+        let available = if is_instance_extension {
+            instance.enabled_extensions.iter().any(|ext| ext == extension)
+        } else {
+            is_extension_supported(extension, Some(&*instance.settings))
+        };
+        if !available {
+            return None;
+        }
     }
+
+    entry.addr()
 }
 
 #[inline]
@@ -497,175 +708,24 @@ pub extern "C" fn gfxGetDeviceProcAddr(
     device: VkDevice,
     pName: *const ::std::os::raw::c_char,
 ) -> PFN_vkVoidFunction {
-    let name = unsafe { CStr::from_ptr(pName) };
-    let name = match name.to_str() {
-        Ok(name) => name,
-        Err(_) => return None,
+    let entry = match dispatch::lookup(pName) {
+        Some(entry) if entry.level == dispatch::Level::Device => entry,
+        _ => return None,
     };
 
     // Requesting the function pointer to an extensions which is available but not
     // enabled with an valid device requires returning NULL.
-    if let Some(device) = device.as_ref() {
-        match name {
-            "vkCreateSwapchainKHR"
-            | "vkDestroySwapchainKHR"
-            | "vkGetSwapchainImagesKHR"
-            | "vkAcquireNextImageKHR"
-            | "vkQueuePresentKHR" => {
-                let search_name = str::from_utf8(
-                    &VK_KHR_SWAPCHAIN_EXTENSION_NAME[.. VK_KHR_SWAPCHAIN_EXTENSION_NAME.len()-1]
-                ).unwrap();
-                if !device.enabled_extensions.iter().any(|ext| ext == search_name) {
-                    return None;
-                }
-            }
-            _ => {}
-        }
-    }
-
-    proc_addr!{ name,
-        vkGetDeviceProcAddr, PFN_vkGetDeviceProcAddr => gfxGetDeviceProcAddr,
-        vkDestroyDevice, PFN_vkDestroyDevice => gfxDestroyDevice,
-        vkGetDeviceMemoryCommitment, PFN_vkGetDeviceMemoryCommitment => gfxGetDeviceMemoryCommitment,
-
-        vkCreateSwapchainKHR, PFN_vkCreateSwapchainKHR => gfxCreateSwapchainKHR,
-        vkDestroySwapchainKHR, PFN_vkDestroySwapchainKHR => gfxDestroySwapchainKHR,
-        vkGetSwapchainImagesKHR, PFN_vkGetSwapchainImagesKHR => gfxGetSwapchainImagesKHR,
-        vkAcquireNextImageKHR, PFN_vkAcquireNextImageKHR => gfxAcquireNextImageKHR,
-        vkQueuePresentKHR, PFN_vkQueuePresentKHR => gfxQueuePresentKHR,
-
-        vkCreateSampler, PFN_vkCreateSampler => gfxCreateSampler,
-        vkDestroySampler, PFN_vkDestroySampler => gfxDestroySampler,
-        vkCreateShaderModule, PFN_vkCreateShaderModule => gfxCreateShaderModule,
-        vkDestroyShaderModule, PFN_vkDestroyShaderModule => gfxDestroyShaderModule,
-        vkGetDeviceQueue, PFN_vkGetDeviceQueue => gfxGetDeviceQueue,
-
-        vkAllocateMemory, PFN_vkAllocateMemory => gfxAllocateMemory,
-        vkFreeMemory, PFN_vkFreeMemory => gfxFreeMemory,
-        vkMapMemory, PFN_vkMapMemory => gfxMapMemory,
-        vkUnmapMemory, PFN_vkUnmapMemory => gfxUnmapMemory,
-        vkFlushMappedMemoryRanges, PFN_vkFlushMappedMemoryRanges => gfxFlushMappedMemoryRanges,
-        vkInvalidateMappedMemoryRanges, PFN_vkInvalidateMappedMemoryRanges => gfxInvalidateMappedMemoryRanges,
-
-        vkCreateBuffer, PFN_vkCreateBuffer => gfxCreateBuffer,
-        vkDestroyBuffer, PFN_vkDestroyBuffer => gfxDestroyBuffer,
-        vkGetBufferMemoryRequirements, PFN_vkGetBufferMemoryRequirements => gfxGetBufferMemoryRequirements,
-        vkBindBufferMemory, PFN_vkBindBufferMemory => gfxBindBufferMemory,
-        vkCreateBufferView, PFN_vkCreateBufferView => gfxCreateBufferView,
-        vkDestroyBufferView, PFN_vkDestroyBufferView => gfxDestroyBufferView,
-
-        vkCreateImage, PFN_vkCreateImage => gfxCreateImage,
-        vkDestroyImage, PFN_vkDestroyImage => gfxDestroyImage,
-        vkGetImageMemoryRequirements, PFN_vkGetImageMemoryRequirements => gfxGetImageMemoryRequirements,
-        vkGetImageSparseMemoryRequirements, PFN_vkGetImageSparseMemoryRequirements => gfxGetImageSparseMemoryRequirements,
-        vkBindImageMemory, PFN_vkBindImageMemory => gfxBindImageMemory,
-        vkCreateImageView, PFN_vkCreateImageView => gfxCreateImageView,
-        vkDestroyImageView, PFN_vkDestroyImageView => gfxDestroyImageView,
-        vkGetImageSubresourceLayout, PFN_vkGetImageSubresourceLayout => gfxGetImageSubresourceLayout,
-
-        vkCreateRenderPass, PFN_vkCreateRenderPass => gfxCreateRenderPass,
-        vkDestroyRenderPass, PFN_vkDestroyRenderPass => gfxDestroyRenderPass,
-        vkCreateFramebuffer, PFN_vkCreateFramebuffer => gfxCreateFramebuffer,
-        vkDestroyFramebuffer, PFN_vkDestroyFramebuffer => gfxDestroyFramebuffer,
-        vkGetRenderAreaGranularity, PFN_vkGetRenderAreaGranularity => gfxGetRenderAreaGranularity,
-
-        vkCreatePipelineLayout, PFN_vkCreatePipelineLayout => gfxCreatePipelineLayout,
-        vkDestroyPipelineLayout, PFN_vkDestroyPipelineLayout => gfxDestroyPipelineLayout,
-        vkCreateGraphicsPipelines, PFN_vkCreateGraphicsPipelines => gfxCreateGraphicsPipelines,
-        vkCreateComputePipelines, PFN_vkCreateComputePipelines => gfxCreateComputePipelines,
-        vkDestroyPipeline, PFN_vkDestroyPipeline => gfxDestroyPipeline,
-        vkCreatePipelineCache, PFN_vkCreatePipelineCache => gfxCreatePipelineCache,
-        vkDestroyPipelineCache, PFN_vkDestroyPipelineCache => gfxDestroyPipelineCache,
-        vkGetPipelineCacheData, PFN_vkGetPipelineCacheData => gfxGetPipelineCacheData,
-        vkMergePipelineCaches, PFN_vkMergePipelineCaches => gfxMergePipelineCaches,
-
-        vkCreateCommandPool, PFN_vkCreateCommandPool => gfxCreateCommandPool,
-        vkDestroyCommandPool, PFN_vkDestroyCommandPool => gfxDestroyCommandPool,
-        vkResetCommandPool, PFN_vkResetCommandPool => gfxResetCommandPool,
-        vkAllocateCommandBuffers, PFN_vkAllocateCommandBuffers => gfxAllocateCommandBuffers,
-        vkFreeCommandBuffers, PFN_vkFreeCommandBuffers => gfxFreeCommandBuffers,
-        vkBeginCommandBuffer, PFN_vkBeginCommandBuffer => gfxBeginCommandBuffer,
-        vkEndCommandBuffer, PFN_vkEndCommandBuffer => gfxEndCommandBuffer,
-        vkResetCommandBuffer, PFN_vkResetCommandBuffer => gfxResetCommandBuffer,
-
-        vkCreateDescriptorSetLayout, PFN_vkCreateDescriptorSetLayout => gfxCreateDescriptorSetLayout,
-        vkDestroyDescriptorSetLayout, PFN_vkDestroyDescriptorSetLayout => gfxDestroyDescriptorSetLayout,
-        vkCreateDescriptorPool, PFN_vkCreateDescriptorPool => gfxCreateDescriptorPool,
-        vkDestroyDescriptorPool, PFN_vkDestroyDescriptorPool => gfxDestroyDescriptorPool,
-        vkResetDescriptorPool, PFN_vkResetDescriptorPool => gfxResetDescriptorPool,
-        vkAllocateDescriptorSets, PFN_vkAllocateDescriptorSets => gfxAllocateDescriptorSets,
-        vkFreeDescriptorSets, PFN_vkFreeDescriptorSets => gfxFreeDescriptorSets,
-        vkUpdateDescriptorSets, PFN_vkUpdateDescriptorSets => gfxUpdateDescriptorSets,
-
-        vkCreateFence, PFN_vkCreateFence => gfxCreateFence,
-        vkDestroyFence, PFN_vkDestroyFence => gfxDestroyFence,
-        vkWaitForFences, PFN_vkWaitForFences => gfxWaitForFences,
-        vkResetFences, PFN_vkResetFences => gfxResetFences,
-        vkGetFenceStatus, PFN_vkGetFenceStatus => gfxGetFenceStatus,
-
-        vkCreateSemaphore, PFN_vkCreateSemaphore => gfxCreateSemaphore,
-        vkDestroySemaphore, PFN_vkDestroySemaphore => gfxDestroySemaphore,
-
-        vkCreateEvent, PFN_vkCreateEvent => gfxCreateEvent,
-        vkDestroyEvent, PFN_vkDestroyEvent => gfxDestroyEvent,
-        vkGetEventStatus, PFN_vkGetEventStatus => gfxGetEventStatus,
-        vkSetEvent, PFN_vkSetEvent => gfxSetEvent,
-        vkResetEvent, PFN_vkResetEvent => gfxResetEvent,
-
-        vkQueueSubmit, PFN_vkQueueSubmit => gfxQueueSubmit,
-        vkQueueBindSparse, PFN_vkQueueBindSparse => gfxQueueBindSparse,
-        vkQueueWaitIdle, PFN_vkQueueWaitIdle => gfxQueueWaitIdle,
-        vkDeviceWaitIdle, PFN_vkDeviceWaitIdle => gfxDeviceWaitIdle,
-
-        vkCreateQueryPool, PFN_vkCreateQueryPool => gfxCreateQueryPool,
-        vkDestroyQueryPool, PFN_vkDestroyQueryPool => gfxDestroyQueryPool,
-        vkGetQueryPoolResults, PFN_vkGetQueryPoolResults => gfxGetQueryPoolResults,
-
-        vkCmdBindPipeline, PFN_vkCmdBindPipeline => gfxCmdBindPipeline,
-        vkCmdSetViewport, PFN_vkCmdSetViewport => gfxCmdSetViewport,
-        vkCmdSetScissor, PFN_vkCmdSetScissor => gfxCmdSetScissor,
-        vkCmdSetLineWidth, PFN_vkCmdSetLineWidth => gfxCmdSetLineWidth,
-        vkCmdSetDepthBias, PFN_vkCmdSetDepthBias => gfxCmdSetDepthBias,
-        vkCmdSetBlendConstants, PFN_vkCmdSetBlendConstants => gfxCmdSetBlendConstants,
-        vkCmdSetDepthBounds, PFN_vkCmdSetDepthBounds => gfxCmdSetDepthBounds,
-        vkCmdSetStencilCompareMask, PFN_vkCmdSetStencilCompareMask => gfxCmdSetStencilCompareMask,
-        vkCmdSetStencilWriteMask, PFN_vkCmdSetStencilWriteMask => gfxCmdSetStencilWriteMask,
-        vkCmdSetStencilReference, PFN_vkCmdSetStencilReference => gfxCmdSetStencilReference,
-        vkCmdBindDescriptorSets, PFN_vkCmdBindDescriptorSets => gfxCmdBindDescriptorSets,
-        vkCmdBindIndexBuffer, PFN_vkCmdBindIndexBuffer => gfxCmdBindIndexBuffer,
-        vkCmdBindVertexBuffers, PFN_vkCmdBindVertexBuffers => gfxCmdBindVertexBuffers,
-        vkCmdDraw, PFN_vkCmdDraw => gfxCmdDraw,
-        vkCmdDrawIndexed, PFN_vkCmdDrawIndexed => gfxCmdDrawIndexed,
-        vkCmdDrawIndirect, PFN_vkCmdDrawIndirect => gfxCmdDrawIndirect,
-        vkCmdDrawIndexedIndirect, PFN_vkCmdDrawIndexedIndirect => gfxCmdDrawIndexedIndirect,
-        vkCmdDispatch, PFN_vkCmdDispatch => gfxCmdDispatch,
-        vkCmdDispatchIndirect, PFN_vkCmdDispatchIndirect => gfxCmdDispatchIndirect,
-        vkCmdCopyBuffer, PFN_vkCmdCopyBuffer => gfxCmdCopyBuffer,
-        vkCmdCopyImage, PFN_vkCmdCopyImage => gfxCmdCopyImage,
-        vkCmdBlitImage, PFN_vkCmdBlitImage => gfxCmdBlitImage,
-        vkCmdCopyBufferToImage, PFN_vkCmdCopyBufferToImage => gfxCmdCopyBufferToImage,
-        vkCmdCopyImageToBuffer, PFN_vkCmdCopyImageToBuffer => gfxCmdCopyImageToBuffer,
-        vkCmdUpdateBuffer, PFN_vkCmdUpdateBuffer => gfxCmdUpdateBuffer,
-        vkCmdFillBuffer, PFN_vkCmdFillBuffer => gfxCmdFillBuffer,
-        vkCmdClearColorImage, PFN_vkCmdClearColorImage => gfxCmdClearColorImage,
-        vkCmdClearDepthStencilImage, PFN_vkCmdClearDepthStencilImage => gfxCmdClearDepthStencilImage,
-        vkCmdClearAttachments, PFN_vkCmdClearAttachments => gfxCmdClearAttachments,
-        vkCmdResolveImage, PFN_vkCmdResolveImage => gfxCmdResolveImage,
-        vkCmdSetEvent, PFN_vkCmdSetEvent => gfxCmdSetEvent,
-        vkCmdResetEvent, PFN_vkCmdResetEvent => gfxCmdResetEvent,
-        vkCmdWaitEvents, PFN_vkCmdWaitEvents => gfxCmdWaitEvents,
-        vkCmdBeginQuery, PFN_vkCmdBeginQuery => gfxCmdBeginQuery,
-        vkCmdEndQuery, PFN_vkCmdEndQuery => gfxCmdEndQuery,
-        vkCmdResetQueryPool, PFN_vkCmdResetQueryPool => gfxCmdResetQueryPool,
-        vkCmdWriteTimestamp, PFN_vkCmdWriteTimestamp => gfxCmdWriteTimestamp,
-        vkCmdCopyQueryPoolResults, PFN_vkCmdCopyQueryPoolResults => gfxCmdCopyQueryPoolResults,
-        vkCmdPushConstants, PFN_vkCmdPushConstants => gfxCmdPushConstants,
-        vkCmdNextSubpass, PFN_vkCmdNextSubpass => gfxCmdNextSubpass,
-        vkCmdExecuteCommands, PFN_vkCmdExecuteCommands => gfxCmdExecuteCommands,
-        vkCmdPipelineBarrier, PFN_vkCmdPipelineBarrier => gfxCmdPipelineBarrier,
-        vkCmdBeginRenderPass, PFN_vkCmdBeginRenderPass => gfxCmdBeginRenderPass,
-        vkCmdEndRenderPass, PFN_vkCmdEndRenderPass => gfxCmdEndRenderPass,
+    if let Some(extension) = entry.extension {
+        let available = match device.as_ref() {
+            Some(device) => device.enabled_extensions.iter().any(|ext| ext == extension),
+            None => is_extension_supported(extension, None),
+        };
+        if !available {
+            return None;
+        }
     }
+
+    entry.addr()
 }
 
 #[inline]
@@ -682,82 +742,108 @@ pub extern "C" fn gfxCreateDevice(
             dev_info.queueCreateInfoCount as _,
         )
     };
-    let max_queue_count = queue_infos
-        .iter()
-        .map(|info| info.queueCount as usize)
-        .max()
-        .unwrap_or(0);
-    let priorities = vec![1.0; max_queue_count];
-    let request_infos = queue_infos
-        .iter()
-        .map(|info| {
-            let family = &adapter.queue_families[info.queueFamilyIndex as usize];
-            (family, &priorities[.. info.queueCount as usize])
-        })
-        .collect::<Vec<_>>();
+    for info in queue_infos {
+        if info.flags != 0 {
+            error!("Protected queues are not supported: {:?}", info.flags);
+            return VkResult::VK_ERROR_FEATURE_NOT_PRESENT;
+        }
 
-    let enabled = if let Some(ef) = unsafe { dev_info.pEnabledFeatures.as_ref() } {
-        fn feat(on: u32, flag: Features) -> Features {
-            if on != 0 { flag } else { Features::empty() }
-        }
-        feat(ef.robustBufferAccess, Features::ROBUST_BUFFER_ACCESS) |
-        feat(ef.fullDrawIndexUint32, Features::FULL_DRAW_INDEX_U32) |
-        feat(ef.imageCubeArray, Features::IMAGE_CUBE_ARRAY) |
-        feat(ef.independentBlend, Features::INDEPENDENT_BLENDING) |
-        feat(ef.geometryShader, Features::GEOMETRY_SHADER) |
-        feat(ef.tessellationShader, Features::TESSELLATION_SHADER) |
-        feat(ef.sampleRateShading, Features::SAMPLE_RATE_SHADING) |
-        feat(ef.dualSrcBlend, Features::DUAL_SRC_BLENDING) |
-        feat(ef.logicOp, Features::LOGIC_OP) |
-        feat(ef.multiDrawIndirect, Features::MULTI_DRAW_INDIRECT) |
-        feat(ef.drawIndirectFirstInstance, Features::DRAW_INDIRECT_FIRST_INSTANCE) |
-        feat(ef.depthClamp, Features::DEPTH_CLAMP) |
-        feat(ef.depthBiasClamp, Features::DEPTH_BIAS_CLAMP) |
-        feat(ef.fillModeNonSolid, Features::NON_FILL_POLYGON_MODE) |
-        feat(ef.depthBounds, Features::DEPTH_BOUNDS) |
-        feat(ef.wideLines, Features::LINE_WIDTH) |
-        feat(ef.largePoints, Features::POINT_SIZE) |
-        feat(ef.alphaToOne, Features::ALPHA_TO_ONE) |
-        feat(ef.multiViewport, Features::MULTI_VIEWPORTS) |
-        feat(ef.samplerAnisotropy, Features::SAMPLER_ANISOTROPY) |
-        feat(ef.textureCompressionETC2, Features::FORMAT_ETC2) |
-        feat(ef.textureCompressionASTC_LDR, Features::FORMAT_ASTC_LDR) |
-        feat(ef.textureCompressionBC, Features::FORMAT_BC) |
-        feat(ef.occlusionQueryPrecise, Features::PRECISE_OCCLUSION_QUERY) |
-        feat(ef.pipelineStatisticsQuery, Features::PIPELINE_STATISTICS_QUERY) |
-        feat(ef.vertexPipelineStoresAndAtomics, Features::VERTEX_STORES_AND_ATOMICS) |
-        feat(ef.fragmentStoresAndAtomics, Features::FRAGMENT_STORES_AND_ATOMICS) |
-        feat(ef.shaderTessellationAndGeometryPointSize, Features::SHADER_TESSELLATION_AND_GEOMETRY_POINT_SIZE) |
-        feat(ef.shaderImageGatherExtended, Features::SHADER_IMAGE_GATHER_EXTENDED) |
-        feat(ef.shaderStorageImageExtendedFormats, Features::SHADER_STORAGE_IMAGE_EXTENDED_FORMATS) |
-        feat(ef.shaderStorageImageMultisample, Features::SHADER_STORAGE_IMAGE_MULTISAMPLE) |
-        feat(ef.shaderStorageImageReadWithoutFormat, Features::SHADER_STORAGE_IMAGE_READ_WITHOUT_FORMAT) |
-        feat(ef.shaderStorageImageWriteWithoutFormat, Features::SHADER_STORAGE_IMAGE_WRITE_WITHOUT_FORMAT) |
-        feat(ef.shaderUniformBufferArrayDynamicIndexing, Features::SHADER_UNIFORM_BUFFER_ARRAY_DYNAMIC_INDEXING) |
-        feat(ef.shaderSampledImageArrayDynamicIndexing, Features::SHADER_SAMPLED_IMAGE_ARRAY_DYNAMIC_INDEXING) |
-        feat(ef.shaderStorageBufferArrayDynamicIndexing, Features::SHADER_STORAGE_BUFFER_ARRAY_DYNAMIC_INDEXING) |
-        feat(ef.shaderStorageImageArrayDynamicIndexing, Features::SHADER_STORAGE_IMAGE_ARRAY_DYNAMIC_INDEXING) |
-        feat(ef.shaderClipDistance, Features::SHADER_CLIP_DISTANCE) |
-        feat(ef.shaderCullDistance, Features::SHADER_CULL_DISTANCE) |
-        feat(ef.shaderFloat64, Features::SHADER_FLOAT64) |
-        feat(ef.shaderInt64, Features::SHADER_INT64) |
-        feat(ef.shaderInt16, Features::SHADER_INT16) |
-        feat(ef.shaderResourceResidency, Features::SHADER_RESOURCE_RESIDENCY) |
-        feat(ef.shaderResourceMinLod, Features::SHADER_RESOURCE_MIN_LOD) |
-        feat(ef.sparseBinding, Features::SPARSE_BINDING) |
-        feat(ef.sparseResidencyBuffer, Features::SPARSE_RESIDENCY_BUFFER) |
-        feat(ef.sparseResidencyImage2D, Features::SPARSE_RESIDENCY_IMAGE_2D) |
-        feat(ef.sparseResidencyImage3D, Features::SPARSE_RESIDENCY_IMAGE_3D) |
-        feat(ef.sparseResidency2Samples, Features::SPARSE_RESIDENCY_2_SAMPLES) |
-        feat(ef.sparseResidency4Samples, Features::SPARSE_RESIDENCY_4_SAMPLES) |
-        feat(ef.sparseResidency8Samples, Features::SPARSE_RESIDENCY_8_SAMPLES) |
-        feat(ef.sparseResidency16Samples, Features::SPARSE_RESIDENCY_16_SAMPLES) |
-        feat(ef.sparseResidencyAliased, Features::SPARSE_RESIDENCY_ALIASED) |
-        feat(ef.variableMultisampleRate, Features::VARIABLE_MULTISAMPLE_RATE) |
-        feat(ef.inheritedQueries, Features::INHERITED_QUERIES)
-    } else {
-        Features::empty()
-    };
+        let mut ptr = info.pNext as *const VkStructureType;
+        while !ptr.is_null() {
+            ptr = match unsafe { *ptr } {
+                VkStructureType::VK_STRUCTURE_TYPE_DEVICE_QUEUE_GLOBAL_PRIORITY_CREATE_INFO_EXT => {
+                    let data = unsafe {
+                        (ptr as *const VkDeviceQueueGlobalPriorityCreateInfoEXT).as_ref().unwrap()
+                    };
+                    // gfx-hal has no notion of system-wide priorities,
+                    // every queue runs at the default (medium) one.
+                    match data.globalPriority {
+                        VkQueueGlobalPriorityEXT::VK_QUEUE_GLOBAL_PRIORITY_MEDIUM_EXT => {}
+                        VkQueueGlobalPriorityEXT::VK_QUEUE_GLOBAL_PRIORITY_LOW_EXT => {
+                            warn!("Low global priority is not supported, using medium");
+                        }
+                        other => {
+                            error!("Global priority {:?} is not permitted", other);
+                            return VkResult::VK_ERROR_NOT_PERMITTED_EXT;
+                        }
+                    }
+                    data.pNext
+                }
+                other => {
+                    warn!("Unrecognized {:?}, skipping", other);
+                    unsafe {
+                        (ptr as *const VkDeviceQueueCreateInfo).as_ref().unwrap()
+                    }.pNext
+                }
+            } as *const VkStructureType;
+        }
+    }
+
+    let mut request_infos = Vec::with_capacity(queue_infos.len());
+    for info in queue_infos {
+        let family = match adapter.raw.queue_families.get(info.queueFamilyIndex as usize) {
+            Some(family) => family,
+            None => {
+                error!("Queue family index {} is out of range", info.queueFamilyIndex);
+                return VkResult::VK_ERROR_INITIALIZATION_FAILED;
+            }
+        };
+        let priorities = unsafe {
+            slice::from_raw_parts(info.pQueuePriorities, info.queueCount as _)
+        };
+        request_infos.push((family, priorities));
+    }
+
+    let mut enabled = unsafe { dev_info.pEnabledFeatures.as_ref() }
+        .map_or(Features::empty(), conv::map_features);
+
+    let mut ptr = dev_info.pNext as *const VkStructureType;
+    while !ptr.is_null() {
+        ptr = match unsafe { *ptr } {
+            VkStructureType::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_FEATURES_2_KHR => {
+                let data = unsafe {
+                    (ptr as *const VkPhysicalDeviceFeatures2KHR).as_ref().unwrap()
+                };
+                enabled |= conv::map_features(&data.features);
+                data.pNext
+            }
+            VkStructureType::VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_PORTABILITY_SUBSET_FEATURES_EXTX => {
+                let data = unsafe {
+                    (ptr as *const VkPhysicalDevicePortabilitySubsetFeaturesEXTX).as_ref().unwrap()
+                };
+                if data.triangleFans != 0 {
+                    enabled |= Features::TRIANGLE_FAN;
+                }
+                if data.separateStencilMaskRef != 0 {
+                    enabled |= Features::SEPARATE_STENCIL_REF_VALUES;
+                }
+                if data.samplerMipLodBias != 0 {
+                    enabled |= Features::SAMPLER_MIP_LOD_BIAS;
+                }
+                if data.events != 0 {
+                    error!("Events are not supported");
+                    return VkResult::VK_ERROR_FEATURE_NOT_PRESENT;
+                }
+                if data.standardImageViews != 0 && cfg!(feature = "gfx-backend-metal") {
+                    error!("Standard image views are not supported");
+                    return VkResult::VK_ERROR_FEATURE_NOT_PRESENT;
+                }
+                data.pNext
+            }
+            other => {
+                warn!("Unrecognized {:?}, skipping", other);
+                unsafe {
+                    (ptr as *const VkDeviceCreateInfo).as_ref().unwrap()
+                }.pNext
+            }
+        } as *const VkStructureType;
+    }
+
+    let supported = adapter_features(adapter);
+    if !supported.contains(enabled) {
+        error!("Requested features are not supported: {:?}", enabled - supported);
+        return VkResult::VK_ERROR_FEATURE_NOT_PRESENT;
+    }
 
     #[cfg(feature = "renderdoc")]
     let mut renderdoc = {
@@ -765,27 +851,33 @@ pub extern "C" fn gfxCreateDevice(
         RenderDoc::new().expect("Failed to init renderdoc")
     };
 
+    let native_fans = adapter.raw.physical_device.features().contains(Features::TRIANGLE_FAN);
     let gpu = unsafe {
-        adapter.physical_device.open(&request_infos, enabled)
+        let native_features = if native_fans { enabled } else { enabled - Features::TRIANGLE_FAN };
+        adapter.raw.physical_device.open(&request_infos, native_features)
     };
 
+    let settings = Arc::clone(&adapter.settings);
+
     match gpu {
         Ok(mut gpu) => {
             #[cfg(feature = "gfx-backend-metal")]
             {
                 use back::OnlineRecording;
 
-                if let Ok(value) = env::var("GFX_METAL_RECORDING") {
-                    gpu.device.online_recording = match value.to_lowercase().as_str() {
-                        "immediate" => OnlineRecording::Immediate,
-                        "deferred" => OnlineRecording::Deferred,
-                        //"remote" => OnlineRecording::Remote(dispatch::QueuePriority::Default),
-                        other => panic!("unknown recording option: {}", other),
+                if let Some(recording) = settings.recording {
+                    gpu.device.online_recording = match recording {
+                        config::Recording::Immediate => OnlineRecording::Immediate,
+                        config::Recording::Deferred => OnlineRecording::Deferred,
+                        //TODO: OnlineRecording::Remote(dispatch::QueuePriority::Default)
                     };
-                    println!("GFX: recording override {:?}", gpu.device.online_recording);
                 }
             }
 
+            let threaded = settings.threaded_submit.unwrap_or(false);
+
+            let lost = Arc::new(LostState::default());
+            let pending = Arc::new(submit::Pending::default());
             let queues = queue_infos
                 .iter()
                 .map(|info| {
@@ -793,7 +885,23 @@ pub extern "C" fn gfxCreateDevice(
                     let group = gpu.queues.take_raw(id).unwrap();
                     let queues = group
                         .into_iter()
-                        .map(DispatchHandle::new)
+                        .map(|raw| {
+                            let mut queue = DispatchHandle::new(Queue {
+                                worker: None,
+                                raw,
+                                lost: Arc::clone(&lost),
+                            });
+                            if threaded {
+                                // the queue is boxed now, so its address is stable
+                                let worker = submit::Worker::new(
+                                    &mut queue.raw,
+                                    Arc::clone(&pending),
+                                    Arc::clone(&lost),
+                                );
+                                queue.worker = Some(worker);
+                            }
+                            queue
+                        })
                         .collect();
 
                     (info.queueFamilyIndex, queues)
@@ -822,6 +930,10 @@ pub extern "C" fn gfxCreateDevice(
                         .to_str()
                         .expect("Invalid extension name")
                         .to_owned();
+                    if settings.is_extension_disabled(&owned) {
+                        warn!("Extension {} is disabled by the config", owned);
+                        return VkResult::VK_ERROR_EXTENSION_NOT_PRESENT;
+                    }
                     enabled_extensions.push(owned);
                 }
             }
@@ -830,6 +942,20 @@ pub extern "C" fn gfxCreateDevice(
                 device: gpu.device,
                 queues,
                 enabled_extensions,
+                features: enabled,
+                limits: adapter_limits(adapter),
+                allocation_count: AtomicUsize::new(0),
+                sampler_count: AtomicUsize::new(0),
+                memory_types: adapter.raw.physical_device.memory_properties().memory_types,
+                memory_sizes: Mutex::new(HashMap::new()),
+                mapped_memory: Mutex::new(HashMap::new()),
+                buffer_memory: Mutex::new(HashMap::new()),
+                native_fans,
+                pattern: Mutex::new(fan::Pattern::new()),
+                lost,
+                pending,
+                settings,
+                debug_reports: Arc::clone(&adapter.debug_reports),
                 #[cfg(feature = "renderdoc")]
                 renderdoc,
                 #[cfg(feature = "renderdoc")]
@@ -837,7 +963,7 @@ pub extern "C" fn gfxCreateDevice(
             };
 
             unsafe {
-                *pDevice = DispatchHandle::new(gpu);
+                *pDevice = DispatchHandle::new(gpu).owned_by(adapter.instance);
             }
 
             VkResult::VK_SUCCESS
@@ -865,6 +991,18 @@ pub extern "C" fn gfxDestroyDevice(gpu: VkDevice, _pAllocator: *const VkAllocati
                 let _ = queue.unbox();
             }
         }
+
+        #[cfg(feature = "leak-tracking")]
+        handle::report_leaks(gpu.key(), |object, message| {
+            d.debug_reports.message(
+                VkDebugReportFlagBitsEXT::VK_DEBUG_REPORT_ERROR_BIT_EXT,
+                VkDebugReportObjectTypeEXT::VK_DEBUG_REPORT_OBJECT_TYPE_UNKNOWN_EXT,
+                object as u64,
+                message,
+            )
+        });
+
+        d.pattern.lock().unwrap().destroy(&d);
     }
 }
 
@@ -880,6 +1018,7 @@ lazy_static! {
             #[cfg(target_os="macos")]
             VK_MVK_MACOS_SURFACE_EXTENSION_NAME,
             VK_KHR_GET_PHYSICAL_DEVICE_PROPERTIES_2_EXTENSION_NAME,
+            VK_EXT_DEBUG_REPORT_EXTENSION_NAME,
         ]
     };
 
@@ -908,6 +1047,10 @@ lazy_static! {
                 extensionName: [0; 256], // VK_KHR_GET_PHYSICAL_DEVICE_PROPERTIES_2_EXTENSION_NAME
                 specVersion: VK_KHR_GET_PHYSICAL_DEVICE_PROPERTIES_2_SPEC_VERSION,
             },
+            VkExtensionProperties {
+                extensionName: [0; 256], // VK_EXT_DEBUG_REPORT_EXTENSION_NAME
+                specVersion: VK_EXT_DEBUG_REPORT_SPEC_VERSION,
+            },
         ];
 
         for (&name, extension) in INSTANCE_EXTENSION_NAMES.iter().zip(&mut extensions) {
@@ -926,6 +1069,8 @@ lazy_static! {
             VK_KHR_SWAPCHAIN_EXTENSION_NAME,
             VK_KHR_MAINTENANCE1_EXTENSION_NAME,
             VK_EXTX_PORTABILITY_SUBSET_EXTENSION_NAME,
+            VK_EXT_GLOBAL_PRIORITY_EXTENSION_NAME,
+            VK_EXT_DEVICE_FAULT_EXTENSION_NAME,
         ]
     };
 
@@ -943,6 +1088,14 @@ lazy_static! {
                 extensionName: [0; 256], // VK_EXTX_PORTABILITY_SUBSET_EXTENSION_NAME
                 specVersion: VK_EXTX_PORTABILITY_SUBSET_SPEC_VERSION,
             },
+            VkExtensionProperties {
+                extensionName: [0; 256], // VK_EXT_GLOBAL_PRIORITY_EXTENSION_NAME
+                specVersion: VK_EXT_GLOBAL_PRIORITY_SPEC_VERSION,
+            },
+            VkExtensionProperties {
+                extensionName: [0; 256], // VK_EXT_DEVICE_FAULT_EXTENSION_NAME
+                specVersion: VK_EXT_DEVICE_FAULT_SPEC_VERSION,
+            },
         ];
 
         for (&name, extension) in DEVICE_EXTENSION_NAMES.iter().zip(&mut extensions) {
@@ -986,13 +1139,23 @@ pub extern "C" fn gfxEnumerateInstanceExtensionProperties(
 
 #[inline]
 pub extern "C" fn gfxEnumerateDeviceExtensionProperties(
-    _physicalDevice: VkPhysicalDevice,
+    physicalDevice: VkPhysicalDevice,
     _pLayerName: *const ::std::os::raw::c_char,
     pPropertyCount: *mut u32,
     pProperties: *mut VkExtensionProperties,
 ) -> VkResult {
+    let settings = &physicalDevice.settings;
+    let extensions = DEVICE_EXTENSION_NAMES
+        .iter()
+        .zip(DEVICE_EXTENSIONS.iter())
+        .filter(|&(name, _)| {
+            let name = CStr::from_bytes_with_nul(name).unwrap();
+            !settings.is_extension_disabled(&name.to_string_lossy())
+        })
+        .map(|(_, &extension)| extension)
+        .collect::<Vec<_>>();
     let property_count = unsafe { &mut *pPropertyCount };
-    let num_extensions = DEVICE_EXTENSIONS.len() as u32;
+    let num_extensions = extensions.len() as u32;
 
     if pProperties.is_null() {
         *property_count = num_extensions;
@@ -1003,7 +1166,7 @@ pub extern "C" fn gfxEnumerateDeviceExtensionProperties(
         let properties =
             unsafe { slice::from_raw_parts_mut(pProperties, *property_count as usize) };
         for i in 0..*property_count as usize {
-            properties[i] = DEVICE_EXTENSIONS[i];
+            properties[i] = extensions[i];
         }
 
         if *property_count < num_extensions {
@@ -1034,6 +1197,25 @@ pub extern "C" fn gfxEnumerateDeviceLayerProperties(
 
     VkResult::VK_SUCCESS
 }
+fn get_device_queue(gpu: VkDevice, family: u32, index: u32) -> VkQueue {
+    let queue = match gpu.queues.get(&family).and_then(|queues| queues.get(index as usize)) {
+        Some(&queue) => queue,
+        None => {
+            error!("Queue {} of family {} has not been requested", index, family);
+            return DispatchHandle::null();
+        }
+    };
+
+    #[cfg(feature = "gfx-backend-metal")]
+    {
+        if let Some(stitching) = gpu.settings.stitching {
+            let mut q = queue;
+            q.raw.stitch_deferred = stitching;
+        }
+    }
+
+    queue
+}
 #[inline]
 pub extern "C" fn gfxGetDeviceQueue(
     gpu: VkDevice,
@@ -1041,28 +1223,70 @@ pub extern "C" fn gfxGetDeviceQueue(
     queueIndex: u32,
     pQueue: *mut VkQueue,
 ) {
-    let queue = gpu.queues
-        .get(&queueFamilyIndex)
-        .unwrap()[queueIndex as usize];
-
-        #[cfg(feature = "gfx-backend-metal")]
-        {
-            if let Ok(value) = env::var("GFX_METAL_STITCHING") {
-                let mut q = queue;
-                q.stitch_deferred = match value.to_lowercase().as_str() {
-                    "yes" => true,
-                    "no" => false,
-                    other => panic!("unknown stitching option: {}", other),
-                };
-                println!("GFX: stitching override {:?}", q.stitch_deferred);
-            }
-        }
-
+    unsafe {
+        *pQueue = get_device_queue(gpu, queueFamilyIndex, queueIndex);
+    }
+}
+#[inline]
+pub extern "C" fn gfxGetDeviceQueue2(
+    gpu: VkDevice,
+    pQueueInfo: *const VkDeviceQueueInfo2,
+    pQueue: *mut VkQueue,
+) {
+    let info = unsafe { &*pQueueInfo };
+    // Only queues created without flags exist, see `gfxCreateDevice`.
+    let queue = if info.flags != 0 {
+        DispatchHandle::null()
+    } else {
+        get_device_queue(gpu, info.queueFamilyIndex, info.queueIndex)
+    };
 
     unsafe {
         *pQueue = queue;
     }
 }
+fn check_submit_chain(info: &VkSubmitInfo) {
+    let mut ptr = info.pNext as *const VkStructureType;
+    while !ptr.is_null() {
+        ptr = match unsafe { *ptr } {
+            VkStructureType::VK_STRUCTURE_TYPE_TIMELINE_SEMAPHORE_SUBMIT_INFO => {
+                let data = unsafe {
+                    (ptr as *const VkTimelineSemaphoreSubmitInfo).as_ref().unwrap()
+                };
+                // Timeline semaphores are not exposed, so the values
+                // can only refer to binary semaphores, which ignore them.
+                data.pNext
+            }
+            VkStructureType::VK_STRUCTURE_TYPE_DEVICE_GROUP_SUBMIT_INFO_KHX => {
+                let data = unsafe {
+                    (ptr as *const VkDeviceGroupSubmitInfoKHX).as_ref().unwrap()
+                };
+                // Every device group consists of a single physical device.
+                let masks = unsafe {
+                    make_slice(data.pCommandBufferDeviceMasks, data.commandBufferCount)
+                };
+                let wait_indices = unsafe {
+                    make_slice(data.pWaitSemaphoreDeviceIndices, data.waitSemaphoreCount)
+                };
+                let signal_indices = unsafe {
+                    make_slice(data.pSignalSemaphoreDeviceIndices, data.signalSemaphoreCount)
+                };
+                if masks.iter().any(|&mask| mask & !1 != 0) ||
+                    wait_indices.iter().chain(signal_indices).any(|&index| index != 0)
+                {
+                    warn!("Device group submission refers to missing devices, ignoring");
+                }
+                data.pNext
+            }
+            other => {
+                warn!("Unrecognized {:?}, skipping", other);
+                unsafe {
+                    (ptr as *const VkSubmitInfo).as_ref().unwrap()
+                }.pNext
+            }
+        } as *const VkStructureType;
+    }
+}
 #[inline]
 pub extern "C" fn gfxQueueSubmit(
     mut queue: VkQueue,
@@ -1070,53 +1294,98 @@ pub extern "C" fn gfxQueueSubmit(
     pSubmits: *const VkSubmitInfo,
     fence: VkFence,
 ) -> VkResult {
+    if queue.lost.is_lost() {
+        return VkResult::VK_ERROR_DEVICE_LOST;
+    }
+
     let submits = unsafe {
-        slice::from_raw_parts(pSubmits, submitCount as usize)
+        make_slice(pSubmits, submitCount)
     };
-    for (i, submission) in submits.iter().enumerate() {
-        let cmd_slice = unsafe {
-            slice::from_raw_parts(submission.pCommandBuffers, submission.commandBufferCount as _)
+    for submission in submits {
+        check_submit_chain(submission);
+        // the emulated draws read the application indices at this point, see `fan.rs`
+        let command_buffers = unsafe {
+            make_slice(submission.pCommandBuffers, submission.commandBufferCount)
         };
+        for cmd_buf in command_buffers {
+            fan::expand(cmd_buf);
+        }
+    }
+
+    // Consecutive submissions are merged into a single backend submission,
+    // as long as they don't wait on semaphores: those waits can't be hoisted
+    // above the signals of earlier submissions, which they may depend on.
+    let mut start = 0;
+    while start < submits.len() {
+        let end = submits[start + 1 ..]
+            .iter()
+            .position(|submission| submission.waitSemaphoreCount != 0)
+            .map_or(submits.len(), |pos| start + 1 + pos);
+        let batch = &submits[start .. end];
+
+        let command_buffers = batch
+            .iter()
+            .flat_map(|submission| unsafe {
+                make_slice(submission.pCommandBuffers, submission.commandBufferCount)
+            });
         let wait_semaphores = unsafe {
-            let semaphores = slice::from_raw_parts(submission.pWaitSemaphores, submission.waitSemaphoreCount as _);
-            let stages = slice::from_raw_parts(submission.pWaitDstStageMask, submission.waitSemaphoreCount as _);
+            let first = &batch[0];
+            let semaphores = make_slice(first.pWaitSemaphores, first.waitSemaphoreCount);
+            let stages = make_slice(first.pWaitDstStageMask, first.waitSemaphoreCount);
 
             stages.into_iter()
                 .zip(semaphores)
-                .map(|(stage, semaphore)| (&**semaphore, conv::map_pipeline_stage_flags(*stage)))
-        };
-        let signal_semaphores = unsafe {
-            slice::from_raw_parts(submission.pSignalSemaphores, submission.signalSemaphoreCount as _)
-                .into_iter()
-                .map(|semaphore| &**semaphore)
-        };
-
-        let submission = hal::queue::Submission {
-            command_buffers: cmd_slice.iter(),
-            wait_semaphores,
-            signal_semaphores,
+                .map(|(stage, semaphore)| (semaphore, conv::map_pipeline_stage_flags(*stage)))
         };
+        let signal_semaphores = batch
+            .iter()
+            .flat_map(|submission| unsafe {
+                make_slice(submission.pSignalSemaphores, submission.signalSemaphoreCount)
+            });
 
         // only provide the fence for the last submission
-        //TODO: support multiple submissions at gfx-hal level
-        let fence = if i + 1 == submits.len() {
-            fence.as_ref()
+        let fence = if end == submits.len() {
+            fence
         } else {
-            None
+            Handle::null()
         };
-        unsafe { queue.submit(submission, fence); }
+
+        match queue.worker {
+            Some(ref worker) => {
+                worker.enqueue(submit::Job::Submit {
+                    command_buffers: command_buffers.cloned().collect(),
+                    wait_semaphores: wait_semaphores
+                        .map(|(&semaphore, stage)| (semaphore, stage))
+                        .collect(),
+                    signal_semaphores: signal_semaphores.cloned().collect(),
+                    fence,
+                });
+            }
+            None => {
+                let submission = hal::queue::Submission {
+                    command_buffers: command_buffers.map(|cmd_buf| &cmd_buf.raw),
+                    wait_semaphores: wait_semaphores
+                        .map(|(semaphore, stage)| (&**semaphore, stage)),
+                    signal_semaphores: signal_semaphores
+                        .map(|semaphore| &**semaphore),
+                };
+                unsafe { queue.raw.submit(submission, fence.as_ref()); }
+            }
+        }
+        start = end;
     }
 
     // sometimes, all you need is a fence...
     if submits.is_empty() {
         use std::iter::empty;
+        queue.flush();
         let submission = hal::queue::Submission {
             command_buffers: empty(),
             wait_semaphores: empty(),
             signal_semaphores: empty(),
         };
         unsafe {
-            queue.submit::<VkCommandBuffer, _, VkSemaphore, _, _>(
+            queue.raw.submit::<<B as hal::Backend>::CommandBuffer, _, VkSemaphore, _, _>(
                 submission,
                 fence.as_ref(),
             )
@@ -1127,12 +1396,52 @@ pub extern "C" fn gfxQueueSubmit(
 }
 #[inline]
 pub extern "C" fn gfxQueueWaitIdle(queue: VkQueue) -> VkResult {
-    let _ = queue.wait_idle();
-    VkResult::VK_SUCCESS
+    if queue.lost.is_lost() {
+        return VkResult::VK_ERROR_DEVICE_LOST;
+    }
+    queue.flush();
+    match queue.raw.wait_idle() {
+        Ok(()) => VkResult::VK_SUCCESS,
+        Err(e) => map_host_execution_error(&queue.lost, e),
+    }
 }
 #[inline]
 pub extern "C" fn gfxDeviceWaitIdle(gpu: VkDevice) -> VkResult {
-    let _ = gpu.device.wait_idle();
+    if gpu.lost.is_lost() {
+        return VkResult::VK_ERROR_DEVICE_LOST;
+    }
+    gpu.pending.flush();
+    match gpu.device.wait_idle() {
+        Ok(()) => VkResult::VK_SUCCESS,
+        Err(e) => map_host_execution_error(&gpu.lost, e),
+    }
+}
+#[inline]
+pub extern "C" fn gfxGetDeviceFaultInfoEXT(
+    gpu: VkDevice,
+    pFaultCounts: *mut VkDeviceFaultCountsEXT,
+    pFaultInfo: *mut VkDeviceFaultInfoEXT,
+) -> VkResult {
+    // The backends don't report fault addresses or vendor data,
+    // only the operation that observed the loss is known.
+    let counts = unsafe { &mut *pFaultCounts };
+    counts.addressInfoCount = 0;
+    counts.vendorInfoCount = 0;
+    counts.vendorBinarySize = 0;
+
+    if let Some(info) = unsafe { pFaultInfo.as_mut() } {
+        let description = match gpu.lost.reason() {
+            Some(reason) => format!("Device lost on {}", reason),
+            None => String::new(),
+        };
+        let bytes = description.as_bytes();
+        let len = bytes.len().min(info.description.len() - 1);
+        for (dst, &src) in info.description.iter_mut().zip(&bytes[..len]) {
+            *dst = src as _;
+        }
+        info.description[len] = 0;
+    }
+
     VkResult::VK_SUCCESS
 }
 #[inline]
@@ -1142,6 +1451,12 @@ pub extern "C" fn gfxAllocateMemory(
     _pAllocator: *const VkAllocationCallbacks,
     pMemory: *mut VkDeviceMemory,
 ) -> VkResult {
+    let count = gpu.allocation_count.fetch_add(1, Ordering::Relaxed) + 1;
+    if exceeds_limit("maxMemoryAllocationCount", count as u64, gpu.limits.maxMemoryAllocationCount as u64) {
+        gpu.allocation_count.fetch_sub(1, Ordering::Relaxed);
+        return VkResult::VK_ERROR_TOO_MANY_OBJECTS;
+    }
+
     unsafe {
         let info = &*pAllocateInfo;
         let memory = gpu.device
@@ -1151,7 +1466,9 @@ pub extern "C" fn gfxAllocateMemory(
             )
             .unwrap(); // TODO:
 
-        *pMemory = Handle::new(memory);
+        let memory = Handle::new(memory).owned_by(gpu);
+        gpu.memory_sizes.lock().unwrap().insert(memory.key(), info.allocationSize);
+        *pMemory = memory;
     }
     VkResult::VK_SUCCESS
 }
@@ -1161,7 +1478,10 @@ pub extern "C" fn gfxFreeMemory(
     memory: VkDeviceMemory,
     _pAllocator: *const VkAllocationCallbacks,
 ) {
-    if let Some(mem) = memory.unbox() {
+    gpu.mapped_memory.lock().unwrap().remove(&memory.key());
+    gpu.memory_sizes.lock().unwrap().remove(&memory.key());
+    if let Some(mem) = memory.check_owner(gpu).unbox() {
+        gpu.allocation_count.fetch_sub(1, Ordering::Relaxed);
         unsafe {
             gpu.device.free_memory(mem);
         }
@@ -1176,10 +1496,12 @@ pub extern "C" fn gfxMapMemory(
     _flags: VkMemoryMapFlags,
     ppData: *mut *mut ::std::os::raw::c_void,
 ) -> VkResult {
-    let range = if size == VK_WHOLE_SIZE as VkDeviceSize {
-        (Some(offset), None)
+    memory.check_owner(gpu);
+    let (range, end) = if size == VK_WHOLE_SIZE as VkDeviceSize {
+        let allocation_size = gpu.memory_sizes.lock().unwrap().get(&memory.key()).cloned();
+        ((Some(offset), None), allocation_size.unwrap_or(offset))
     } else {
-        (Some(offset), Some(offset + size))
+        ((Some(offset), Some(offset + size)), offset + size)
     };
 
     unsafe {
@@ -1187,11 +1509,18 @@ pub extern "C" fn gfxMapMemory(
             .map_memory(&memory, range)
             .unwrap() as *mut _; // TODO
     }
+    // read back by the triangle fan emulation
+    gpu.mapped_memory
+        .lock()
+        .unwrap()
+        .insert(memory.key(), (offset, end, unsafe { *ppData } as usize));
 
     VkResult::VK_SUCCESS
 }
 #[inline]
 pub extern "C" fn gfxUnmapMemory(gpu: VkDevice, memory: VkDeviceMemory) {
+    memory.check_owner(gpu);
+    gpu.mapped_memory.lock().unwrap().remove(&memory.key());
     unsafe {
         gpu.device.unmap_memory(&memory);
     }
@@ -1265,11 +1594,17 @@ pub extern "C" fn gfxBindBufferMemory(
     memory: VkDeviceMemory,
     memoryOffset: VkDeviceSize,
 ) -> VkResult {
+    buffer.check_owner(gpu);
+    memory.check_owner(gpu);
     unsafe {
         gpu.device
             .bind_buffer_memory(&memory, memoryOffset, &mut *buffer)
             .unwrap(); //TODO
     }
+    gpu.buffer_memory
+        .lock()
+        .unwrap()
+        .insert(buffer.key(), (memory.key(), memoryOffset));
     VkResult::VK_SUCCESS
 }
 #[inline]
@@ -1279,6 +1614,8 @@ pub extern "C" fn gfxBindImageMemory(
     memory: VkDeviceMemory,
     memoryOffset: VkDeviceSize,
 ) -> VkResult {
+    image.check_owner(gpu);
+    memory.check_owner(gpu);
     unsafe {
         gpu.device
             .bind_image_memory(&memory, memoryOffset, &mut image.raw)
@@ -1292,6 +1629,7 @@ pub extern "C" fn gfxGetBufferMemoryRequirements(
     buffer: VkBuffer,
     pMemoryRequirements: *mut VkMemoryRequirements,
 ) {
+    buffer.check_owner(gpu);
     let req = unsafe {
         gpu.device.get_buffer_requirements(&*buffer)
     };
@@ -1308,6 +1646,7 @@ pub extern "C" fn gfxGetImageMemoryRequirements(
     image: VkImage,
     pMemoryRequirements: *mut VkMemoryRequirements,
 ) {
+    image.check_owner(gpu);
     let req = unsafe {
         gpu.device.get_image_requirements(&image.raw)
     };
@@ -1323,10 +1662,11 @@ pub extern "C" fn gfxGetImageMemoryRequirements(
 pub extern "C" fn gfxGetImageSparseMemoryRequirements(
     _device: VkDevice,
     _image: VkImage,
-    _pSparseMemoryRequirementCount: *mut u32,
+    pSparseMemoryRequirementCount: *mut u32,
     _pSparseMemoryRequirements: *mut VkSparseImageMemoryRequirements,
 ) {
-    unimplemented!()
+    // Images are never created with `VK_IMAGE_CREATE_SPARSE_RESIDENCY_BIT`.
+    unsafe { *pSparseMemoryRequirementCount = 0; }
 }
 #[inline]
 pub extern "C" fn gfxGetPhysicalDeviceSparseImageFormatProperties(
@@ -1339,16 +1679,74 @@ pub extern "C" fn gfxGetPhysicalDeviceSparseImageFormatProperties(
     pPropertyCount: *mut u32,
     _pProperties: *mut VkSparseImageFormatProperties,
 ) {
-    unsafe { *pPropertyCount = 0; } //TODO
+    // `sparseResidency*` features are not exposed, so no format supports sparse images.
+    unsafe { *pPropertyCount = 0; }
 }
 #[inline]
 pub extern "C" fn gfxQueueBindSparse(
-    _queue: VkQueue,
-    _bindInfoCount: u32,
-    _pBindInfo: *const VkBindSparseInfo,
-    _fence: VkFence,
+    mut queue: VkQueue,
+    bindInfoCount: u32,
+    pBindInfo: *const VkBindSparseInfo,
+    fence: VkFence,
 ) -> VkResult {
-    unimplemented!()
+    use std::iter::empty;
+
+    if queue.lost.is_lost() {
+        return VkResult::VK_ERROR_DEVICE_LOST;
+    }
+    queue.flush();
+
+    let bind_infos = unsafe { make_slice(pBindInfo, bindInfoCount) };
+    // Sparse resources can't be created without `sparseBinding`,
+    // so only the synchronization part of the batches is meaningful.
+    let binds_memory = bind_infos.iter().any(|info| {
+        info.bufferBindCount != 0 || info.imageOpaqueBindCount != 0 || info.imageBindCount != 0
+    });
+    if binds_memory {
+        error!("Sparse memory binding is not supported");
+        return VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY;
+    }
+
+    for (i, info) in bind_infos.iter().enumerate() {
+        let wait_semaphores = unsafe {
+            make_slice(info.pWaitSemaphores, info.waitSemaphoreCount)
+                .into_iter()
+                .map(|semaphore| (&**semaphore, pso::PipelineStage::all()))
+        };
+        let signal_semaphores = unsafe {
+            make_slice(info.pSignalSemaphores, info.signalSemaphoreCount)
+                .into_iter()
+                .map(|semaphore| &**semaphore)
+        };
+
+        let submission = hal::queue::Submission {
+            command_buffers: empty::<&<B as hal::Backend>::CommandBuffer>(),
+            wait_semaphores,
+            signal_semaphores,
+        };
+        let fence = if i + 1 == bind_infos.len() {
+            fence.as_ref()
+        } else {
+            None
+        };
+        unsafe { queue.raw.submit(submission, fence); }
+    }
+
+    if bind_infos.is_empty() {
+        let submission = hal::queue::Submission {
+            command_buffers: empty(),
+            wait_semaphores: empty(),
+            signal_semaphores: empty(),
+        };
+        unsafe {
+            queue.raw.submit::<<B as hal::Backend>::CommandBuffer, _, VkSemaphore, _, _>(
+                submission,
+                fence.as_ref(),
+            )
+        };
+    }
+
+    VkResult::VK_SUCCESS
 }
 #[inline]
 pub extern "C" fn gfxCreateFence(
@@ -1366,7 +1764,7 @@ pub extern "C" fn gfxCreateFence(
     };
 
     unsafe {
-        *pFence = Handle::new(fence);
+        *pFence = Handle::new(fence).owned_by(gpu);
     }
 
     VkResult::VK_SUCCESS
@@ -1377,7 +1775,7 @@ pub extern "C" fn gfxDestroyFence(
     fence: VkFence,
     _pAllocator: *const VkAllocationCallbacks,
 ) {
-    if let Some(fence) = fence.unbox() {
+    if let Some(fence) = fence.check_owner(gpu).unbox() {
         unsafe {
             gpu.device.destroy_fence(fence);
         }
@@ -1405,12 +1803,20 @@ pub extern "C" fn gfxResetFences(
 }
 #[inline]
 pub extern "C" fn gfxGetFenceStatus(gpu: VkDevice, fence: VkFence) -> VkResult {
+    fence.check_owner(gpu);
+    if gpu.lost.is_lost() {
+        return VkResult::VK_ERROR_DEVICE_LOST;
+    }
+    gpu.pending.flush();
     match unsafe {
         gpu.device.get_fence_status(&*fence)
     } {
         Ok(true) => VkResult::VK_SUCCESS,
         Ok(false) => VkResult::VK_NOT_READY,
-        Err(hal::device::DeviceLost) => VkResult::VK_ERROR_DEVICE_LOST,
+        Err(hal::device::DeviceLost) => {
+            gpu.lost.mark("fence status");
+            VkResult::VK_ERROR_DEVICE_LOST
+        }
     }
 }
 #[inline]
@@ -1421,6 +1827,11 @@ pub extern "C" fn gfxWaitForFences(
     waitAll: VkBool32,
     timeout: u64,
 ) -> VkResult {
+    if gpu.lost.is_lost() {
+        return VkResult::VK_ERROR_DEVICE_LOST;
+    }
+    gpu.pending.flush();
+
     let result = match fenceCount {
         0 => Ok(true),
         1 => unsafe {
@@ -1447,7 +1858,10 @@ pub extern "C" fn gfxWaitForFences(
         Ok(true) => VkResult::VK_SUCCESS,
         Ok(false) => VkResult::VK_TIMEOUT,
         Err(hal::device::OomOrDeviceLost::OutOfMemory(oom)) => map_oom(oom),
-        Err(hal::device::OomOrDeviceLost::DeviceLost(hal::device::DeviceLost)) => VkResult::VK_ERROR_DEVICE_LOST,
+        Err(hal::device::OomOrDeviceLost::DeviceLost(hal::device::DeviceLost)) => {
+            gpu.lost.mark("fence wait");
+            VkResult::VK_ERROR_DEVICE_LOST
+        }
     }
 }
 #[inline]
@@ -1463,7 +1877,7 @@ pub extern "C" fn gfxCreateSemaphore(
     };
 
     unsafe {
-        *pSemaphore = Handle::new(semaphore);
+        *pSemaphore = Handle::new(semaphore).owned_by(gpu);
     }
     VkResult::VK_SUCCESS
 }
@@ -1473,7 +1887,7 @@ pub extern "C" fn gfxDestroySemaphore(
     semaphore: VkSemaphore,
     _pAllocator: *const VkAllocationCallbacks,
 ) {
-    if let Some(sem) = semaphore.unbox() {
+    if let Some(sem) = semaphore.check_owner(gpu).unbox() {
         unsafe {
             gpu.device.destroy_semaphore(sem);
         }
@@ -1529,7 +1943,7 @@ pub extern "C" fn gfxCreateQueryPool(
 
     match pool {
         Ok(pool) => {
-            unsafe { *pQueryPool = Handle::new(pool) };
+            unsafe { *pQueryPool = Handle::new(pool).owned_by(gpu) };
             VkResult::VK_SUCCESS
         }
         Err(_) => {
@@ -1544,7 +1958,7 @@ pub extern "C" fn gfxDestroyQueryPool(
     queryPool: VkQueryPool,
     _pAllocator: *const VkAllocationCallbacks,
 ) {
-    if let Some(pool) = queryPool.unbox() {
+    if let Some(pool) = queryPool.check_owner(gpu).unbox() {
         unsafe {
             gpu.device.destroy_query_pool(pool);
         }
@@ -1561,6 +1975,12 @@ pub extern "C" fn gfxGetQueryPoolResults(
     stride: VkDeviceSize,
     flags: VkQueryResultFlags,
 ) -> VkResult {
+    queryPool.check_owner(gpu);
+    if gpu.lost.is_lost() {
+        return VkResult::VK_ERROR_DEVICE_LOST;
+    }
+    gpu.pending.flush();
+
     let result = unsafe {
         gpu.device.get_query_pool_results(
             &*queryPool,
@@ -1573,7 +1993,11 @@ pub extern "C" fn gfxGetQueryPoolResults(
     match result {
         Ok(true) => VkResult::VK_SUCCESS,
         Ok(false) => VkResult::VK_NOT_READY,
-        Err(_) => VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY,
+        Err(hal::device::OomOrDeviceLost::OutOfMemory(oom)) => map_oom(oom),
+        Err(hal::device::OomOrDeviceLost::DeviceLost(hal::device::DeviceLost)) => {
+            gpu.lost.mark("query pool results");
+            VkResult::VK_ERROR_DEVICE_LOST
+        }
     }
 }
 #[inline]
@@ -1584,14 +2008,18 @@ pub extern "C" fn gfxCreateBuffer(
     pBuffer: *mut VkBuffer,
 ) -> VkResult {
     let info = unsafe { &*pCreateInfo };
-    assert_eq!(info.sharingMode, VkSharingMode::VK_SHARING_MODE_EXCLUSIVE); // TODO
-    assert_eq!(info.flags, 0); // TODO
+    check_sharing_mode(&gpu, info.sharingMode, info.queueFamilyIndexCount, info.pQueueFamilyIndices);
+    // All of the buffer creation flags are about sparse residency.
+    if info.flags != 0 {
+        error!("Sparse buffers are not supported: {:?}", info.flags);
+        return VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY;
+    }
 
     unsafe {
         let buffer = gpu.device
             .create_buffer(info.size, conv::map_buffer_usage(info.usage))
             .expect("Error on creating buffer");
-        *pBuffer = Handle::new(buffer);
+        *pBuffer = Handle::new(buffer).owned_by(gpu);
     };
     VkResult::VK_SUCCESS
 }
@@ -1601,7 +2029,8 @@ pub extern "C" fn gfxDestroyBuffer(
     buffer: VkBuffer,
     _pAllocator: *const VkAllocationCallbacks,
 ) {
-    if let Some(buffer) = buffer.unbox() {
+    gpu.buffer_memory.lock().unwrap().remove(&buffer.key());
+    if let Some(buffer) = buffer.check_owner(gpu).unbox() {
         unsafe {
             gpu.device.destroy_buffer(buffer);
         }
@@ -1615,6 +2044,7 @@ pub extern "C" fn gfxCreateBufferView(
     pView: *mut VkBufferView,
 ) -> VkResult {
     let info = unsafe { &*pCreateInfo };
+    info.buffer.check_owner(gpu);
     let end = if info.range as i32 == VK_WHOLE_SIZE {
         None
     } else {
@@ -1632,7 +2062,7 @@ pub extern "C" fn gfxCreateBufferView(
     match view_result {
         Ok(view) => {
             unsafe {
-                *pView = Handle::new(view);
+                *pView = Handle::new(view).owned_by(gpu);
             }
             VkResult::VK_SUCCESS
         }
@@ -1648,7 +2078,7 @@ pub extern "C" fn gfxDestroyBufferView(
     view: VkBufferView,
     _pAllocator: *const VkAllocationCallbacks,
 ) {
-    if let Some(v) = view.unbox() {
+    if let Some(v) = view.check_owner(gpu).unbox() {
         unsafe {
             gpu.device.destroy_buffer_view(v);
         }
@@ -1662,10 +2092,38 @@ pub extern "C" fn gfxCreateImage(
     pImage: *mut VkImage,
 ) -> VkResult {
     let info = unsafe { &*pCreateInfo };
-    assert_eq!(info.sharingMode, VkSharingMode::VK_SHARING_MODE_EXCLUSIVE); // TODO
+    check_sharing_mode(&gpu, info.sharingMode, info.queueFamilyIndexCount, info.pQueueFamilyIndices);
+    let sparse_flags = VkImageCreateFlagBits::VK_IMAGE_CREATE_SPARSE_BINDING_BIT as u32
+        | VkImageCreateFlagBits::VK_IMAGE_CREATE_SPARSE_RESIDENCY_BIT as u32
+        | VkImageCreateFlagBits::VK_IMAGE_CREATE_SPARSE_ALIASED_BIT as u32;
+    if info.flags & sparse_flags != 0 {
+        error!("Sparse images are not supported: {:?}", info.flags);
+        return VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY;
+    }
     if info.initialLayout != VkImageLayout::VK_IMAGE_LAYOUT_UNDEFINED {
         warn!("unexpected initial layout: {:?}", info.initialLayout);
     }
+    let cube = info.flags & VkImageCreateFlagBits::VK_IMAGE_CREATE_CUBE_COMPATIBLE_BIT as u32 != 0;
+    let (dimension_name, max_dimension) = match info.imageType {
+        VkImageType::VK_IMAGE_TYPE_1D => ("maxImageDimension1D", gpu.limits.maxImageDimension1D),
+        VkImageType::VK_IMAGE_TYPE_3D => ("maxImageDimension3D", gpu.limits.maxImageDimension3D),
+        _ if cube => ("maxImageDimensionCube", gpu.limits.maxImageDimensionCube),
+        _ => ("maxImageDimension2D", gpu.limits.maxImageDimension2D),
+    };
+    let dimension = info.extent.width.max(info.extent.height).max(info.extent.depth);
+    if exceeds_limit(dimension_name, dimension as _, max_dimension as _) ||
+        exceeds_limit("maxImageArrayLayers", info.arrayLayers as _, gpu.limits.maxImageArrayLayers as _)
+    {
+        return VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY;
+    }
+
+    let format = match conv::map_format(info.format) {
+        Some(format) => format,
+        None => {
+            error!("Image format {:?} is not supported", info.format);
+            return VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY;
+        }
+    };
 
     let kind = conv::map_image_kind(
         info.imageType,
@@ -1678,8 +2136,7 @@ pub extern "C" fn gfxCreateImage(
             .create_image(
                 kind,
                 info.mipLevels as _,
-                conv::map_format(info.format)
-                    .expect(&format!("Unsupported image format: {:?}", info.format)),
+                format,
                 conv::map_tiling(info.tiling),
                 conv::map_image_usage(info.usage),
                 conv::map_image_create_flags(info.flags),
@@ -1690,7 +2147,7 @@ pub extern "C" fn gfxCreateImage(
             raw: image,
             mip_levels: info.mipLevels,
             array_layers: info.arrayLayers,
-        });
+        }).owned_by(gpu);
     }
 
     VkResult::VK_SUCCESS
@@ -1701,7 +2158,7 @@ pub extern "C" fn gfxDestroyImage(
     image: VkImage,
     _pAllocator: *const VkAllocationCallbacks,
 ) {
-    if let Some(image) = image.unbox() {
+    if let Some(image) = image.check_owner(gpu).unbox() {
         unsafe {
             gpu.device.destroy_image(image.raw);
         }
@@ -1714,6 +2171,7 @@ pub extern "C" fn gfxGetImageSubresourceLayout(
     pSubresource: *const VkImageSubresource,
     pLayout: *mut VkSubresourceLayout,
 ) {
+    image.check_owner(gpu);
     let footprint = unsafe {
         gpu.device.get_image_subresource_footprint(
             &image.raw,
@@ -1741,11 +2199,19 @@ pub extern "C" fn gfxCreateImageView(
     pView: *mut VkImageView,
 ) -> VkResult {
     let info = unsafe { &*pCreateInfo };
+    info.image.check_owner(gpu);
+    let format = match conv::map_format(info.format) {
+        Some(format) => format,
+        None => {
+            error!("Image view format {:?} is not supported", info.format);
+            return VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY;
+        }
+    };
     let view = unsafe {
         gpu.device.create_image_view(
             &info.image.raw,
             conv::map_view_kind(info.viewType),
-            conv::map_format(info.format).unwrap(),
+            format,
             conv::map_swizzle(info.components),
             info.image.map_subresource_range(info.subresourceRange),
         )
@@ -1753,7 +2219,7 @@ pub extern "C" fn gfxCreateImageView(
 
     match view {
         Ok(view) => {
-            unsafe { *pView = Handle::new(view) };
+            unsafe { *pView = Handle::new(view).owned_by(gpu) };
             VkResult::VK_SUCCESS
         }
         Err(err) => panic!("Unexpected image view creation error: {:?}", err),
@@ -1765,7 +2231,7 @@ pub extern "C" fn gfxDestroyImageView(
     imageView: VkImageView,
     _pAllocator: *const VkAllocationCallbacks,
 ) {
-    if let Some(view) = imageView.unbox() {
+    if let Some(view) = imageView.check_owner(gpu).unbox() {
         unsafe {
             gpu.device.destroy_image_view(view);
         }
@@ -1784,7 +2250,7 @@ pub extern "C" fn gfxCreateShaderModule(
         let shader_module = gpu.device
             .create_shader_module(code)
             .expect("Error creating shader module"); // TODO
-        *pShaderModule = Handle::new(shader_module);
+        *pShaderModule = Handle::new(shader_module).owned_by(gpu);
     }
     VkResult::VK_SUCCESS
 }
@@ -1794,7 +2260,7 @@ pub extern "C" fn gfxDestroyShaderModule(
     shaderModule: VkShaderModule,
     _pAllocator: *const VkAllocationCallbacks,
 ) {
-    if let Some(module) = shaderModule.unbox() {
+    if let Some(module) = shaderModule.check_owner(gpu).unbox() {
         unsafe {
             gpu.device.destroy_shader_module(module);
         }
@@ -1822,7 +2288,7 @@ pub extern "C" fn gfxCreatePipelineCache(
         Ok(cache) => cache,
         Err(oom) => return map_oom(oom),
     };
-    unsafe { *pPipelineCache = Handle::new(cache) };
+    unsafe { *pPipelineCache = Handle::new(cache).owned_by(gpu) };
 
     VkResult::VK_SUCCESS
 }
@@ -1832,7 +2298,7 @@ pub extern "C" fn gfxDestroyPipelineCache(
     pipelineCache: VkPipelineCache,
     _pAllocator: *const VkAllocationCallbacks,
 ) {
-    if let Some(cache) = pipelineCache.unbox() {
+    if let Some(cache) = pipelineCache.check_owner(gpu).unbox() {
         unsafe {
             gpu.device.destroy_pipeline_cache(cache);
         }
@@ -1856,6 +2322,7 @@ pub extern "C" fn gfxMergePipelineCaches(
     srcCacheCount: u32,
     pSrcCaches: *const VkPipelineCache,
 ) -> VkResult {
+    dstCache.check_owner(gpu);
     match unsafe {
         let caches = slice::from_raw_parts(pSrcCaches, srcCacheCount as usize);
         gpu.device.merge_pipeline_caches(&*dstCache, caches.iter().map(|h| &**h))
@@ -1878,6 +2345,74 @@ pub extern "C" fn gfxCreateGraphicsPipelines(
         slice::from_raw_parts(pCreateInfos, createInfoCount as _)
     };
 
+    let batch_flags = infos.iter().map(|info| info.flags).collect::<Vec<_>>();
+    let valid_parents = infos
+        .iter()
+        .enumerate()
+        .map(|(i, info)| {
+            !invalid_pipeline_parent(info.flags, info.basePipelineHandle, info.basePipelineIndex, i, &batch_flags, true)
+        })
+        .collect::<Vec<_>>();
+    let mut attribute_formats = Vec::with_capacity(infos.len());
+    for info in infos {
+        let mut formats = Vec::new();
+        if let Some(input) = unsafe { info.pVertexInputState.as_ref() } {
+            let limits = &gpu.limits;
+            if exceeds_limit("maxVertexInputBindings", input.vertexBindingDescriptionCount as _, limits.maxVertexInputBindings as _) ||
+                exceeds_limit("maxVertexInputAttributes", input.vertexAttributeDescriptionCount as _, limits.maxVertexInputAttributes as _)
+            {
+                return fail_pipelines(pPipelines, createInfoCount, VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY);
+            }
+            let attributes = unsafe {
+                make_slice(input.pVertexAttributeDescriptions, input.vertexAttributeDescriptionCount)
+            };
+            for attrib in attributes {
+                match conv::map_format(attrib.format) {
+                    Some(format) => formats.push(format),
+                    None => {
+                        error!("Vertex attribute format {:?} is not supported", attrib.format);
+                        return fail_pipelines(pPipelines, createInfoCount, VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY);
+                    }
+                }
+            }
+        }
+        attribute_formats.push(formats);
+        if let Some(input) = unsafe { info.pInputAssemblyState.as_ref() } {
+            if input.topology == VkPrimitiveTopology::VK_PRIMITIVE_TOPOLOGY_TRIANGLE_FAN &&
+                !gpu.native_fans && gpu.settings.emulate_triangle_fans != Some(true)
+            {
+                error!("Triangle fans are not supported by the backend, unless `emulate_triangle_fans` is set");
+                return fail_pipelines(pPipelines, createInfoCount, VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY);
+            }
+        }
+        let rasterizer_discard = unsafe { &*info.pRasterizationState }.rasterizerDiscardEnable == VK_TRUE;
+        if let Some(viewport) = unsafe { info.pViewportState.as_ref() } {
+            if !rasterizer_discard && exceeds_limit("maxViewports", viewport.viewportCount as _, gpu.limits.maxViewports as _) {
+                return fail_pipelines(pPipelines, createInfoCount, VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY);
+            }
+        }
+        if rasterizer_discard {
+            continue;
+        }
+        // usage of disabled features fails like the limits, see `exceeds_limit`
+        if let Some(multisampling) = unsafe { info.pMultisampleState.as_ref() } {
+            if multisampling.sampleShadingEnable == VK_TRUE && !gpu.features.contains(Features::SAMPLE_RATE_SHADING) {
+                error!("Sample shading requires the sampleRateShading feature");
+                return fail_pipelines(pPipelines, createInfoCount, VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY);
+            }
+            if multisampling.alphaToOneEnable == VK_TRUE && !gpu.features.contains(Features::ALPHA_TO_ONE) {
+                error!("Alpha to one requires the alphaToOne feature");
+                return fail_pipelines(pPipelines, createInfoCount, VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY);
+            }
+        }
+        if let Some(state) = depth_stencil_state(info) {
+            if state.depthBoundsTestEnable == VK_TRUE && !gpu.features.contains(Features::DEPTH_BOUNDS) {
+                error!("Depth bounds test requires the depthBounds feature");
+                return fail_pipelines(pPipelines, createInfoCount, VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY);
+            }
+        }
+    }
+
     let mut spec_constants = Vec::new();
     let mut spec_data = Vec::new();
 
@@ -1909,7 +2444,7 @@ pub extern "C" fn gfxCreateGraphicsPipelines(
 
     let mut cur_specialization = 0;
 
-    let descs = infos.into_iter().map(|info| {
+    let descs = infos.into_iter().zip(&valid_parents).zip(&attribute_formats).map(|((info, &valid_parent), formats)| {
         let rasterizer_discard = unsafe { &*info.pRasterizationState }.rasterizerDiscardEnable == VK_TRUE;
 
         let empty_dyn_states = [];
@@ -2029,12 +2564,13 @@ pub extern "C" fn gfxCreateGraphicsPipelines(
 
             let attributes = attributes
                 .into_iter()
-                .map(|attrib| {
+                .zip(formats)
+                .map(|(attrib, &format)| {
                     pso::AttributeDesc {
                         location: attrib.location,
                         binding: attrib.binding,
                         element: pso::Element {
-                            format: conv::map_format(attrib.format).unwrap(), // TODO: undefined allowed?
+                            format,
                             offset: attrib.offset,
                         },
                     }
@@ -2051,12 +2587,23 @@ pub extern "C" fn gfxCreateGraphicsPipelines(
                 .as_ref()
                 .map(|_| unsafe { &*info.pTessellationState });
 
-            if input_state.primitiveRestartEnable != VK_FALSE {
-                warn!("Primitive restart may not work as expected!");
-            }
+            let restart_enable = input_state.primitiveRestartEnable != VK_FALSE;
+            let emulated_strip = restart::Strip::emulated(input_state.topology, restart_enable);
+            let emulated_fan = !gpu.native_fans &&
+                input_state.topology == VkPrimitiveTopology::VK_PRIMITIVE_TOPOLOGY_TRIANGLE_FAN;
 
+            let topology = match input_state.topology {
+                // drawn as lists with expanded indices, see `fan.rs`
+                VkPrimitiveTopology::VK_PRIMITIVE_TOPOLOGY_TRIANGLE_FAN if emulated_fan => {
+                    if restart_enable {
+                        warn!("Primitive restart is not supported with triangle fans");
+                    }
+                    VkPrimitiveTopology::VK_PRIMITIVE_TOPOLOGY_TRIANGLE_LIST
+                }
+                other => emulated_strip.map_or(other, |strip| strip.list_topology()),
+            };
             let primitive = match conv::map_primitive_topology(
-                input_state.topology,
+                topology,
                 tessellation_state
                     .map(|state| state.patchControlPoints as _)
                     .unwrap_or(0),
@@ -2068,9 +2615,16 @@ pub extern "C" fn gfxCreateGraphicsPipelines(
                 },
             };
 
+            // the 16-bit variant is created separately, see `restart.rs`
+            let native_restart = restart_enable && emulated_strip.is_none() && !emulated_fan;
+
             pso::InputAssemblerDesc {
                 primitive,
-                primitive_restart: pso::PrimitiveRestart::Disabled, // TODO
+                primitive_restart: if native_restart {
+                    pso::PrimitiveRestart::U32
+                } else {
+                    pso::PrimitiveRestart::Disabled
+                },
             }
         };
 
@@ -2127,7 +2681,10 @@ pub extern "C" fn gfxCreateGraphicsPipelines(
                 } else {
                     None
                 },
-                sample_mask: !0, // TODO
+                sample_mask: conv::map_sample_mask(
+                    multisampling.rasterizationSamples,
+                    multisampling.pSampleMask,
+                ),
                 alpha_coverage: multisampling.alphaToCoverageEnable == VK_TRUE,
                 alpha_to_one: multisampling.alphaToOneEnable == VK_TRUE,
             })
@@ -2135,12 +2692,8 @@ pub extern "C" fn gfxCreateGraphicsPipelines(
             None
         };
 
-        // TODO: `pDepthStencilState` could contain garbage, but implementations
-        //        can ignore it in some circumstances. How to handle it?
         let depth_stencil = if !rasterizer_discard {
-            unsafe { info
-                .pDepthStencilState
-                .as_ref()
+            depth_stencil_state(info)
                 .map(|state| {
                     let depth_test = if state.depthTestEnable == VK_TRUE {
                         pso::DepthTest::On {
@@ -2185,8 +2738,6 @@ pub extern "C" fn gfxCreateGraphicsPipelines(
                         pso::StencilTest::Off
                     };
 
-                    // TODO: depth bounds
-
                     pso::DepthStencilDesc {
                         depth: depth_test,
                         depth_bounds: state.depthBoundsTestEnable == VK_TRUE,
@@ -2194,7 +2745,6 @@ pub extern "C" fn gfxCreateGraphicsPipelines(
                     }
                 })
                 .unwrap_or_default()
-            }
         } else {
             pso::DepthStencilDesc::default()
         };
@@ -2228,15 +2778,16 @@ pub extern "C" fn gfxCreateGraphicsPipelines(
             depth_bounds: if dyn_states.iter().any(|&ds| ds == VkDynamicState::VK_DYNAMIC_STATE_DEPTH_BOUNDS) {
                 None
             } else {
-                unsafe { info.pDepthStencilState.as_ref() }
-                    .map(|db| db.minDepthBounds .. db.maxDepthBounds)
+                depth_stencil_state(info)
+                    .filter(|state| !rasterizer_discard && state.depthBoundsTestEnable == VK_TRUE)
+                    .map(|state| state.minDepthBounds .. state.maxDepthBounds)
             },
         };
 
-        let layout = &*info.layout;
+        let layout = &*info.layout.check_owner(gpu);
         let subpass = pass::Subpass {
             index: info.subpass as _,
-            main_pass: &*info.renderPass,
+            main_pass: &info.renderPass.check_owner(gpu).raw,
         };
 
         let flags = {
@@ -2252,18 +2803,14 @@ pub extern "C" fn gfxCreateGraphicsPipelines(
             flags
         };
 
-        let parent = {
-            let is_derivative = info.flags & VkPipelineCreateFlagBits::VK_PIPELINE_CREATE_DERIVATIVE_BIT as u32 != 0;
-
-            if let Some(base_pso) = info.basePipelineHandle.as_ref() {
-                match *base_pso {
-                    Pipeline::Graphics(ref pso) => pso::BasePipeline::Pipeline(pso),
-                    Pipeline::Compute(_) => panic!("Base pipeline handle must be a graphics pipeline"),
-                }
-            } else if is_derivative && info.basePipelineIndex > 0 {
-                pso::BasePipeline::Index(info.basePipelineIndex as _)
-            } else {
-                pso::BasePipeline::None // TODO
+        // validated by `invalid_pipeline_parent`
+        let parent = if info.flags & VkPipelineCreateFlagBits::VK_PIPELINE_CREATE_DERIVATIVE_BIT as u32 == 0 || !valid_parent {
+            pso::BasePipeline::None
+        } else {
+            match info.basePipelineHandle.as_ref() {
+                Some(&Pipeline::Graphics { raw: ref pso, .. }) => pso::BasePipeline::Pipeline(pso),
+                Some(&Pipeline::Compute { .. }) => pso::BasePipeline::None,
+                None => pso::BasePipeline::Index(info.basePipelineIndex as _),
             }
         };
 
@@ -2282,17 +2829,39 @@ pub extern "C" fn gfxCreateGraphicsPipelines(
             flags,
             parent,
         }
-    });
+    }).collect::<Vec<_>>();
+
+    // pipelines restarting at 16-bit indices, for the backends baking the restart value
+    let variant_descs = descs
+        .iter()
+        .enumerate()
+        .filter(|&(_, desc)| restart::TYPED && match desc.input_assembler.primitive_restart {
+            pso::PrimitiveRestart::Disabled => false,
+            _ => true,
+        })
+        .map(|(i, desc)| {
+            let mut desc = desc.clone();
+            desc.input_assembler.primitive_restart = pso::PrimitiveRestart::U16;
+            desc.parent = pso::BasePipeline::None;
+            (i, desc)
+        })
+        .collect::<Vec<_>>();
 
     let pipelines = unsafe {
-        gpu.device.create_graphics_pipelines(descs, pipelineCache.as_ref())
+        gpu.device.create_graphics_pipelines(&descs, pipelineCache.as_ref())
+    };
+    let variants = unsafe {
+        gpu.device.create_graphics_pipelines(
+            variant_descs.iter().map(|&(_, ref desc)| desc),
+            pipelineCache.as_ref(),
+        )
     };
     let out_pipelines = unsafe {
         slice::from_raw_parts_mut(pPipelines, infos.len())
     };
 
-    if pipelines.iter().any(|p| p.is_err()) {
-        for pipeline in pipelines {
+    if pipelines.iter().chain(&variants).any(|p| p.is_err()) {
+        for pipeline in pipelines.into_iter().chain(variants) {
             if let Err(e) = pipeline {
                 error!("{}", e);
             }
@@ -2302,8 +2871,23 @@ pub extern "C" fn gfxCreateGraphicsPipelines(
         }
         VkResult::VK_ERROR_INCOMPATIBLE_DRIVER
     } else {
-        for (op, raw) in out_pipelines.iter_mut().zip(pipelines) {
-            *op = Handle::new(Pipeline::Graphics(raw.unwrap()));
+        let mut variants_u16 = infos.iter().map(|_| None).collect::<Vec<_>>();
+        for (&(i, _), variant) in variant_descs.iter().zip(variants) {
+            variants_u16[i] = variant.ok();
+        }
+        let outputs = out_pipelines.iter_mut().zip(pipelines).zip(variants_u16).zip(infos);
+        for (((op, raw), raw_u16), info) in outputs {
+            let input_state = unsafe { &*info.pInputAssemblyState };
+            let restart_enable = input_state.primitiveRestartEnable != VK_FALSE;
+            let pipeline = Pipeline::Graphics {
+                raw: raw.unwrap(),
+                raw_u16,
+                allow_derivatives: info.flags & VkPipelineCreateFlagBits::VK_PIPELINE_CREATE_ALLOW_DERIVATIVES_BIT as u32 != 0,
+                triangle_fan: !gpu.native_fans &&
+                    input_state.topology == VkPrimitiveTopology::VK_PRIMITIVE_TOPOLOGY_TRIANGLE_FAN,
+                restart: restart::Strip::emulated(input_state.topology, restart_enable),
+            };
+            *op = Handle::new(pipeline).owned_by(gpu);
         }
         VkResult::VK_SUCCESS
     }
@@ -2321,6 +2905,15 @@ pub extern "C" fn gfxCreateComputePipelines(
         slice::from_raw_parts(pCreateInfos, createInfoCount as _)
     };
 
+    let batch_flags = infos.iter().map(|info| info.flags).collect::<Vec<_>>();
+    let valid_parents = infos
+        .iter()
+        .enumerate()
+        .map(|(i, info)| {
+            !invalid_pipeline_parent(info.flags, info.basePipelineHandle, info.basePipelineIndex, i, &batch_flags, false)
+        })
+        .collect::<Vec<_>>();
+
     // Collect all information which we will borrow later. Need to work around
     // the borrow checker here.
     let mut spec_constants = Vec::new();
@@ -2350,7 +2943,8 @@ pub extern "C" fn gfxCreateComputePipelines(
     let mut cur_specialization = 0;
     let descs = infos
         .iter()
-        .map(|info| {
+        .zip(&valid_parents)
+        .map(|(info, &valid_parent)| {
             let name = unsafe { CStr::from_ptr(info.stage.pName) };
             let spec_count = unsafe {
                 info.stage.pSpecializationInfo
@@ -2368,7 +2962,7 @@ pub extern "C" fn gfxCreateComputePipelines(
             };
             cur_specialization += spec_count;
 
-            let layout = &*info.layout;
+            let layout = &*info.layout.check_owner(gpu);
             let flags = {
                 let mut flags = pso::PipelineCreationFlags::empty();
 
@@ -2382,18 +2976,14 @@ pub extern "C" fn gfxCreateComputePipelines(
                 flags
             };
 
-            let parent = {
-                let is_derivative = info.flags & VkPipelineCreateFlagBits::VK_PIPELINE_CREATE_DERIVATIVE_BIT as u32 != 0;
-
-                if let Some(base_pso) = info.basePipelineHandle.as_ref() {
-                    match *base_pso {
-                        Pipeline::Graphics(_) => panic!("Base pipeline handle must be a compute pipeline"),
-                        Pipeline::Compute(ref pso) => pso::BasePipeline::Pipeline(pso),
-                    }
-                } else if is_derivative && info.basePipelineIndex > 0 {
-                    pso::BasePipeline::Index(info.basePipelineIndex as _)
-                } else {
-                    pso::BasePipeline::None // TODO
+            // validated by `invalid_pipeline_parent`
+            let parent = if info.flags & VkPipelineCreateFlagBits::VK_PIPELINE_CREATE_DERIVATIVE_BIT as u32 == 0 || !valid_parent {
+                pso::BasePipeline::None
+            } else {
+                match info.basePipelineHandle.as_ref() {
+                    Some(&Pipeline::Compute { raw: ref pso, .. }) => pso::BasePipeline::Pipeline(pso),
+                    Some(&Pipeline::Graphics { .. }) => pso::BasePipeline::None,
+                    None => pso::BasePipeline::Index(info.basePipelineIndex as _),
                 }
             };
 
@@ -2423,8 +3013,12 @@ pub extern "C" fn gfxCreateComputePipelines(
         }
         VkResult::VK_ERROR_INCOMPATIBLE_DRIVER
     } else {
-        for (op, raw) in out_pipelines.iter_mut().zip(pipelines) {
-            *op = Handle::new(Pipeline::Compute(raw.unwrap()));
+        for ((op, raw), info) in out_pipelines.iter_mut().zip(pipelines).zip(infos) {
+            let pipeline = Pipeline::Compute {
+                raw: raw.unwrap(),
+                allow_derivatives: info.flags & VkPipelineCreateFlagBits::VK_PIPELINE_CREATE_ALLOW_DERIVATIVES_BIT as u32 != 0,
+            };
+            *op = Handle::new(pipeline).owned_by(gpu);
         }
         VkResult::VK_SUCCESS
     }
@@ -2435,12 +3029,15 @@ pub extern "C" fn gfxDestroyPipeline(
     pipeline: VkPipeline,
     _pAllocator: *const VkAllocationCallbacks,
 ) {
-    match pipeline.unbox() {
-        Some(Pipeline::Graphics(pipeline)) => unsafe {
-            gpu.device.destroy_graphics_pipeline(pipeline)
+    match pipeline.check_owner(gpu).unbox() {
+        Some(Pipeline::Graphics { raw, raw_u16, .. }) => unsafe {
+            gpu.device.destroy_graphics_pipeline(raw);
+            if let Some(raw_u16) = raw_u16 {
+                gpu.device.destroy_graphics_pipeline(raw_u16);
+            }
         }
-        Some(Pipeline::Compute(pipeline)) => unsafe {
-            gpu.device.destroy_compute_pipeline(pipeline)
+        Some(Pipeline::Compute { raw, .. }) => unsafe {
+            gpu.device.destroy_compute_pipeline(raw)
         }
         None => {}
     }
@@ -2459,6 +3056,16 @@ pub extern "C" fn gfxCreatePipelineLayout(
     let push_constants = unsafe {
         slice::from_raw_parts(info.pPushConstantRanges, info.pushConstantRangeCount as _)
     };
+    let push_constants_size = push_constants
+        .iter()
+        .map(|constant| constant.offset + constant.size)
+        .max()
+        .unwrap_or(0);
+    if exceeds_limit("maxBoundDescriptorSets", set_layouts.len() as _, gpu.limits.maxBoundDescriptorSets as _) ||
+        exceeds_limit("maxPushConstantsSize", push_constants_size as _, gpu.limits.maxPushConstantsSize as _)
+    {
+        return VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY;
+    }
 
     let layouts = set_layouts
         .iter()
@@ -2481,7 +3088,7 @@ pub extern "C" fn gfxCreatePipelineLayout(
         Err(oom) => return map_oom(oom),
     };
 
-    unsafe { *pPipelineLayout = Handle::new(pipeline_layout); }
+    unsafe { *pPipelineLayout = Handle::new(pipeline_layout).owned_by(gpu); }
     VkResult::VK_SUCCESS
 }
 #[inline]
@@ -2490,7 +3097,7 @@ pub extern "C" fn gfxDestroyPipelineLayout(
     pipelineLayout: VkPipelineLayout,
     _pAllocator: *const VkAllocationCallbacks,
 ) {
-    if let Some(layout) = pipelineLayout.unbox() {
+    if let Some(layout) = pipelineLayout.check_owner(gpu).unbox() {
         unsafe {
             gpu.device.destroy_pipeline_layout(layout);
         }
@@ -2504,15 +3111,58 @@ pub extern "C" fn gfxCreateSampler(
     pSampler: *mut VkSampler,
 ) -> VkResult {
     let info = unsafe { &*pCreateInfo };
+    // unsupported parameters fail like the limits, see `exceeds_limit`
+    if info.anisotropyEnable == VK_TRUE {
+        if !gpu.features.contains(Features::SAMPLER_ANISOTROPY) {
+            error!("Anisotropic filtering requires the samplerAnisotropy feature");
+            return VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY;
+        }
+        if info.maxAnisotropy < 1.0 || info.maxAnisotropy > gpu.limits.maxSamplerAnisotropy {
+            error!("maxAnisotropy of {} is out of the device range 1 ..= {}",
+                info.maxAnisotropy, gpu.limits.maxSamplerAnisotropy);
+            return VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY;
+        }
+    }
+    let wrap_mode = match (
+        conv::map_wrap_mode(info.addressModeU),
+        conv::map_wrap_mode(info.addressModeV),
+        conv::map_wrap_mode(info.addressModeW),
+    ) {
+        (Some(u), Some(v), Some(w)) => (u, v, w),
+        _ => {
+            error!("Sampler address modes {:?} are not supported",
+                (info.addressModeU, info.addressModeV, info.addressModeW));
+            return VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY;
+        }
+    };
+    if info.unnormalizedCoordinates == VK_TRUE {
+        // gfx-hal samplers always use normalized coordinates
+        error!("Unnormalized sampler coordinates are not supported by the backend");
+        return VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY;
+    }
+    let border = match conv::map_border_color(info.borderColor) {
+        Some(border) => border,
+        None => {
+            error!("Sampler border color {:?} is not supported", info.borderColor);
+            return VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY;
+        }
+    };
+    if !info.pNext.is_null() {
+        // notably `VkSamplerReductionModeCreateInfoEXT`, as gfx-hal has no reduction
+        // modes to expose `VK_EXT_sampler_filter_minmax` with
+        warn!("Sampler extension structures are not supported, ignoring");
+    }
+    let count = gpu.sampler_count.fetch_add(1, Ordering::Relaxed) + 1;
+    if exceeds_limit("maxSamplerAllocationCount", count as _, gpu.limits.maxSamplerAllocationCount as _) {
+        gpu.sampler_count.fetch_sub(1, Ordering::Relaxed);
+        return VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY;
+    }
+
     let gfx_info = hal::image::SamplerInfo {
         min_filter: conv::map_filter(info.minFilter),
         mag_filter: conv::map_filter(info.magFilter),
         mip_filter: conv::map_mipmap_filter(info.mipmapMode),
-        wrap_mode: (
-            conv::map_wrap_mode(info.addressModeU),
-            conv::map_wrap_mode(info.addressModeV),
-            conv::map_wrap_mode(info.addressModeW),
-        ),
+        wrap_mode,
         lod_bias: info.mipLodBias.into(),
         lod_range: info.minLod.into() .. info.maxLod.into(),
         comparison: if info.compareEnable == VK_TRUE {
@@ -2520,7 +3170,7 @@ pub extern "C" fn gfxCreateSampler(
         } else {
             None
         },
-        border: [0.0; 4].into(), // TODO
+        border,
         anisotropic: if info.anisotropyEnable == VK_TRUE {
             hal::image::Anisotropic::On(info.maxAnisotropy as _)
         } else {
@@ -2531,9 +3181,12 @@ pub extern "C" fn gfxCreateSampler(
         gpu.device.create_sampler(gfx_info)
     } {
         Ok(s) => s,
-        Err(alloc) => return map_alloc_error(alloc),
+        Err(alloc) => {
+            gpu.sampler_count.fetch_sub(1, Ordering::Relaxed);
+            return map_alloc_error(alloc);
+        }
     };
-    unsafe { *pSampler = Handle::new(sampler); }
+    unsafe { *pSampler = Handle::new(sampler).owned_by(gpu); }
     VkResult::VK_SUCCESS
 }
 #[inline]
@@ -2542,7 +3195,8 @@ pub extern "C" fn gfxDestroySampler(
     sampler: VkSampler,
     _pAllocator: *const VkAllocationCallbacks,
 ) {
-    if let Some(sam) = sampler.unbox() {
+    if let Some(sam) = sampler.check_owner(gpu).unbox() {
+        gpu.sampler_count.fetch_sub(1, Ordering::Relaxed);
         unsafe {
             gpu.device.destroy_sampler(sam);
         }
@@ -2590,7 +3244,7 @@ pub extern "C" fn gfxCreateDescriptorSetLayout(
         Err(oom) => return map_oom(oom),
     };
 
-    unsafe { *pSetLayout = Handle::new(set_layout); }
+    unsafe { *pSetLayout = Handle::new(set_layout).owned_by(gpu); }
     VkResult::VK_SUCCESS
 }
 #[inline]
@@ -2599,7 +3253,7 @@ pub extern "C" fn gfxDestroyDescriptorSetLayout(
     descriptorSetLayout: VkDescriptorSetLayout,
     _pAllocator: *const VkAllocationCallbacks,
 ) {
-    if let Some(layout) = descriptorSetLayout.unbox() {
+    if let Some(layout) = descriptorSetLayout.check_owner(gpu).unbox() {
         unsafe {
             gpu.device.destroy_descriptor_set_layout(layout);
         }
@@ -2645,9 +3299,10 @@ pub extern "C" fn gfxCreateDescriptorPool(
         } else {
             Some(Vec::with_capacity(max_sets))
         },
+        slab: HandleSlab::new(),
     };
 
-    unsafe { *pDescriptorPool = Handle::new(pool); }
+    unsafe { *pDescriptorPool = Handle::new(pool).owned_by(gpu); }
     VkResult::VK_SUCCESS
 }
 #[inline]
@@ -2656,7 +3311,7 @@ pub extern "C" fn gfxDestroyDescriptorPool(
     descriptorPool: VkDescriptorPool,
     _pAllocator: *const VkAllocationCallbacks,
 ) {
-    if let Some(pool) = descriptorPool.unbox() {
+    if let Some(pool) = descriptorPool.check_owner(gpu).unbox() {
         unsafe {
             gpu.device.destroy_descriptor_pool(pool.raw);
         }
@@ -2669,28 +3324,31 @@ pub extern "C" fn gfxDestroyDescriptorPool(
 }
 #[inline]
 pub extern "C" fn gfxResetDescriptorPool(
-    _gpu: VkDevice,
+    gpu: VkDevice,
     mut descriptorPool: VkDescriptorPool,
     _flags: VkDescriptorPoolResetFlags,
 ) -> VkResult {
+    descriptorPool.check_owner(gpu);
+    let super::DescriptorPool { ref mut raw, ref mut set_handles, ref mut slab, .. } = *descriptorPool;
     unsafe {
-        descriptorPool.raw.reset();
+        raw.reset();
     }
-    if let Some(ref mut sets) = descriptorPool.set_handles {
+    if let Some(ref mut sets) = *set_handles {
         for set in sets.drain(..) {
-            let _ = set.unbox();
+            let _ = set.unbox_into(slab);
         }
     }
     VkResult::VK_SUCCESS
 }
 #[inline]
 pub extern "C" fn gfxAllocateDescriptorSets(
-    _gpu: VkDevice,
+    gpu: VkDevice,
     pAllocateInfo: *const VkDescriptorSetAllocateInfo,
     pDescriptorSets: *mut VkDescriptorSet,
 ) -> VkResult {
     let info = unsafe { &mut *(pAllocateInfo as *mut VkDescriptorSetAllocateInfo) };
-    let super::DescriptorPool { ref mut raw, ref mut temp_sets, ref mut set_handles } = *info.descriptorPool;
+    info.descriptorPool.check_owner(gpu);
+    let super::DescriptorPool { ref mut raw, ref mut temp_sets, ref mut set_handles, ref mut slab } = *info.descriptorPool;
 
     let out_sets = unsafe {
         slice::from_raw_parts_mut(pDescriptorSets, info.descriptorSetCount as _)
@@ -2708,7 +3366,7 @@ pub extern "C" fn gfxAllocateDescriptorSets(
         Ok(()) => {
             assert_eq!(temp_sets.len(), info.descriptorSetCount as usize);
             for (set, raw_set) in out_sets.iter_mut().zip(temp_sets.drain(..)) {
-                *set = Handle::new(raw_set);
+                *set = Handle::new_in(raw_set, slab).owned_by(gpu);
             }
             if let Some(ref mut local_sets) = set_handles {
                 local_sets.extend_from_slice(out_sets);
@@ -2733,22 +3391,24 @@ pub extern "C" fn gfxAllocateDescriptorSets(
 }
 #[inline]
 pub extern "C" fn gfxFreeDescriptorSets(
-    _device: VkDevice,
+    gpu: VkDevice,
     mut descriptorPool: VkDescriptorPool,
     descriptorSetCount: u32,
     pDescriptorSets: *const VkDescriptorSet,
 ) -> VkResult {
+    descriptorPool.check_owner(gpu);
     let descriptor_sets = unsafe {
         slice::from_raw_parts(pDescriptorSets, descriptorSetCount as _)
     };
     assert!(descriptorPool.set_handles.is_none());
+    let super::DescriptorPool { ref mut raw, ref mut slab, .. } = *descriptorPool;
 
     let sets = descriptor_sets
         .into_iter()
-        .filter_map(|set| set.unbox());
+        .filter_map(|set| set.unbox_into(slab));
 
     unsafe {
-        descriptorPool.raw.free_sets(sets);
+        raw.free_sets(sets);
     }
 
     VkResult::VK_SUCCESS
@@ -2880,6 +3540,14 @@ pub extern "C" fn gfxCreateFramebuffer(
     pFramebuffer: *mut VkFramebuffer,
 ) -> VkResult {
     let info = unsafe { &*pCreateInfo };
+    info.renderPass.check_owner(gpu);
+
+    if exceeds_limit("maxFramebufferWidth", info.width as _, gpu.limits.maxFramebufferWidth as _) ||
+        exceeds_limit("maxFramebufferHeight", info.height as _, gpu.limits.maxFramebufferHeight as _) ||
+        exceeds_limit("maxFramebufferLayers", info.layers as _, gpu.limits.maxFramebufferLayers as _)
+    {
+        return VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY;
+    }
 
     let attachments_slice = unsafe {
         slice::from_raw_parts(info.pAttachments, info.attachmentCount as _)
@@ -2896,9 +3564,9 @@ pub extern "C" fn gfxCreateFramebuffer(
 
     unsafe {
         let framebuffer = gpu.device
-            .create_framebuffer(&*info.renderPass, attachments, extent)
+            .create_framebuffer(&info.renderPass.raw, attachments, extent)
             .unwrap();
-        *pFramebuffer = Handle::new(framebuffer);
+        *pFramebuffer = Handle::new(framebuffer).owned_by(gpu);
     }
 
     VkResult::VK_SUCCESS
@@ -2909,7 +3577,7 @@ pub extern "C" fn gfxDestroyFramebuffer(
     framebuffer: VkFramebuffer,
     _pAllocator: *const VkAllocationCallbacks,
 ) {
-    if let Some(fbo) = framebuffer.unbox() {
+    if let Some(fbo) = framebuffer.check_owner(gpu).unbox() {
         unsafe {
             gpu.device.destroy_framebuffer(fbo);
         }
@@ -2955,6 +3623,11 @@ pub extern "C" fn gfxCreateRenderPass(
     let subpasses_raw = unsafe {
         slice::from_raw_parts(info.pSubpasses, info.subpassCount as _)
     };
+    for subpass in subpasses_raw {
+        if exceeds_limit("maxColorAttachments", subpass.colorAttachmentCount as _, gpu.limits.maxColorAttachments as _) {
+            return VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY;
+        }
+    }
 
     // Store all attachment references, referenced by the subpasses.
     let mut attachment_refs = Vec::with_capacity(subpasses_raw.len());
@@ -3063,15 +3736,19 @@ pub extern "C" fn gfxCreateRenderPass(
             }
         });
 
+    let depth_stencil = attachment_refs
+        .iter()
+        .map(|attachment_ref| attachment_ref.depth_stencil.is_some())
+        .collect();
     let render_pass = match unsafe {
         gpu.device.create_render_pass(attachments, subpasses, dependencies)
     } {
-        Ok(pass) => pass,
+        Ok(raw) => RenderPass { raw, depth_stencil },
         Err(oom) => return map_oom(oom),
     };
 
     unsafe {
-        *pRenderPass = Handle::new(render_pass);
+        *pRenderPass = Handle::new(render_pass).owned_by(gpu);
     }
 
     VkResult::VK_SUCCESS
@@ -3082,9 +3759,9 @@ pub extern "C" fn gfxDestroyRenderPass(
     renderPass: VkRenderPass,
     _pAllocator: *const VkAllocationCallbacks,
 ) {
-    if let Some(rp) = renderPass.unbox() {
+    if let Some(rp) = renderPass.check_owner(gpu).unbox() {
         unsafe {
-            gpu.device.destroy_render_pass(rp);
+            gpu.device.destroy_render_pass(rp.raw);
         }
     }
 }
@@ -3129,8 +3806,9 @@ pub extern "C" fn gfxCreateCommandPool(
             Err(oom) => return map_oom(oom),
         },
         buffers: Vec::new(),
+        slab: DispatchHandleSlab::new(),
     };
-    unsafe { *pCommandPool = Handle::new(pool) };
+    unsafe { *pCommandPool = Handle::new(pool).owned_by(gpu) };
     VkResult::VK_SUCCESS
 }
 
@@ -3140,9 +3818,11 @@ pub extern "C" fn gfxDestroyCommandPool(
     commandPool: VkCommandPool,
     _pAllocator: *const VkAllocationCallbacks,
 ) {
-    if let Some(cp) = commandPool.unbox() {
+    if let Some(cp) = commandPool.check_owner(gpu).unbox() {
         for cmd_buf in cp.buffers {
-            let _ = cmd_buf.unbox();
+            if let Some(mut cmd_buf) = cmd_buf.unbox() {
+                cmd_buf.fan.release(&gpu);
+            }
         }
         unsafe {
             gpu.device.destroy_command_pool(cp.pool);
@@ -3152,10 +3832,20 @@ pub extern "C" fn gfxDestroyCommandPool(
 
 #[inline]
 pub extern "C" fn gfxResetCommandPool(
-    _gpu: VkDevice,
+    gpu: VkDevice,
     mut commandPool: VkCommandPool,
-    _flags: VkCommandPoolResetFlags,
+    flags: VkCommandPoolResetFlags,
 ) -> VkResult {
+    commandPool.check_owner(gpu);
+    let release_resources = flags & VkCommandPoolResetFlagBits::VK_COMMAND_POOL_RESET_RELEASE_RESOURCES_BIT as u32 != 0;
+    for &cmd_buf in &commandPool.buffers {
+        let mut cmd_buf = cmd_buf;
+        if release_resources {
+            cmd_buf.fan.release(&gpu);
+        } else {
+            cmd_buf.fan.reset();
+        }
+    }
     unsafe {
         commandPool.pool.reset();
     }
@@ -3164,11 +3854,12 @@ pub extern "C" fn gfxResetCommandPool(
 
 #[inline]
 pub extern "C" fn gfxAllocateCommandBuffers(
-    _gpu: VkDevice,
+    gpu: VkDevice,
     pAllocateInfo: *const VkCommandBufferAllocateInfo,
     pCommandBuffers: *mut VkCommandBuffer,
 ) -> VkResult {
     let info = unsafe { &mut *(pAllocateInfo as *mut VkCommandBufferAllocateInfo) };
+    info.commandPool.check_owner(gpu);
     let level = match info.level {
         VkCommandBufferLevel::VK_COMMAND_BUFFER_LEVEL_PRIMARY => com::RawLevel::Primary,
         VkCommandBufferLevel::VK_COMMAND_BUFFER_LEVEL_SECONDARY => com::RawLevel::Secondary,
@@ -3178,30 +3869,43 @@ pub extern "C" fn gfxAllocateCommandBuffers(
     let output = unsafe {
         slice::from_raw_parts_mut(pCommandBuffers, info.commandBufferCount as usize)
     };
+    let CommandPool { ref mut pool, ref mut buffers, ref mut slab } = *info.commandPool;
     for out in output.iter_mut() {
-        let cmd_buf = info.commandPool.pool.allocate_one(level);
-        *out = DispatchHandle::new(cmd_buf);
+        let cmd_buf = CommandBuffer {
+            raw: pool.allocate_one(level),
+            device: gpu,
+            fan: fan::State::new(),
+        };
+        *out = DispatchHandle::new_in(cmd_buf, slab).owned_by(gpu);
     }
-    info.commandPool.buffers.extend_from_slice(output);
+    buffers.extend_from_slice(output);
 
     VkResult::VK_SUCCESS
 }
 
 #[inline]
 pub extern "C" fn gfxFreeCommandBuffers(
-    _gpu: VkDevice,
+    gpu: VkDevice,
     mut commandPool: VkCommandPool,
     commandBufferCount: u32,
     pCommandBuffers: *const VkCommandBuffer,
 ) {
+    commandPool.check_owner(gpu);
     let slice = unsafe {
         slice::from_raw_parts(pCommandBuffers, commandBufferCount as _)
     };
-    commandPool.buffers.retain(|buf| !slice.contains(buf));
+    let CommandPool { ref mut pool, ref mut buffers, ref mut slab } = *commandPool;
+    buffers.retain(|buf| !slice.contains(buf));
 
-    let buffers = slice.iter().filter_map(|buffer| buffer.unbox());
+    let buffers = slice
+        .iter()
+        .filter_map(|buffer| buffer.unbox_into(slab))
+        .map(|mut cmd_buf| {
+            cmd_buf.fan.release(&gpu);
+            cmd_buf.raw
+        });
     unsafe {
-        commandPool.pool.free(buffers);
+        pool.free(buffers);
     }
 }
 
@@ -3213,8 +3917,8 @@ pub extern "C" fn gfxBeginCommandBuffer(
     let info = unsafe { &*pBeginInfo };
     let inheritance = match unsafe { info.pInheritanceInfo.as_ref() } {
         Some(ii) => com::CommandBufferInheritanceInfo {
-            subpass: ii.renderPass.as_ref().map(|main_pass| pass::Subpass {
-                main_pass,
+            subpass: ii.renderPass.as_ref().map(|render_pass| pass::Subpass {
+                main_pass: &render_pass.raw,
                 index: ii.subpass as _,
             }),
             framebuffer: ii.framebuffer.as_ref(),
@@ -3224,15 +3928,16 @@ pub extern "C" fn gfxBeginCommandBuffer(
         },
         None => com::CommandBufferInheritanceInfo::default(),
     };
+    commandBuffer.fan.reset();
     unsafe {
-        commandBuffer.begin(conv::map_cmd_buffer_usage(info.flags), inheritance);
+        commandBuffer.raw.begin(conv::map_cmd_buffer_usage(info.flags), inheritance);
     }
     VkResult::VK_SUCCESS
 }
 #[inline]
 pub extern "C" fn gfxEndCommandBuffer(mut commandBuffer: VkCommandBuffer) -> VkResult {
     unsafe {
-        commandBuffer.finish();
+        commandBuffer.raw.finish();
     }
     VkResult::VK_SUCCESS
 }
@@ -3242,8 +3947,14 @@ pub extern "C" fn gfxResetCommandBuffer(
     flags: VkCommandBufferResetFlags,
 ) -> VkResult {
     let release_resources = flags & VkCommandBufferResetFlagBits::VK_COMMAND_BUFFER_RESET_RELEASE_RESOURCES_BIT as u32 != 0;
+    if release_resources {
+        let gpu = commandBuffer.device;
+        commandBuffer.fan.release(&gpu);
+    } else {
+        commandBuffer.fan.reset();
+    }
     unsafe {
-        commandBuffer.reset(release_resources);
+        commandBuffer.raw.reset(release_resources);
     }
     VkResult::VK_SUCCESS
 }
@@ -3253,12 +3964,16 @@ pub extern "C" fn gfxCmdBindPipeline(
     _pipelineBindPoint: VkPipelineBindPoint, // ignore, needs to match by spec
     pipeline: VkPipeline,
 ) {
+    pipeline.check_owner(commandBuffer.device);
     match *pipeline {
-        Pipeline::Graphics(ref pipeline) => unsafe {
-            commandBuffer.bind_graphics_pipeline(pipeline)
+        Pipeline::Graphics { triangle_fan, restart: strip, .. } => {
+            commandBuffer.fan.pipeline = triangle_fan;
+            commandBuffer.fan.restart = strip;
+            commandBuffer.fan.bound_pipeline = Some(pipeline);
+            restart::bind_pipeline(&mut *commandBuffer);
         }
-        Pipeline::Compute(ref pipeline) => unsafe {
-            commandBuffer.bind_compute_pipeline(pipeline)
+        Pipeline::Compute { ref raw, .. } => unsafe {
+            commandBuffer.raw.bind_compute_pipeline(raw)
         }
     }
 }
@@ -3273,7 +3988,7 @@ pub extern "C" fn gfxCmdSetViewport(
         let viewports = slice::from_raw_parts(pViewports, viewportCount as _)
             .into_iter()
             .map(conv::map_viewport);
-        commandBuffer.set_viewports(firstViewport, viewports);
+        commandBuffer.raw.set_viewports(firstViewport, viewports);
     }
 }
 #[inline]
@@ -3287,13 +4002,13 @@ pub extern "C" fn gfxCmdSetScissor(
         let scissors = slice::from_raw_parts(pScissors, scissorCount as _)
             .into_iter()
             .map(conv::map_rect);
-        commandBuffer.set_scissors(firstScissor, scissors);
+        commandBuffer.raw.set_scissors(firstScissor, scissors);
     }
 }
 #[inline]
 pub extern "C" fn gfxCmdSetLineWidth(mut commandBuffer: VkCommandBuffer, lineWidth: f32) {
     unsafe {
-        commandBuffer.set_line_width(lineWidth);
+        commandBuffer.raw.set_line_width(lineWidth);
     }
 }
 #[inline]
@@ -3304,7 +4019,7 @@ pub extern "C" fn gfxCmdSetDepthBias(
     depthBiasSlopeFactor: f32,
 ) {
     unsafe {
-        commandBuffer.set_depth_bias(pso::DepthBias {
+        commandBuffer.raw.set_depth_bias(pso::DepthBias {
             const_factor: depthBiasConstantFactor,
             clamp: depthBiasClamp,
             slope_factor: depthBiasSlopeFactor,
@@ -3318,7 +4033,7 @@ pub extern "C" fn gfxCmdSetBlendConstants(
 ) {
     unsafe {
         let value = *(blendConstants as *const pso::ColorValue);
-        commandBuffer.set_blend_constants(value);
+        commandBuffer.raw.set_blend_constants(value);
     }
 }
 #[inline]
@@ -3327,8 +4042,12 @@ pub extern "C" fn gfxCmdSetDepthBounds(
     minDepthBounds: f32,
     maxDepthBounds: f32,
 ) {
+    if !commandBuffer.device.features.contains(Features::DEPTH_BOUNDS) {
+        error!("Depth bounds are set without the depthBounds feature enabled");
+        return;
+    }
     unsafe {
-        commandBuffer.set_depth_bounds(minDepthBounds .. maxDepthBounds);
+        commandBuffer.raw.set_depth_bounds(minDepthBounds .. maxDepthBounds);
     }
 }
 #[inline]
@@ -3338,7 +4057,7 @@ pub extern "C" fn gfxCmdSetStencilCompareMask(
     compareMask: u32,
 ) {
     unsafe {
-        commandBuffer.set_stencil_read_mask(
+        commandBuffer.raw.set_stencil_read_mask(
             conv::map_stencil_face(faceMask),
             compareMask,
         );
@@ -3351,7 +4070,7 @@ pub extern "C" fn gfxCmdSetStencilWriteMask(
     writeMask: u32,
 ) {
     unsafe {
-        commandBuffer.set_stencil_write_mask(
+        commandBuffer.raw.set_stencil_write_mask(
             conv::map_stencil_face(faceMask),
             writeMask,
         );
@@ -3364,7 +4083,7 @@ pub extern "C" fn gfxCmdSetStencilReference(
     reference: u32,
 ) {
     unsafe {
-        commandBuffer.set_stencil_reference(
+        commandBuffer.raw.set_stencil_reference(
             conv::map_stencil_face(faceMask),
             reference,
         );
@@ -3381,6 +4100,7 @@ pub extern "C" fn gfxCmdBindDescriptorSets(
     dynamicOffsetCount: u32,
     pDynamicOffsets: *const u32,
 ) {
+    layout.check_owner(commandBuffer.device);
     let descriptor_sets = unsafe {
         slice::from_raw_parts(pDescriptorSets, descriptorSetCount as _)
             .into_iter()
@@ -3392,7 +4112,7 @@ pub extern "C" fn gfxCmdBindDescriptorSets(
 
     match pipelineBindPoint {
         VkPipelineBindPoint::VK_PIPELINE_BIND_POINT_GRAPHICS => unsafe {
-            commandBuffer.bind_graphics_descriptor_sets(
+            commandBuffer.raw.bind_graphics_descriptor_sets(
                 &*layout,
                 firstSet as _,
                 descriptor_sets,
@@ -3400,7 +4120,7 @@ pub extern "C" fn gfxCmdBindDescriptorSets(
             );
         }
         VkPipelineBindPoint::VK_PIPELINE_BIND_POINT_COMPUTE => unsafe {
-            commandBuffer.bind_compute_descriptor_sets(
+            commandBuffer.raw.bind_compute_descriptor_sets(
                 &*layout,
                 firstSet as _,
                 descriptor_sets,
@@ -3417,8 +4137,11 @@ pub extern "C" fn gfxCmdBindIndexBuffer(
     offset: VkDeviceSize,
     indexType: VkIndexType,
 ) {
+    buffer.check_owner(commandBuffer.device);
+    let previous = commandBuffer.fan.index_buffer.map(|(_, _, index_type)| index_type);
+    commandBuffer.fan.index_buffer = Some((buffer, offset, indexType));
     unsafe {
-        commandBuffer.bind_index_buffer(
+        commandBuffer.raw.bind_index_buffer(
             IndexBufferView {
                 buffer: &*buffer,
                 offset,
@@ -3426,6 +4149,9 @@ pub extern "C" fn gfxCmdBindIndexBuffer(
             }
         );
     }
+    if restart::TYPED && previous != Some(indexType) {
+        restart::rebind_pipeline(&mut *commandBuffer);
+    }
 }
 
 #[inline]
@@ -3451,7 +4177,7 @@ pub extern "C" fn gfxCmdBindVertexBuffers(
         });
 
     unsafe {
-        commandBuffer.bind_vertex_buffers(firstBinding, views);
+        commandBuffer.raw.bind_vertex_buffers(firstBinding, views);
     }
 }
 #[inline]
@@ -3462,11 +4188,12 @@ pub extern "C" fn gfxCmdDraw(
     firstVertex: u32,
     firstInstance: u32,
 ) {
+    let instances = firstInstance .. firstInstance + instanceCount;
+    if let Some(shape) = commandBuffer.fan.shape() {
+        return fan::draw(&mut *commandBuffer, shape, vertexCount, instances, firstVertex);
+    }
     unsafe {
-        commandBuffer.draw(
-            firstVertex .. firstVertex + vertexCount,
-            firstInstance .. firstInstance + instanceCount,
-        );
+        commandBuffer.raw.draw(firstVertex .. firstVertex + vertexCount, instances);
     }
 }
 #[inline]
@@ -3478,12 +4205,12 @@ pub extern "C" fn gfxCmdDrawIndexed(
     vertexOffset: i32,
     firstInstance: u32,
 ) {
+    let instances = firstInstance .. firstInstance + instanceCount;
+    if let Some(shape) = commandBuffer.fan.shape() {
+        return fan::draw_indexed(&mut *commandBuffer, shape, indexCount, instances, firstIndex, vertexOffset);
+    }
     unsafe {
-        commandBuffer.draw_indexed(
-            firstIndex .. firstIndex + indexCount,
-            vertexOffset,
-            firstInstance .. firstInstance + instanceCount,
-        );
+        commandBuffer.raw.draw_indexed(firstIndex .. firstIndex + indexCount, vertexOffset, instances);
     }
 }
 #[inline]
@@ -3494,8 +4221,12 @@ pub extern "C" fn gfxCmdDrawIndirect(
     drawCount: u32,
     stride: u32,
 ) {
+    buffer.check_owner(commandBuffer.device);
+    if let Some(shape) = commandBuffer.fan.shape() {
+        return fan::skip_indirect(shape);
+    }
     unsafe {
-        commandBuffer.draw_indirect(
+        commandBuffer.raw.draw_indirect(
             &*buffer,
             offset,
             drawCount,
@@ -3511,8 +4242,12 @@ pub extern "C" fn gfxCmdDrawIndexedIndirect(
     drawCount: u32,
     stride: u32,
 ) {
+    buffer.check_owner(commandBuffer.device);
+    if let Some(shape) = commandBuffer.fan.shape() {
+        return fan::skip_indirect(shape);
+    }
     unsafe {
-        commandBuffer.draw_indexed_indirect(
+        commandBuffer.raw.draw_indexed_indirect(
             &*buffer,
             offset,
             drawCount,
@@ -3528,7 +4263,7 @@ pub extern "C" fn gfxCmdDispatch(
     groupCountZ: u32,
 ) {
     unsafe {
-        commandBuffer.dispatch([groupCountX, groupCountY, groupCountZ]);
+        commandBuffer.raw.dispatch([groupCountX, groupCountY, groupCountZ]);
     }
 }
 #[inline]
@@ -3537,8 +4272,9 @@ pub extern "C" fn gfxCmdDispatchIndirect(
     buffer: VkBuffer,
     offset: VkDeviceSize,
 ) {
+    buffer.check_owner(commandBuffer.device);
     unsafe {
-        commandBuffer.dispatch_indirect(
+        commandBuffer.raw.dispatch_indirect(
             &*buffer,
             offset,
         );
@@ -3552,6 +4288,8 @@ pub extern "C" fn gfxCmdCopyBuffer(
     regionCount: u32,
     pRegions: *const VkBufferCopy,
 ) {
+    srcBuffer.check_owner(commandBuffer.device);
+    dstBuffer.check_owner(commandBuffer.device);
     let regions = unsafe {
             slice::from_raw_parts(pRegions, regionCount as _)
         }
@@ -3563,7 +4301,7 @@ pub extern "C" fn gfxCmdCopyBuffer(
         });
 
     unsafe {
-        commandBuffer.copy_buffer(
+        commandBuffer.raw.copy_buffer(
             &*srcBuffer,
             &*dstBuffer,
             regions,
@@ -3580,6 +4318,8 @@ pub extern "C" fn gfxCmdCopyImage(
     regionCount: u32,
     pRegions: *const VkImageCopy,
 ) {
+    srcImage.check_owner(commandBuffer.device);
+    dstImage.check_owner(commandBuffer.device);
     let regions = unsafe {
             slice::from_raw_parts(pRegions, regionCount as _)
         }
@@ -3593,7 +4333,7 @@ pub extern "C" fn gfxCmdCopyImage(
         });
 
     unsafe {
-        commandBuffer.copy_image(
+        commandBuffer.raw.copy_image(
             &srcImage.raw,
             conv::map_image_layout(srcImageLayout),
             &dstImage.raw,
@@ -3613,6 +4353,8 @@ pub extern "C" fn gfxCmdBlitImage(
     pRegions: *const VkImageBlit,
     filter: VkFilter,
 ) {
+    srcImage.check_owner(commandBuffer.device);
+    dstImage.check_owner(commandBuffer.device);
     let regions = unsafe {
             slice::from_raw_parts(pRegions, regionCount as _)
         }
@@ -3625,7 +4367,7 @@ pub extern "C" fn gfxCmdBlitImage(
         });
 
     unsafe {
-        commandBuffer.blit_image(
+        commandBuffer.raw.blit_image(
             &srcImage.raw,
             conv::map_image_layout(srcImageLayout),
             &dstImage.raw,
@@ -3644,6 +4386,8 @@ pub extern "C" fn gfxCmdCopyBufferToImage(
     regionCount: u32,
     pRegions: *const VkBufferImageCopy,
 ) {
+    srcBuffer.check_owner(commandBuffer.device);
+    dstImage.check_owner(commandBuffer.device);
     let regions = unsafe {
             slice::from_raw_parts(pRegions, regionCount as _)
         }
@@ -3658,7 +4402,7 @@ pub extern "C" fn gfxCmdCopyBufferToImage(
         });
 
     unsafe {
-        commandBuffer.copy_buffer_to_image(
+        commandBuffer.raw.copy_buffer_to_image(
             &*srcBuffer,
             &dstImage.raw,
             conv::map_image_layout(dstImageLayout),
@@ -3675,6 +4419,8 @@ pub extern "C" fn gfxCmdCopyImageToBuffer(
     regionCount: u32,
     pRegions: *const VkBufferImageCopy,
 ) {
+    srcImage.check_owner(commandBuffer.device);
+    dstBuffer.check_owner(commandBuffer.device);
     let regions = unsafe {
             slice::from_raw_parts(pRegions, regionCount as _)
         }
@@ -3689,7 +4435,7 @@ pub extern "C" fn gfxCmdCopyImageToBuffer(
         });
 
     unsafe {
-        commandBuffer.copy_image_to_buffer(
+        commandBuffer.raw.copy_image_to_buffer(
             &srcImage.raw,
             conv::map_image_layout(srcImageLayout),
             &*dstBuffer,
@@ -3705,8 +4451,9 @@ pub extern "C" fn gfxCmdUpdateBuffer(
     dataSize: VkDeviceSize,
     pData: *const ::std::os::raw::c_void,
 ) {
+    dstBuffer.check_owner(commandBuffer.device);
     unsafe {
-        commandBuffer.update_buffer(
+        commandBuffer.raw.update_buffer(
             &*dstBuffer,
             dstOffset,
             slice::from_raw_parts(pData as _, dataSize as _),
@@ -3721,13 +4468,14 @@ pub extern "C" fn gfxCmdFillBuffer(
     size: VkDeviceSize,
     data: u32,
 ) {
+    dstBuffer.check_owner(commandBuffer.device);
     let range = if size == VK_WHOLE_SIZE as VkDeviceSize {
         (Some(dstOffset), None)
     } else {
         (Some(dstOffset), Some(dstOffset + size))
     };
     unsafe {
-        commandBuffer.fill_buffer(
+        commandBuffer.raw.fill_buffer(
             &*dstBuffer,
             range,
             data,
@@ -3743,6 +4491,7 @@ pub extern "C" fn gfxCmdClearColorImage(
     rangeCount: u32,
     pRanges: *const VkImageSubresourceRange,
 ) {
+    image.check_owner(commandBuffer.device);
     let subresource_ranges = unsafe {
             slice::from_raw_parts(pRanges, rangeCount as _)
         }
@@ -3750,7 +4499,7 @@ pub extern "C" fn gfxCmdClearColorImage(
         .map(|&range| image.map_subresource_range(range));
 
     unsafe {
-        commandBuffer.clear_image(
+        commandBuffer.raw.clear_image(
             &image.raw,
             conv::map_image_layout(imageLayout),
             mem::transmute(*pColor),
@@ -3768,6 +4517,7 @@ pub extern "C" fn gfxCmdClearDepthStencilImage(
     rangeCount: u32,
     pRanges: *const VkImageSubresourceRange,
 ) {
+    image.check_owner(commandBuffer.device);
     let subresource_ranges = unsafe {
             slice::from_raw_parts(pRanges, rangeCount as _)
         }
@@ -3775,7 +4525,7 @@ pub extern "C" fn gfxCmdClearDepthStencilImage(
         .map(|&range| image.map_subresource_range(range));
 
     unsafe {
-        commandBuffer.clear_image(
+        commandBuffer.raw.clear_image(
             &image.raw,
             conv::map_image_layout(imageLayout),
             mem::zeroed(),
@@ -3822,7 +4572,7 @@ pub extern "C" fn gfxCmdClearAttachments(
         .map(conv::map_clear_rect);
 
     unsafe {
-        commandBuffer.clear_attachments(
+        commandBuffer.raw.clear_attachments(
             attachments,
             rects
         );
@@ -3838,6 +4588,8 @@ pub extern "C" fn gfxCmdResolveImage(
     regionCount: u32,
     pRegions: *const VkImageResolve,
 ) {
+    srcImage.check_owner(commandBuffer.device);
+    dstImage.check_owner(commandBuffer.device);
     let regions = unsafe {
             slice::from_raw_parts(pRegions, regionCount as _)
         }
@@ -3852,7 +4604,7 @@ pub extern "C" fn gfxCmdResolveImage(
         });
 
     unsafe {
-        commandBuffer.resolve_image(
+        commandBuffer.raw.resolve_image(
             &srcImage.raw,
             conv::map_image_layout(srcImageLayout),
             &dstImage.raw,
@@ -3934,7 +4686,7 @@ pub extern "C" fn gfxCmdPipelineBarrier(
             states: conv::map_buffer_access(b.srcAccessMask) .. conv::map_buffer_access(b.dstAccessMask),
             target: &*b.buffer,
             range: Some(b.offset) .. if b.size as i32 == VK_WHOLE_SIZE { None } else { Some(b.offset + b.size) },
-            families: None,
+            families: conv::map_queue_family_transfer(b.srcQueueFamilyIndex, b.dstQueueFamilyIndex),
         });
 
     let image_barriers = unsafe {
@@ -3947,11 +4699,11 @@ pub extern "C" fn gfxCmdPipelineBarrier(
                 (conv::map_image_access(b.dstAccessMask), conv::map_image_layout(b.newLayout)),
             target: &b.image.raw,
             range: b.image.map_subresource_range(b.subresourceRange),
-            families: None,
+            families: conv::map_queue_family_transfer(b.srcQueueFamilyIndex, b.dstQueueFamilyIndex),
         });
 
     unsafe {
-        commandBuffer.pipeline_barrier(
+        commandBuffer.raw.pipeline_barrier(
             conv::map_pipeline_stage_flags(srcStageMask) .. conv::map_pipeline_stage_flags(dstStageMask),
             memory::Dependencies::from_bits(dependencyFlags as _).unwrap_or(memory::Dependencies::empty()),
             global_barriers.chain(buffer_barriers).chain(image_barriers),
@@ -3965,12 +4717,13 @@ pub extern "C" fn gfxCmdBeginQuery(
     query: u32,
     flags: VkQueryControlFlags,
 ) {
+    queryPool.check_owner(commandBuffer.device);
     let query = hal::query::Query {
         pool: &*queryPool,
         id: query,
     };
     unsafe {
-        commandBuffer.begin_query(query, conv::map_query_control(flags));
+        commandBuffer.raw.begin_query(query, conv::map_query_control(flags));
     }
 }
 #[inline]
@@ -3979,12 +4732,13 @@ pub extern "C" fn gfxCmdEndQuery(
     queryPool: VkQueryPool,
     query: u32,
 ) {
+    queryPool.check_owner(commandBuffer.device);
     let query = hal::query::Query {
         pool: &*queryPool,
         id: query,
     };
     unsafe {
-        commandBuffer.end_query(query);
+        commandBuffer.raw.end_query(query);
     }
 }
 #[inline]
@@ -3994,8 +4748,9 @@ pub extern "C" fn gfxCmdResetQueryPool(
     firstQuery: u32,
     queryCount: u32,
 ) {
+    queryPool.check_owner(commandBuffer.device);
     unsafe {
-        commandBuffer.reset_query_pool(&*queryPool, firstQuery .. firstQuery + queryCount);
+        commandBuffer.raw.reset_query_pool(&*queryPool, firstQuery .. firstQuery + queryCount);
     }
 }
 #[inline]
@@ -4005,12 +4760,13 @@ pub extern "C" fn gfxCmdWriteTimestamp(
     queryPool: VkQueryPool,
     query: u32,
 ) {
+    queryPool.check_owner(commandBuffer.device);
     let query = hal::query::Query {
         pool: &*queryPool,
         id: query,
     };
     unsafe {
-        commandBuffer.write_timestamp(conv::map_pipeline_stage_flags(pipelineStage as u32), query);
+        commandBuffer.raw.write_timestamp(conv::map_pipeline_stage_flags(pipelineStage as u32), query);
     }
 }
 #[inline]
@@ -4024,8 +4780,10 @@ pub extern "C" fn gfxCmdCopyQueryPoolResults(
     stride: VkDeviceSize,
     flags: VkQueryResultFlags,
 ) {
+    queryPool.check_owner(commandBuffer.device);
+    dstBuffer.check_owner(commandBuffer.device);
     unsafe {
-        commandBuffer.copy_query_pool_results(
+        commandBuffer.raw.copy_query_pool_results(
             &*queryPool,
             firstQuery .. firstQuery + queryCount,
             &*dstBuffer,
@@ -4044,19 +4802,20 @@ pub extern "C" fn gfxCmdPushConstants(
     size: u32,
     pValues: *const ::std::os::raw::c_void,
 ) {
+    layout.check_owner(commandBuffer.device);
     assert_eq!(size % 4, 0);
     unsafe {
         let values = slice::from_raw_parts(pValues as *const u32, size as usize / 4);
 
         if stageFlags & VkShaderStageFlagBits::VK_SHADER_STAGE_COMPUTE_BIT as u32 != 0 {
-            commandBuffer.push_compute_constants(
+            commandBuffer.raw.push_compute_constants(
                 &*layout,
                 offset,
                 values,
             );
         }
         if stageFlags & VkShaderStageFlagBits::VK_SHADER_STAGE_ALL_GRAPHICS as u32 != 0 {
-            commandBuffer.push_graphics_constants(
+            commandBuffer.raw.push_graphics_constants(
                 &*layout,
                 conv::map_stage_flags(stageFlags),
                 offset,
@@ -4072,6 +4831,8 @@ pub extern "C" fn gfxCmdBeginRenderPass(
     contents: VkSubpassContents,
 ) {
     let info = unsafe { &*pRenderPassBegin };
+    info.renderPass.check_owner(commandBuffer.device);
+    info.framebuffer.check_owner(commandBuffer.device);
 
     let render_area = pso::Rect {
         x: info.renderArea.offset.x as _,
@@ -4090,8 +4851,8 @@ pub extern "C" fn gfxCmdBeginRenderPass(
     let contents = conv::map_subpass_contents(contents);
 
     unsafe {
-        commandBuffer.begin_render_pass(
-            &*info.renderPass,
+        commandBuffer.raw.begin_render_pass(
+            &info.renderPass.raw,
             &*info.framebuffer,
             render_area,
             clear_values,
@@ -4105,7 +4866,7 @@ pub extern "C" fn gfxCmdNextSubpass(
     contents: VkSubpassContents,
 ) {
     unsafe {
-        commandBuffer.next_subpass(conv::map_subpass_contents(contents));
+        commandBuffer.raw.next_subpass(conv::map_subpass_contents(contents));
     }
 }
 #[inline]
@@ -4113,7 +4874,7 @@ pub extern "C" fn gfxCmdEndRenderPass(
     mut commandBuffer: VkCommandBuffer,
 ) {
     unsafe {
-        commandBuffer.end_render_pass();
+        commandBuffer.raw.end_render_pass();
     }
 }
 #[inline]
@@ -4122,30 +4883,38 @@ pub extern "C" fn gfxCmdExecuteCommands(
     commandBufferCount: u32,
     pCommandBuffers: *const VkCommandBuffer,
 ) {
+    let secondaries = unsafe {
+        slice::from_raw_parts(pCommandBuffers, commandBufferCount as _)
+    };
     unsafe {
-        commandBuffer.execute_commands(
-            slice::from_raw_parts(pCommandBuffers, commandBufferCount as _),
-        );
+        commandBuffer.raw.execute_commands(secondaries.iter().map(|cmd_buf| &cmd_buf.raw));
     }
+    commandBuffer.fan.secondaries.extend_from_slice(secondaries);
 }
 
 #[inline]
 pub extern "C" fn gfxDestroySurfaceKHR(
-    _instance: VkInstance,
+    instance: VkInstance,
     surface: VkSurfaceKHR,
     _: *const VkAllocationCallbacks,
 ) {
-    let _ = surface.unbox(); //TODO
+    let _ = surface.check_owner(instance).unbox(); //TODO
 }
 
 #[inline]
-pub extern fn gfxGetPhysicalDeviceSurfaceSupportKHR(
+pub extern "C" fn gfxGetPhysicalDeviceSurfaceSupportKHR(
     adapter: VkPhysicalDevice,
     queueFamilyIndex: u32,
     surface: VkSurfaceKHR,
     pSupported: *mut VkBool32,
 ) -> VkResult {
-    let family = &adapter.queue_families[queueFamilyIndex as usize];
+    let family = match adapter.raw.queue_families.get(queueFamilyIndex as usize) {
+        Some(family) => family,
+        None => {
+            error!("Queue family index {} is out of range", queueFamilyIndex);
+            return VkResult::VK_ERROR_INITIALIZATION_FAILED;
+        }
+    };
     let supports = surface.supports_queue_family(family);
     unsafe { *pSupported = supports as _ };
     VkResult::VK_SUCCESS
@@ -4158,7 +4927,7 @@ pub extern "C" fn gfxGetPhysicalDeviceSurfaceCapabilitiesKHR(
     pSurfaceCapabilities: *mut VkSurfaceCapabilitiesKHR,
 ) -> VkResult {
     let (caps, _, _supported_transforms) =
-        surface.compatibility(&adapter.physical_device);
+        surface.compatibility(&adapter.raw.physical_device);
 
     let output = VkSurfaceCapabilitiesKHR {
         minImageCount: caps.image_count.start,
@@ -4192,7 +4961,7 @@ pub extern "C" fn gfxGetPhysicalDeviceSurfaceFormatsKHR(
     pSurfaceFormats: *mut VkSurfaceFormatKHR,
 ) -> VkResult {
     let formats = surface
-        .compatibility(&adapter.physical_device)
+        .compatibility(&adapter.raw.physical_device)
         .1
         .map(|formats| formats.into_iter().map(conv::format_from_hal).collect())
         .unwrap_or(vec![VkFormat::VK_FORMAT_UNDEFINED]);
@@ -4225,7 +4994,7 @@ pub extern "C" fn gfxGetPhysicalDeviceSurfacePresentModesKHR(
     pPresentModes: *mut VkPresentModeKHR,
 ) -> VkResult {
     let (_, _, present_modes) = surface
-        .compatibility(&adapter.physical_device);
+        .compatibility(&adapter.raw.physical_device);
 
     let num_present_modes = present_modes.len();
 
@@ -4259,17 +5028,24 @@ pub extern "C" fn gfxCreateSwapchainKHR(
     pSwapchain: *mut VkSwapchainKHR,
 ) -> VkResult {
     let info = unsafe { &*pCreateInfo };
+    // the old swapchain may still be used by a pending presentation
+    gpu.pending.flush();
     // TODO: more checks
     assert_eq!(info.clipped, VK_TRUE); // TODO
-    assert_eq!(
-        info.imageSharingMode,
-        VkSharingMode::VK_SHARING_MODE_EXCLUSIVE
-    ); // TODO
+    check_sharing_mode(&gpu, info.imageSharingMode, info.queueFamilyIndexCount, info.pQueueFamilyIndices);
+
+    let format = match conv::map_format(info.imageFormat) {
+        Some(format) => format,
+        None => {
+            error!("Swapchain format {:?} is not supported", info.imageFormat);
+            return VkResult::VK_ERROR_INITIALIZATION_FAILED;
+        }
+    };
 
     let config = hal::SwapchainConfig {
         present_mode: conv::map_present_mode(info.presentMode),
         composite_alpha: conv::map_composite_alpha(info.compositeAlpha),
-        format: conv::map_format(info.imageFormat).unwrap(),
+        format,
         extent: conv::map_extent2d(info.imageExtent),
         image_count: info.minImageCount,
         image_layers: 1,
@@ -4284,7 +5060,10 @@ pub extern "C" fn gfxCreateSwapchainKHR(
     } {
         Ok(pair) => pair,
         Err(hal::window::CreationError::OutOfMemory(oom)) => return map_oom(oom),
-        Err(hal::window::CreationError::DeviceLost(hal::device::DeviceLost)) => return VkResult::VK_ERROR_DEVICE_LOST,
+        Err(hal::window::CreationError::DeviceLost(hal::device::DeviceLost)) => {
+            gpu.lost.mark("swapchain creation");
+            return VkResult::VK_ERROR_DEVICE_LOST;
+        }
         Err(hal::window::CreationError::SurfaceLost(hal::device::SurfaceLost)) => return VkResult::VK_ERROR_SURFACE_LOST_KHR,
         Err(hal::window::CreationError::WindowInUse(hal::device::WindowInUse)) => return VkResult::VK_ERROR_NATIVE_WINDOW_IN_USE_KHR,
     };
@@ -4293,13 +5072,11 @@ pub extern "C" fn gfxCreateSwapchainKHR(
     {
         use back::AcquireMode;
 
-        if let Ok(value) = env::var("GFX_METAL_ACQUIRING") {
-            swapchain.acquire_mode = match value.to_lowercase().as_str() {
-                "wait" => AcquireMode::Wait,
-                "oldest" => AcquireMode::Oldest,
-                other => panic!("unknown acquiring option: {}", other),
+        if let Some(acquiring) = gpu.settings.acquiring {
+            swapchain.acquire_mode = match acquiring {
+                config::Acquiring::Wait => AcquireMode::Wait,
+                config::Acquiring::Oldest => AcquireMode::Oldest,
             };
-            println!("GFX: acquiring override {:?}", swapchain.acquire_mode);
         }
     }
 
@@ -4309,35 +5086,38 @@ pub extern "C" fn gfxCreateSwapchainKHR(
             raw,
             mip_levels: 1,
             array_layers: 1,
-        }))
+        }).owned_by(gpu))
         .collect();
 
     let swapchain = Swapchain {
         raw: Some(swapchain),
         images,
+        present_error: Mutex::new(None),
     };
 
-    unsafe { *pSwapchain = Handle::new(swapchain) };
+    unsafe { *pSwapchain = Handle::new(swapchain).owned_by(gpu) };
     VkResult::VK_SUCCESS
 }
 #[inline]
 pub extern "C" fn gfxDestroySwapchainKHR(
-    _gpu: VkDevice,
+    gpu: VkDevice,
     mut swapchain: VkSwapchainKHR,
     _pAllocator: *const VkAllocationCallbacks,
 ) {
+    gpu.pending.flush();
     for image in &mut swapchain.images {
         let _ = image.unbox();
     }
-    let _ = swapchain.unbox();
+    let _ = swapchain.check_owner(gpu).unbox();
 }
 #[inline]
 pub extern "C" fn gfxGetSwapchainImagesKHR(
-    _gpu: VkDevice,
+    gpu: VkDevice,
     swapchain: VkSwapchainKHR,
     pSwapchainImageCount: *mut u32,
     pSwapchainImages: *mut VkImage,
 ) -> VkResult {
+    swapchain.check_owner(gpu);
     debug_assert!(!pSwapchainImageCount.is_null());
 
     let swapchain_image_count = unsafe { &mut *pSwapchainImageCount };
@@ -4363,151 +5143,6 @@ pub extern "C" fn gfxGetSwapchainImagesKHR(
     VkResult::VK_SUCCESS
 }
 
-#[inline]
-pub extern "C" fn gfxCmdProcessCommandsNVX(
-    _commandBuffer: VkCommandBuffer,
-    _pProcessCommandsInfo: *const VkCmdProcessCommandsInfoNVX,
-) {
-    unimplemented!()
-}
-#[inline]
-pub extern "C" fn gfxCmdReserveSpaceForCommandsNVX(
-    _commandBuffer: VkCommandBuffer,
-    _pReserveSpaceInfo: *const VkCmdReserveSpaceForCommandsInfoNVX,
-) {
-    unimplemented!()
-}
-#[inline]
-pub extern "C" fn gfxCreateIndirectCommandsLayoutNVX(
-    _gpu: VkDevice,
-    _pCreateInfo: *const VkIndirectCommandsLayoutCreateInfoNVX,
-    _pAllocator: *const VkAllocationCallbacks,
-    _pIndirectCommandsLayout: *mut VkIndirectCommandsLayoutNVX,
-) -> VkResult {
-    unimplemented!()
-}
-#[inline]
-pub extern "C" fn gfxDestroyIndirectCommandsLayoutNVX(
-    _gpu: VkDevice,
-    _indirectCommandsLayout: VkIndirectCommandsLayoutNVX,
-    _pAllocator: *const VkAllocationCallbacks,
-) {
-    unimplemented!()
-}
-#[inline]
-pub extern "C" fn gfxCreateObjectTableNVX(
-    _gpu: VkDevice,
-    _pCreateInfo: *const VkObjectTableCreateInfoNVX,
-    _pAllocator: *const VkAllocationCallbacks,
-    _pObjectTable: *mut VkObjectTableNVX,
-) -> VkResult {
-    unimplemented!()
-}
-#[inline]
-pub extern "C" fn gfxDestroyObjectTableNVX(
-    _gpu: VkDevice,
-    _objectTable: VkObjectTableNVX,
-    _pAllocator: *const VkAllocationCallbacks,
-) {
-    unimplemented!()
-}
-#[inline]
-pub extern "C" fn gfxRegisterObjectsNVX(
-    _gpu: VkDevice,
-    _objectTable: VkObjectTableNVX,
-    _objectCount: u32,
-    _ppObjectTableEntries: *const *const VkObjectTableEntryNVX,
-    _pObjectIndices: *const u32,
-) -> VkResult {
-    unimplemented!()
-}
-#[inline]
-pub extern "C" fn gfxUnregisterObjectsNVX(
-    _gpu: VkDevice,
-    _objectTable: VkObjectTableNVX,
-    _objectCount: u32,
-    _pObjectEntryTypes: *const VkObjectEntryTypeNVX,
-    _pObjectIndices: *const u32,
-) -> VkResult {
-    unimplemented!()
-}
-#[inline]
-pub extern "C" fn gfxGetPhysicalDeviceGeneratedCommandsPropertiesNVX(
-    _physicalDevice: VkPhysicalDevice,
-    _pFeatures: *mut VkDeviceGeneratedCommandsFeaturesNVX,
-    _pLimits: *mut VkDeviceGeneratedCommandsLimitsNVX,
-) {
-    unimplemented!()
-}
-#[inline]
-pub extern "C" fn gfxCmdSetViewportWScalingNV(
-    _commandBuffer: VkCommandBuffer,
-    _firstViewport: u32,
-    _viewportCount: u32,
-    _pViewportWScalings: *const VkViewportWScalingNV,
-) {
-    unimplemented!()
-}
-#[inline]
-pub extern "C" fn gfxReleaseDisplayEXT(
-    _physicalDevice: VkPhysicalDevice,
-    _display: VkDisplayKHR,
-) -> VkResult {
-    unimplemented!()
-}
-#[inline]
-pub extern "C" fn gfxGetPhysicalDeviceSurfaceCapabilities2EXT(
-    _physicalDevice: VkPhysicalDevice,
-    _surface: VkSurfaceKHR,
-    _pSurfaceCapabilities: *mut VkSurfaceCapabilities2EXT,
-) -> VkResult {
-    unimplemented!()
-}
-#[inline]
-pub extern "C" fn gfxDisplayPowerControlEXT(
-    _gpu: VkDevice,
-    _display: VkDisplayKHR,
-    _pDisplayPowerInfo: *const VkDisplayPowerInfoEXT,
-) -> VkResult {
-    unimplemented!()
-}
-#[inline]
-pub extern "C" fn gfxRegisterDeviceEventEXT(
-    _gpu: VkDevice,
-    _pDeviceEventInfo: *const VkDeviceEventInfoEXT,
-    _pAllocator: *const VkAllocationCallbacks,
-    _pFence: *mut VkFence,
-) -> VkResult {
-    unimplemented!()
-}
-#[inline]
-pub extern "C" fn gfxRegisterDisplayEventEXT(
-    _gpu: VkDevice,
-    _display: VkDisplayKHR,
-    _pDisplayEventInfo: *const VkDisplayEventInfoEXT,
-    _pAllocator: *const VkAllocationCallbacks,
-    _pFence: *mut VkFence,
-) -> VkResult {
-    unimplemented!()
-}
-#[inline]
-pub extern "C" fn gfxGetSwapchainCounterEXT(
-    _gpu: VkDevice,
-    _swapchain: VkSwapchainKHR,
-    _counter: VkSurfaceCounterFlagBitsEXT,
-    _pCounterValue: *mut u64,
-) -> VkResult {
-    unimplemented!()
-}
-#[inline]
-pub extern "C" fn gfxCmdSetDiscardRectangleEXT(
-    _commandBuffer: VkCommandBuffer,
-    _firstDiscardRectangle: u32,
-    _discardRectangleCount: u32,
-    _pDiscardRectangles: *const VkRect2D,
-) {
-    unimplemented!()
-}
 #[inline]
 pub extern "C" fn gfxCreateWin32SurfaceKHR(
     instance: VkInstance,
@@ -4523,7 +5158,7 @@ pub extern "C" fn gfxCreateWin32SurfaceKHR(
             assert_eq!(info.flags, 0);
             *pSurface = Handle::new(
                 instance.backend.create_surface_from_hwnd(info.hinstance, info.hwnd),
-            );
+            ).owned_by(instance);
             VkResult::VK_SUCCESS
         }
     }
@@ -4531,7 +5166,8 @@ pub extern "C" fn gfxCreateWin32SurfaceKHR(
     {
         unsafe {
             assert_eq!(info.flags, 0);
-            *pSurface = Handle::new(instance.backend.create_surface_from_hwnd(info.hwnd));
+            *pSurface = Handle::new(instance.backend.create_surface_from_hwnd(info.hwnd))
+                .owned_by(instance);
             VkResult::VK_SUCCESS
         }
     }
@@ -4539,7 +5175,8 @@ pub extern "C" fn gfxCreateWin32SurfaceKHR(
     {
         unsafe {
             assert_eq!(info.flags, 0);
-            *pSurface = Handle::new(instance.backend.create_surface_from_hwnd(info.hwnd));
+            *pSurface = Handle::new(instance.backend.create_surface_from_hwnd(info.hwnd))
+                .owned_by(instance);
             VkResult::VK_SUCCESS
         }
     }
@@ -4563,7 +5200,7 @@ pub extern "C" fn gfxCreateXcbSurfaceKHR(
             assert_eq!(info.flags, 0);
             *pSurface = Handle::new(
                 instance.backend.create_surface_from_xcb(info.connection as _, info.window),
-            );
+            ).owned_by(instance);
             VkResult::VK_SUCCESS
         }
     }
@@ -4575,13 +5212,25 @@ pub extern "C" fn gfxCreateXcbSurfaceKHR(
 }
 #[inline]
 pub extern "C" fn gfxAcquireNextImageKHR(
-    _device: VkDevice,
+    gpu: VkDevice,
     mut swapchain: VkSwapchainKHR,
     timeout: u64,
     semaphore: VkSemaphore,
     fence: VkFence,
     pImageIndex: *mut u32,
 ) -> VkResult {
+    swapchain.check_owner(gpu);
+    semaphore.check_owner(gpu);
+    fence.check_owner(gpu);
+    // images are only released back to the swapchain by the presentation
+    gpu.pending.flush();
+    if gpu.lost.is_lost() {
+        return VkResult::VK_ERROR_DEVICE_LOST;
+    }
+    if let Some(error) = swapchain.present_error() {
+        return error;
+    }
+
     let raw = match swapchain.raw {
         Some(ref mut raw) => raw,
         None => return VkResult::VK_ERROR_OUT_OF_DATE_KHR,
@@ -4599,7 +5248,10 @@ pub extern "C" fn gfxAcquireNextImageKHR(
         Err(hal::AcquireError::NotReady) => VkResult::VK_NOT_READY,
         Err(hal::AcquireError::OutOfDate) => VkResult::VK_ERROR_OUT_OF_DATE_KHR,
         Err(hal::AcquireError::SurfaceLost(_)) => VkResult::VK_ERROR_SURFACE_LOST_KHR,
-        Err(hal::AcquireError::DeviceLost(_)) => VkResult::VK_ERROR_DEVICE_LOST,
+        Err(hal::AcquireError::DeviceLost(_)) => {
+            gpu.lost.mark("image acquisition");
+            VkResult::VK_ERROR_DEVICE_LOST
+        }
         Err(hal::AcquireError::OutOfMemory(OutOfDeviceMemory)) => VkResult::VK_ERROR_OUT_OF_DEVICE_MEMORY,
         Err(hal::AcquireError::OutOfMemory(OutOfHostMemory)) => VkResult::VK_ERROR_OUT_OF_HOST_MEMORY,
         Err(hal::AcquireError::Timeout) => VkResult::VK_TIMEOUT,
@@ -4610,6 +5262,10 @@ pub extern "C" fn gfxQueuePresentKHR(
     mut queue: VkQueue,
     pPresentInfo: *const VkPresentInfoKHR,
 ) -> VkResult {
+    if queue.lost.is_lost() {
+        return VkResult::VK_ERROR_DEVICE_LOST;
+    }
+
     let info = unsafe { &*pPresentInfo };
 
     let swapchain_slice = unsafe {
@@ -4618,23 +5274,49 @@ pub extern "C" fn gfxQueuePresentKHR(
     let index_slice = unsafe {
         slice::from_raw_parts(info.pImageIndices, info.swapchainCount as _)
     };
-    let swapchains = swapchain_slice
-        .into_iter()
-        .zip(index_slice)
-        .map(|(swapchain, index)| (swapchain.raw.as_ref().unwrap(), *index));
-
-    let wait_semaphores = unsafe {
+    let wait_slice = unsafe {
         slice::from_raw_parts(info.pWaitSemaphores, info.waitSemaphoreCount as _)
-            .into_iter()
-            .map(|semaphore| &**semaphore)
     };
 
-    match unsafe {
-        queue.present(swapchains, wait_semaphores)
-    } {
-        Ok(_) => VkResult::VK_SUCCESS,
-        Err(_) => VkResult::VK_ERROR_SURFACE_LOST_KHR,
+    let result = match queue.worker {
+        // presented in order with the submissions, failures of the earlier
+        // presentations are reported instead
+        Some(ref worker) => match swapchain_slice.iter().filter_map(|swapchain| swapchain.present_error()).next() {
+            Some(error) => error,
+            None => {
+                worker.enqueue(submit::Job::Present {
+                    swapchains: swapchain_slice
+                        .iter()
+                        .cloned()
+                        .zip(index_slice.iter().cloned())
+                        .collect(),
+                    wait_semaphores: wait_slice.to_vec(),
+                });
+                VkResult::VK_SUCCESS
+            }
+        },
+        None => {
+            let swapchains = swapchain_slice
+                .into_iter()
+                .zip(index_slice)
+                .map(|(swapchain, index)| (swapchain.raw.as_ref().unwrap(), *index));
+            let wait_semaphores = wait_slice
+                .into_iter()
+                .map(|semaphore| &**semaphore);
+
+            match unsafe {
+                queue.raw.present(swapchains, wait_semaphores)
+            } {
+                Ok(_) => VkResult::VK_SUCCESS,
+                Err(err) => conv::map_present_error(err),
+            }
+        }
+    };
+
+    if result == VkResult::VK_ERROR_DEVICE_LOST {
+        queue.lost.mark("presentation");
     }
+    result
 }
 
 #[inline]
@@ -4648,14 +5330,11 @@ pub extern "C" fn gfxCreateMetalSurfaceEXT(
     let info = unsafe { &*pCreateInfo };
     #[cfg(feature = "gfx-backend-metal")]
     unsafe {
-        let enable_signposts = env::var("GFX_METAL_SIGNPOSTS").is_ok();
-        if enable_signposts {
-            println!("GFX: enabled signposts");
-        }
+        let enable_signposts = instance.settings.signposts.unwrap_or(false);
         assert_eq!(info.flags, 0);
         *pSurface = Handle::new(
             instance.backend.create_surface_from_layer(info.pLayer as *mut _, enable_signposts),
-        );
+        ).owned_by(instance);
         VkResult::VK_SUCCESS
     }
     #[cfg(not(feature = "gfx-backend-metal"))]
@@ -4676,14 +5355,11 @@ pub extern "C" fn gfxCreateMacOSSurfaceMVK(
     let info = unsafe { &*pCreateInfo };
     #[cfg(target_os="macos")]
     unsafe {
-        let enable_signposts = env::var("GFX_METAL_SIGNPOSTS").is_ok();
-        if enable_signposts {
-            println!("GFX: enabled signposts");
-        }
+        let enable_signposts = instance.settings.signposts.unwrap_or(false);
         assert_eq!(info.flags, 0);
         *pSurface = Handle::new(
             instance.backend.create_surface_from_nsview(info.pView, enable_signposts),
-        );
+        ).owned_by(instance);
         VkResult::VK_SUCCESS
     }
     #[cfg(not(target_os = "macos"))]
@@ -4692,3 +5368,66 @@ pub extern "C" fn gfxCreateMacOSSurfaceMVK(
         unreachable!()
     }
 }
+
+#[inline]
+pub extern "C" fn gfxCreateDebugReportCallbackEXT(
+    instance: VkInstance,
+    pCreateInfo: *const VkDebugReportCallbackCreateInfoEXT,
+    _pAllocator: *const VkAllocationCallbacks,
+    pCallback: *mut VkDebugReportCallbackEXT,
+) -> VkResult {
+    let info = unsafe { &*pCreateInfo };
+    assert_eq!(info.sType, VkStructureType::VK_STRUCTURE_TYPE_DEBUG_REPORT_CALLBACK_CREATE_INFO_EXT);
+
+    let callback = Handle::new(DebugReportCallback {
+        flags: info.flags,
+        function: info.pfnCallback,
+        user_data: info.pUserData,
+    }).owned_by(instance);
+    instance.debug_reports.callbacks.lock().unwrap().push(callback);
+
+    unsafe { *pCallback = callback };
+    VkResult::VK_SUCCESS
+}
+
+#[inline]
+pub extern "C" fn gfxDestroyDebugReportCallbackEXT(
+    instance: VkInstance,
+    callback: VkDebugReportCallbackEXT,
+    _pAllocator: *const VkAllocationCallbacks,
+) {
+    instance.debug_reports.callbacks.lock().unwrap().retain(|&c| c != callback);
+    let _ = callback.check_owner(instance).unbox();
+}
+
+#[inline]
+pub extern "C" fn gfxDebugReportMessageEXT(
+    instance: VkInstance,
+    flags: VkDebugReportFlagsEXT,
+    objectType: VkDebugReportObjectTypeEXT,
+    object: u64,
+    location: usize,
+    messageCode: i32,
+    pLayerPrefix: *const ::std::os::raw::c_char,
+    pMessage: *const ::std::os::raw::c_char,
+) {
+    for callback in instance.debug_reports.callbacks.lock().unwrap().iter() {
+        if callback.flags & flags == 0 {
+            continue;
+        }
+        if let Some(function) = callback.function {
+            unsafe {
+                function(
+                    flags,
+                    objectType,
+                    object,
+                    location,
+                    messageCode,
+                    pLayerPrefix,
+                    pMessage,
+                    callback.user_data,
+                )
+            };
+        }
+    }
+}
@@ -2,7 +2,6 @@
 #![allow(non_camel_case_types)]
 #![allow(non_upper_case_globals)]
 #![allow(improper_ctypes)] //TEMP: buggy Rustc FFI analysis
-#![cfg_attr(feature = "nightly", feature(core_intrinsics))]
 
 extern crate gfx_hal as hal;
 #[cfg(feature = "gfx-backend-dx12")]
@@ -16,6 +15,8 @@ extern crate gfx_backend_vulkan as back;
 #[cfg(feature = "gfx-backend-gl")]
 extern crate gfx_backend_gl as back;
 
+#[cfg(feature = "backtrace")]
+extern crate backtrace;
 extern crate copyless;
 #[macro_use]
 extern crate lazy_static;
@@ -25,26 +26,45 @@ extern crate log;
 extern crate env_logger;
 #[cfg(feature = "renderdoc")]
 extern crate renderdoc;
+extern crate phf;
+extern crate portability_subset;
+extern crate toml;
 
+mod config;
 mod conv;
+mod dispatch;
+mod fan;
 mod handle;
 mod impls;
+mod portability;
+mod restart;
+mod submit;
 
 use back::Backend as B;
-use handle::{DispatchHandle, Handle};
+use handle::{DispatchHandle, DispatchHandleSlab, Handle, HandleSlab};
 
 use std::{slice};
+use std::ffi::CString;
+use std::os::raw::c_void;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
+pub use dispatch::{loader_interface_version, physical_device_proc_addr, set_loader_interface_version};
 pub use impls::*;
+pub use portability_subset::{
+    PortabilitySubset, PORTABILITY_FEATURE_NAMES,
+    VkPhysicalDevicePortabilitySubsetFeaturesEXTX, VkPhysicalDevicePortabilitySubsetPropertiesEXTX,
+    VK_EXTX_PORTABILITY_SUBSET_EXTENSION_NAME, VK_EXTX_PORTABILITY_SUBSET_SPEC_VERSION,
+};
 
 // Vulkan objects
-pub type VkInstance = Handle<RawInstance>;
-pub type VkPhysicalDevice = Handle<hal::Adapter<B>>;
+pub type VkInstance = DispatchHandle<RawInstance>;
+pub type VkPhysicalDevice = DispatchHandle<PhysicalDevice<B>>;
 pub type VkDevice = DispatchHandle<Gpu<B>>;
-pub type VkQueue = DispatchHandle<<B as hal::Backend>::CommandQueue>;
+pub type VkQueue = DispatchHandle<Queue<B>>;
 pub type VkCommandPool = Handle<CommandPool<B>>;
-pub type VkCommandBuffer = DispatchHandle<<B as hal::Backend>::CommandBuffer>;
+pub type VkCommandBuffer = DispatchHandle<CommandBuffer<B>>;
 pub type VkDeviceMemory = Handle<<B as hal::Backend>::Memory>;
 pub type VkDescriptorSetLayout = Handle<<B as hal::Backend>::DescriptorSetLayout>;
 pub type VkPipelineLayout = Handle<<B as hal::Backend>::PipelineLayout>;
@@ -58,7 +78,7 @@ pub type VkImageView = Handle<<B as hal::Backend>::ImageView>;
 pub type VkBuffer = Handle<<B as hal::Backend>::Buffer>;
 pub type VkSemaphore = Handle<<B as hal::Backend>::Semaphore>;
 pub type VkFence = Handle<<B as hal::Backend>::Fence>;
-pub type VkRenderPass = Handle<<B as hal::Backend>::RenderPass>;
+pub type VkRenderPass = Handle<RenderPass<B>>;
 pub type VkFramebuffer = Handle<<B as hal::Backend>::Framebuffer>;
 pub type VkPipeline = Handle<Pipeline<B>>;
 pub type VkPipelineCache = Handle<<B as hal::Backend>::PipelineCache>;
@@ -70,27 +90,173 @@ pub struct RawInstance {
     pub backend: back::Instance,
     pub adapters: Vec<VkPhysicalDevice>,
     pub enabled_extensions: Vec<String>,
+    pub settings: Arc<config::Settings>,
+    pub debug_reports: Arc<DebugReports>,
 }
 
+pub struct PhysicalDevice<B: hal::Backend> {
+    raw: hal::Adapter<B>,
+    /// Instance the device was enumerated from, owning the devices created from it.
+    instance: VkInstance,
+    /// Settings of the instance the device was enumerated from.
+    settings: Arc<config::Settings>,
+    debug_reports: Arc<DebugReports>,
+}
+
+/// Callbacks registered with `VK_EXT_debug_report`, shared by an instance with its devices.
+pub struct DebugReports {
+    callbacks: Mutex<Vec<VkDebugReportCallbackEXT>>,
+}
+
+impl DebugReports {
+    fn new() -> Self {
+        DebugReports {
+            callbacks: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Passes a message to the callbacks interested in the given flag.
+    fn message(
+        &self,
+        flag: VkDebugReportFlagBitsEXT,
+        object_type: VkDebugReportObjectTypeEXT,
+        object: u64,
+        message: &str,
+    ) {
+        let callbacks = self.callbacks.lock().unwrap();
+        if callbacks.is_empty() {
+            return;
+        }
+        let message = CString::new(message.replace('\0', "")).unwrap();
+        for callback in callbacks.iter() {
+            if callback.flags & flag as u32 == 0 {
+                continue;
+            }
+            if let Some(function) = callback.function {
+                unsafe {
+                    function(
+                        flag as _,
+                        object_type,
+                        object,
+                        0,
+                        0,
+                        DEBUG_REPORT_LAYER_PREFIX.as_ptr() as *const _,
+                        message.as_ptr(),
+                        callback.user_data,
+                    )
+                };
+            }
+        }
+    }
+}
+
+const DEBUG_REPORT_LAYER_PREFIX: &[u8] = b"portability\0";
+
 pub struct Gpu<B: hal::Backend> {
     device: B::Device,
     queues: HashMap<QueueFamilyIndex, Vec<VkQueue>>,
     enabled_extensions: Vec<String>,
+    features: hal::Features,
+    /// Limits reported by the physical device, checked against the usage.
+    limits: VkPhysicalDeviceLimits,
+    /// Live memory allocations and samplers, bounded by the limits.
+    allocation_count: AtomicUsize,
+    sampler_count: AtomicUsize,
+    /// Memory types of the adapter, for the allocations owned by the driver.
+    memory_types: Vec<hal::adapter::MemoryType>,
+    /// Sizes of the memory objects, their host mappings as offset, end and address,
+    /// and the memory bound to the buffers, to read the application data (see `fan.rs`).
+    memory_sizes: Mutex<HashMap<usize, VkDeviceSize>>,
+    mapped_memory: Mutex<HashMap<usize, (VkDeviceSize, VkDeviceSize, usize)>>,
+    buffer_memory: Mutex<HashMap<usize, (usize, VkDeviceSize)>>,
+    /// The backend draws triangle fans, which are emulated otherwise.
+    native_fans: bool,
+    /// Index patterns of the emulated fans and strips.
+    pattern: Mutex<fan::Pattern<B>>,
+    lost: Arc<LostState>,
+    pending: Arc<submit::Pending>,
+    settings: Arc<config::Settings>,
+    debug_reports: Arc<DebugReports>,
     #[cfg(feature = "renderdoc")]
     renderdoc: renderdoc::RenderDoc<renderdoc::V110>,
     #[cfg(feature = "renderdoc")]
     capturing: *mut (),
 }
 
+pub struct Queue<B: hal::Backend> {
+    // declared first, so that the thread is joined before `raw` is dropped
+    worker: Option<submit::Worker>,
+    raw: B::CommandQueue,
+    lost: Arc<LostState>,
+}
+
+impl<B: hal::Backend> Queue<B> {
+    /// Makes sure all the work enqueued on the device reached the backend.
+    fn flush(&self) {
+        if let Some(ref worker) = self.worker {
+            worker.flush();
+        }
+    }
+}
+
+/// Sticky device loss status, shared between a device and its queues.
+#[derive(Debug, Default)]
+pub struct LostState {
+    lost: AtomicBool,
+    reason: Mutex<Option<String>>,
+}
+
+impl LostState {
+    fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Acquire)
+    }
+
+    /// Marks the device as lost, keeping the reason of the first loss only.
+    fn mark(&self, reason: &str) {
+        let mut guard = self.reason.lock().unwrap();
+        if guard.is_none() {
+            error!("Device lost on {}", reason);
+            *guard = Some(reason.to_owned());
+        }
+        self.lost.store(true, Ordering::Release);
+    }
+
+    fn reason(&self) -> Option<String> {
+        self.reason.lock().unwrap().clone()
+    }
+}
+
 pub struct DescriptorPool<B: hal::Backend> {
     raw: B::DescriptorPool,
     temp_sets: Vec<B::DescriptorSet>,
     set_handles: Option<Vec<VkDescriptorSet>>,
+    /// Storage of the freed set handles.
+    slab: HandleSlab<B::DescriptorSet>,
 }
 
 pub enum Pipeline<B: hal::Backend> {
-    Graphics(B::GraphicsPipeline),
-    Compute(B::ComputePipeline),
+    Graphics {
+        raw: B::GraphicsPipeline,
+        /// Variant restarting at 16-bit indices, see `restart.rs`.
+        raw_u16: Option<B::GraphicsPipeline>,
+        /// Created with `VK_PIPELINE_CREATE_ALLOW_DERIVATIVES_BIT`.
+        allow_derivatives: bool,
+        /// Emulated triangle fans drawn as lists, see `fan.rs`.
+        triangle_fan: bool,
+        /// Strips with primitive restart drawn as lists.
+        restart: Option<restart::Strip>,
+    },
+    Compute {
+        raw: B::ComputePipeline,
+        allow_derivatives: bool,
+    },
+}
+
+pub struct RenderPass<B: hal::Backend> {
+    raw: B::RenderPass,
+    /// Subpasses using a depth-stencil attachment, which the pipelines
+    /// created for the other ones don't read the depth-stencil state of.
+    depth_stencil: Vec<bool>,
 }
 
 pub struct Image<B: hal::Backend> {
@@ -145,17 +311,47 @@ impl<B: hal::Backend> Image<B> {
 pub struct CommandPool<B: hal::Backend> {
     pool: B::CommandPool,
     buffers: Vec<VkCommandBuffer>,
+    /// Storage of the freed command buffer handles.
+    slab: DispatchHandleSlab<CommandBuffer<B>>,
+}
+
+pub struct CommandBuffer<B: hal::Backend> {
+    raw: B::CommandBuffer,
+    device: VkDevice,
+    /// Triangle fan emulation state.
+    fan: fan::State<B>,
 }
 
 //NOTE: all *KHR types have to be pure `Handle` things for compatibility with
 //`VK_DEFINE_NON_DISPATCHABLE_HANDLE` used in `vulkan.h`
 pub type VkSurfaceKHR = Handle<<B as hal::Backend>::Surface>;
 pub type VkSwapchainKHR = Handle<Swapchain>;
+pub type VkDebugReportCallbackEXT = Handle<DebugReportCallback>;
 
 pub struct Swapchain {
     // this can become None if it was used as the "old_swapchain"
     raw: Option<<B as hal::Backend>::Swapchain>,
     images: Vec<VkImage>,
+    /// Failure of a presentation done by a submission thread.
+    present_error: Mutex<Option<VkResult>>,
+}
+
+impl Swapchain {
+    /// Error of an asynchronous presentation, reported by the next acquisition or presentation.
+    fn present_error(&self) -> Option<VkResult> {
+        let mut error = self.present_error.lock().unwrap();
+        match *error {
+            // the swapchain stays unusable until recreated
+            Some(VkResult::VK_ERROR_OUT_OF_DATE_KHR) | Some(VkResult::VK_ERROR_SURFACE_LOST_KHR) => *error,
+            _ => error.take(),
+        }
+    }
+}
+
+pub struct DebugReportCallback {
+    flags: VkDebugReportFlagsEXT,
+    function: PFN_vkDebugReportCallbackEXT,
+    user_data: *mut c_void,
 }
 
 /* automatically generated by rust-bindgen */
@@ -519,12 +715,14 @@ pub const VK_EXT_DISCARD_RECTANGLES_SPEC_VERSION: ::std::os::raw::c_uint = 1;
 pub const VK_EXT_DISCARD_RECTANGLES_EXTENSION_NAME: &'static [u8; 26usize] =
     b"VK_EXT_discard_rectangles\x00";
 pub const VK_EXTX_portability_subset: ::std::os::raw::c_uint = 1;
-pub const VK_EXTX_PORTABILITY_SUBSET_SPEC_VERSION:
-          ::std::os::raw::c_uint =
-    1;
-pub const VK_EXTX_PORTABILITY_SUBSET_EXTENSION_NAME:
-          &'static [u8; 27usize] =
-    b"VK_EXTX_portability_subset\x00";
+pub const VK_EXT_global_priority: ::std::os::raw::c_uint = 1;
+pub const VK_EXT_GLOBAL_PRIORITY_SPEC_VERSION: ::std::os::raw::c_uint = 2;
+pub const VK_EXT_GLOBAL_PRIORITY_EXTENSION_NAME: &'static [u8; 23usize] =
+    b"VK_EXT_global_priority\x00";
+pub const VK_EXT_device_fault: ::std::os::raw::c_uint = 1;
+pub const VK_EXT_DEVICE_FAULT_SPEC_VERSION: ::std::os::raw::c_uint = 2;
+pub const VK_EXT_DEVICE_FAULT_EXTENSION_NAME: &'static [u8; 20usize] =
+    b"VK_EXT_device_fault\x00";
 
 
 pub type wchar_t = ::std::os::raw::c_int;
@@ -677,6 +875,7 @@ pub enum VkResult {
     VK_ERROR_INVALID_SHADER_NV = -1000012000,
     VK_ERROR_OUT_OF_POOL_MEMORY_KHR = -1000069000,
     VK_ERROR_INVALID_EXTERNAL_HANDLE_KHX = -1000072003,
+    VK_ERROR_NOT_PERMITTED_EXT = -1000174001,
     VK_RESULT_RANGE_SIZE = 18,
     VK_RESULT_MAX_ENUM = 2147483647,
 }
@@ -849,6 +1048,11 @@ pub enum VkStructureType {
     VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_PORTABILITY_SUBSET_FEATURES_EXTX = 1000163000,
     VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_PORTABILITY_SUBSET_PROPERTIES_EXTX = 1000163001,
     VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_IMAGE_VIEW_SUPPORT_EXTX = 100163002,
+    VK_STRUCTURE_TYPE_DEVICE_QUEUE_INFO_2 = 1000145003,
+    VK_STRUCTURE_TYPE_DEVICE_QUEUE_GLOBAL_PRIORITY_CREATE_INFO_EXT = 1000174000,
+    VK_STRUCTURE_TYPE_DEVICE_FAULT_COUNTS_EXT = 1000341001,
+    VK_STRUCTURE_TYPE_DEVICE_FAULT_INFO_EXT = 1000341002,
+    VK_STRUCTURE_TYPE_TIMELINE_SEMAPHORE_SUBMIT_INFO = 1000207003,
     VK_STRUCTURE_TYPE_MAX_ENUM = 2147483647,
 }
 pub const VkSystemAllocationScope_VK_SYSTEM_ALLOCATION_SCOPE_BEGIN_RANGE:
@@ -1653,6 +1857,12 @@ pub enum VkMemoryHeapFlagBits {
 }
 pub type VkMemoryHeapFlags = VkFlags;
 pub type VkDeviceCreateFlags = VkFlags;
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum VkDeviceQueueCreateFlagBits {
+    VK_DEVICE_QUEUE_CREATE_PROTECTED_BIT = 1,
+    VK_DEVICE_QUEUE_CREATE_FLAG_BITS_MAX_ENUM = 2147483647,
+}
 pub type VkDeviceQueueCreateFlags = VkFlags;
 #[repr(u32)]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
@@ -5214,12 +5424,6 @@ extern "C" {
                                                  pData:
                                                      *const ::std::os::raw::c_void);
 }
-#[repr(C)]
-#[derive(Debug, Copy, Clone)]
-pub struct VkDebugReportCallbackEXT_T {
-    _unused: [u8; 0],
-}
-pub type VkDebugReportCallbackEXT = *mut VkDebugReportCallbackEXT_T;
 pub const VkDebugReportObjectTypeEXT_VK_DEBUG_REPORT_OBJECT_TYPE_BEGIN_RANGE_EXT:
           VkDebugReportObjectTypeEXT =
     VkDebugReportObjectTypeEXT::VK_DEBUG_REPORT_OBJECT_TYPE_UNKNOWN_EXT;
@@ -5341,32 +5545,6 @@ pub type PFN_vkDebugReportMessageEXT =
                                                    *const ::std::os::raw::c_char,
                                                pMessage:
                                                    *const ::std::os::raw::c_char)>;
-extern "C" {
-    pub fn vkCreateDebugReportCallbackEXT(instance: VkInstance,
-                                          pCreateInfo:
-                                              *const VkDebugReportCallbackCreateInfoEXT,
-                                          pAllocator:
-                                              *const VkAllocationCallbacks,
-                                          pCallback:
-                                              *mut VkDebugReportCallbackEXT)
-     -> VkResult;
-}
-extern "C" {
-    pub fn vkDestroyDebugReportCallbackEXT(instance: VkInstance,
-                                           callback: VkDebugReportCallbackEXT,
-                                           pAllocator:
-                                               *const VkAllocationCallbacks);
-}
-extern "C" {
-    pub fn vkDebugReportMessageEXT(instance: VkInstance,
-                                   flags: VkDebugReportFlagsEXT,
-                                   objectType: VkDebugReportObjectTypeEXT,
-                                   object: u64, location: usize,
-                                   messageCode: i32,
-                                   pLayerPrefix:
-                                       *const ::std::os::raw::c_char,
-                                   pMessage: *const ::std::os::raw::c_char);
-}
 pub const VkRasterizationOrderAMD_VK_RASTERIZATION_ORDER_BEGIN_RANGE_AMD:
           VkRasterizationOrderAMD =
     VkRasterizationOrderAMD::VK_RASTERIZATION_ORDER_STRICT_AMD;
@@ -6939,50 +7117,139 @@ pub type PFN_vkCreateMacOSSurfaceMVK = ::std::option::Option<unsafe extern "C" f
 
 #[repr(C)]
 #[derive(Debug, Copy)]
-pub struct VkPhysicalDevicePortabilitySubsetFeaturesEXTX {
+pub struct VkPhysicalDeviceImageViewSupportEXTX {
+    pub sType: VkStructureType,
+    pub pNext: *const ::std::os::raw::c_void,
+    pub flags: VkImageViewCreateFlags,
+    pub viewType: VkImageViewType,
+    pub format: VkFormat,
+    pub components: VkComponentMapping,
+    pub aspectMask: VkImageAspectFlags,
+}
+impl Clone for VkPhysicalDeviceImageViewSupportEXTX {
+    fn clone(&self) -> Self { *self }
+}
+#[repr(C)]
+#[derive(Debug, Copy)]
+pub struct VkMetalSurfaceCreateInfoEXT {
     pub sType: VkStructureType,
     pub pNext: *const ::std::os::raw::c_void,
-    pub triangleFans: VkBool32,
-    pub separateStencilMaskRef: VkBool32,
-    pub events: VkBool32,
-    pub standardImageViews: VkBool32,
-    pub samplerMipLodBias: VkBool32,
+    pub flags: VkMetalSurfaceCreateFlagsEXT,
+    pub pLayer: *const ::std::os::raw::c_void,
 }
-impl Clone for VkPhysicalDevicePortabilitySubsetFeaturesEXTX {
+impl Clone for VkMetalSurfaceCreateInfoEXT {
     fn clone(&self) -> Self { *self }
 }
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum VkQueueGlobalPriorityEXT {
+    VK_QUEUE_GLOBAL_PRIORITY_LOW_EXT = 128,
+    VK_QUEUE_GLOBAL_PRIORITY_MEDIUM_EXT = 256,
+    VK_QUEUE_GLOBAL_PRIORITY_HIGH_EXT = 512,
+    VK_QUEUE_GLOBAL_PRIORITY_REALTIME_EXT = 1024,
+    VK_QUEUE_GLOBAL_PRIORITY_MAX_ENUM_EXT = 2147483647,
+}
 #[repr(C)]
 #[derive(Debug, Copy)]
-pub struct VkPhysicalDevicePortabilitySubsetPropertiesEXTX {
+pub struct VkDeviceQueueGlobalPriorityCreateInfoEXT {
     pub sType: VkStructureType,
     pub pNext: *const ::std::os::raw::c_void,
-    pub minVertexInputBindingStrideAlignment: u32,
+    pub globalPriority: VkQueueGlobalPriorityEXT,
 }
-impl Clone for VkPhysicalDevicePortabilitySubsetPropertiesEXTX {
+impl Clone for VkDeviceQueueGlobalPriorityCreateInfoEXT {
     fn clone(&self) -> Self { *self }
 }
 #[repr(C)]
 #[derive(Debug, Copy)]
-pub struct VkPhysicalDeviceImageViewSupportEXTX {
+pub struct VkDeviceQueueInfo2 {
     pub sType: VkStructureType,
     pub pNext: *const ::std::os::raw::c_void,
-    pub flags: VkImageViewCreateFlags,
-    pub viewType: VkImageViewType,
-    pub format: VkFormat,
-    pub components: VkComponentMapping,
-    pub aspectMask: VkImageAspectFlags,
+    pub flags: VkDeviceQueueCreateFlags,
+    pub queueFamilyIndex: u32,
+    pub queueIndex: u32,
 }
-impl Clone for VkPhysicalDeviceImageViewSupportEXTX {
+impl Clone for VkDeviceQueueInfo2 {
     fn clone(&self) -> Self { *self }
 }
+
+pub type PFN_vkGetDeviceQueue2 = ::std::option::Option<unsafe extern "C" fn(
+    device: VkDevice,
+    pQueueInfo: *const VkDeviceQueueInfo2,
+    pQueue: *mut VkQueue,
+)>;
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum VkDeviceFaultAddressTypeEXT {
+    VK_DEVICE_FAULT_ADDRESS_TYPE_NONE_EXT = 0,
+    VK_DEVICE_FAULT_ADDRESS_TYPE_READ_INVALID_EXT = 1,
+    VK_DEVICE_FAULT_ADDRESS_TYPE_WRITE_INVALID_EXT = 2,
+    VK_DEVICE_FAULT_ADDRESS_TYPE_EXECUTE_INVALID_EXT = 3,
+    VK_DEVICE_FAULT_ADDRESS_TYPE_INSTRUCTION_POINTER_UNKNOWN_EXT = 4,
+    VK_DEVICE_FAULT_ADDRESS_TYPE_INSTRUCTION_POINTER_INVALID_EXT = 5,
+    VK_DEVICE_FAULT_ADDRESS_TYPE_INSTRUCTION_POINTER_FAULT_EXT = 6,
+    VK_DEVICE_FAULT_ADDRESS_TYPE_MAX_ENUM_EXT = 2147483647,
+}
 #[repr(C)]
 #[derive(Debug, Copy)]
-pub struct VkMetalSurfaceCreateInfoEXT {
+pub struct VkDeviceFaultCountsEXT {
+    pub sType: VkStructureType,
+    pub pNext: *mut ::std::os::raw::c_void,
+    pub addressInfoCount: u32,
+    pub vendorInfoCount: u32,
+    pub vendorBinarySize: VkDeviceSize,
+}
+impl Clone for VkDeviceFaultCountsEXT {
+    fn clone(&self) -> Self { *self }
+}
+#[repr(C)]
+#[derive(Debug, Copy)]
+pub struct VkDeviceFaultAddressInfoEXT {
+    pub addressType: VkDeviceFaultAddressTypeEXT,
+    pub reportedAddress: u64,
+    pub addressPrecision: VkDeviceSize,
+}
+impl Clone for VkDeviceFaultAddressInfoEXT {
+    fn clone(&self) -> Self { *self }
+}
+#[repr(C)]
+#[derive(Copy)]
+pub struct VkDeviceFaultVendorInfoEXT {
+    pub description: [::std::os::raw::c_char; 256usize],
+    pub vendorFaultCode: u64,
+    pub vendorFaultData: u64,
+}
+impl Clone for VkDeviceFaultVendorInfoEXT {
+    fn clone(&self) -> Self { *self }
+}
+#[repr(C)]
+#[derive(Copy)]
+pub struct VkDeviceFaultInfoEXT {
+    pub sType: VkStructureType,
+    pub pNext: *mut ::std::os::raw::c_void,
+    pub description: [::std::os::raw::c_char; 256usize],
+    pub pAddressInfos: *mut VkDeviceFaultAddressInfoEXT,
+    pub pVendorInfos: *mut VkDeviceFaultVendorInfoEXT,
+    pub pVendorBinaryData: *mut ::std::os::raw::c_void,
+}
+impl Clone for VkDeviceFaultInfoEXT {
+    fn clone(&self) -> Self { *self }
+}
+
+pub type PFN_vkGetDeviceFaultInfoEXT = ::std::option::Option<unsafe extern "C" fn(
+    device: VkDevice,
+    pFaultCounts: *mut VkDeviceFaultCountsEXT,
+    pFaultInfo: *mut VkDeviceFaultInfoEXT,
+) -> VkResult>;
+#[repr(C)]
+#[derive(Debug, Copy)]
+pub struct VkTimelineSemaphoreSubmitInfo {
     pub sType: VkStructureType,
     pub pNext: *const ::std::os::raw::c_void,
-    pub flags: VkMetalSurfaceCreateFlagsEXT,
-    pub pLayer: *const ::std::os::raw::c_void,
+    pub waitSemaphoreValueCount: u32,
+    pub pWaitSemaphoreValues: *const u64,
+    pub signalSemaphoreValueCount: u32,
+    pub pSignalSemaphoreValues: *const u64,
 }
-impl Clone for VkMetalSurfaceCreateInfoEXT {
+impl Clone for VkTimelineSemaphoreSubmitInfo {
     fn clone(&self) -> Self { *self }
 }
@@ -0,0 +1,16 @@
+//! Portability subset supported by the adapters.
+
+use super::*;
+
+/// Subset supported by an adapter.
+pub fn subset_from_hal(features: hal::Features, limits: &hal::Limits) -> PortabilitySubset {
+    PortabilitySubset {
+        triangle_fans: features.contains(hal::Features::TRIANGLE_FAN),
+        separate_stencil_mask_ref: features.contains(hal::Features::SEPARATE_STENCIL_REF_VALUES),
+        sampler_mip_lod_bias: features.contains(hal::Features::SAMPLER_MIP_LOD_BIAS),
+        //TODO: turn this into a feature flag
+        standard_image_views: !cfg!(feature = "gfx-backend-metal"),
+        min_vertex_input_binding_stride_alignment:
+            limits.min_vertex_input_binding_stride_alignment as u32,
+    }
+}
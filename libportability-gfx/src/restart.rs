@@ -0,0 +1,83 @@
+//! Primitive restart, following the index type bound at draw time.
+//!
+//! Vulkan restarts strips at the maximum value of the bound index type:
+//!   - backends baking that value into the pipeline state get a 16-bit variant
+//!     of the pipeline, bound instead of the 32-bit one along 16-bit indices,
+//!   - backends with fixed restart semantics draw the strips as lists, with
+//!     the indices split at the restart values on the CPU when submitted (see `fan.rs`).
+
+use hal::command::RawCommandBuffer;
+
+use super::*;
+
+/// Backends with the restart value baked into the pipeline state.
+pub const TYPED: bool = cfg!(feature = "gfx-backend-dx12");
+/// Backends with fixed restart semantics, ignoring the pipeline state.
+pub const FIXED: bool = cfg!(feature = "gfx-backend-gl");
+
+/// Strip topology drawn as a list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Strip {
+    Line,
+    Triangle,
+}
+
+impl Strip {
+    /// Returns the strip to split on the CPU, if the pipeline can't restart natively.
+    pub fn emulated(topology: VkPrimitiveTopology, restart: bool) -> Option<Self> {
+        use super::VkPrimitiveTopology::*;
+
+        if !FIXED || !restart {
+            return None;
+        }
+        match topology {
+            VK_PRIMITIVE_TOPOLOGY_LINE_STRIP => Some(Strip::Line),
+            VK_PRIMITIVE_TOPOLOGY_TRIANGLE_STRIP => Some(Strip::Triangle),
+            _ => None,
+        }
+    }
+
+    /// Topology of the pipeline drawing the split strips.
+    pub fn list_topology(&self) -> VkPrimitiveTopology {
+        match *self {
+            Strip::Line => VkPrimitiveTopology::VK_PRIMITIVE_TOPOLOGY_LINE_LIST,
+            Strip::Triangle => VkPrimitiveTopology::VK_PRIMITIVE_TOPOLOGY_TRIANGLE_LIST,
+        }
+    }
+}
+
+/// Index restarting the strips of the given index type.
+pub fn restart_index(index_type: VkIndexType) -> u32 {
+    match index_type {
+        VkIndexType::VK_INDEX_TYPE_UINT16 => 0xFFFF,
+        _ => !0,
+    }
+}
+
+/// Binds the variant of the graphics pipeline matching the bound index type.
+pub fn bind_pipeline(cmd_buf: &mut CommandBuffer<B>) {
+    let pipeline = match cmd_buf.fan.bound_pipeline {
+        Some(pipeline) => pipeline,
+        None => return,
+    };
+    if let Pipeline::Graphics { ref raw, ref raw_u16, .. } = *pipeline {
+        let raw = match (raw_u16, cmd_buf.fan.index_buffer) {
+            (&Some(ref raw_u16), Some((_, _, VkIndexType::VK_INDEX_TYPE_UINT16))) => raw_u16,
+            _ => raw,
+        };
+        unsafe {
+            cmd_buf.raw.bind_graphics_pipeline(raw);
+        }
+    }
+}
+
+/// Switches to the other variant of the bound pipeline, once the index type changes.
+pub fn rebind_pipeline(cmd_buf: &mut CommandBuffer<B>) {
+    let has_variant = match cmd_buf.fan.bound_pipeline.as_ref().map(|pipeline| &**pipeline) {
+        Some(&Pipeline::Graphics { raw_u16: Some(_), .. }) => true,
+        _ => false,
+    };
+    if has_variant {
+        bind_pipeline(cmd_buf);
+    }
+}
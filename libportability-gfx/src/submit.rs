@@ -0,0 +1,190 @@
+//! Optional per-queue submission thread, taking the backend submission
+//! and presentation work off the application thread.
+
+use hal::pso;
+use hal::queue::RawCommandQueue;
+
+use std::collections::HashMap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+use super::*;
+
+/// Backend work recorded by `gfxQueueSubmit` and `gfxQueuePresentKHR`.
+pub enum Job {
+    Submit {
+        command_buffers: Vec<VkCommandBuffer>,
+        wait_semaphores: Vec<(VkSemaphore, pso::PipelineStage)>,
+        signal_semaphores: Vec<VkSemaphore>,
+        fence: VkFence,
+    },
+    /// Errors are kept on the swapchains, for the next acquisition or presentation.
+    Present {
+        swapchains: Vec<(VkSwapchainKHR, u32)>,
+        wait_semaphores: Vec<VkSemaphore>,
+    },
+}
+
+// The application keeps everything referenced by a submission alive until
+// it observes the completion, which always goes through `Pending::flush`.
+unsafe impl Send for Job {}
+
+/// Number of jobs not yet handed to the backend by the workers of a device.
+#[derive(Debug, Default)]
+pub struct Pending {
+    count: Mutex<usize>,
+    idle: Condvar,
+    /// Worker having enqueued the last signal of each semaphore, by handle key.
+    signals: Mutex<HashMap<usize, usize>>,
+    workers: AtomicUsize,
+}
+
+impl Pending {
+    fn add(&self) {
+        *self.count.lock().unwrap() += 1;
+    }
+
+    fn remove(&self) {
+        let mut count = self.count.lock().unwrap();
+        *count -= 1;
+        if *count == 0 {
+            self.idle.notify_all();
+        }
+    }
+
+    /// Blocks until every job enqueued so far has reached the backend.
+    pub fn flush(&self) {
+        let mut count = self.count.lock().unwrap();
+        while *count != 0 {
+            count = self.idle.wait(count).unwrap();
+        }
+    }
+}
+
+struct RawQueue(*mut <B as hal::Backend>::CommandQueue);
+unsafe impl Send for RawQueue {}
+
+pub struct Worker {
+    id: usize,
+    sender: Option<mpsc::Sender<Job>>,
+    thread: Option<thread::JoinHandle<()>>,
+    pending: Arc<Pending>,
+}
+
+impl Worker {
+    /// Spawns a thread submitting to the given queue, which has to outlive the worker.
+    pub fn new(
+        queue: *mut <B as hal::Backend>::CommandQueue,
+        pending: Arc<Pending>,
+        lost: Arc<LostState>,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel();
+
+        let raw = RawQueue(queue);
+        let thread_pending = Arc::clone(&pending);
+        let thread = thread::Builder::new()
+            .name("gfx-submit".to_string())
+            .spawn(move || {
+                let queue = unsafe { &mut *raw.0 };
+                for job in receiver {
+                    execute(queue, &lost, job);
+                    thread_pending.remove();
+                }
+            })
+            .expect("Failed to spawn the submission thread");
+
+        Worker {
+            id: pending.workers.fetch_add(1, Ordering::Relaxed),
+            sender: Some(sender),
+            thread: Some(thread),
+            pending,
+        }
+    }
+
+    pub fn enqueue(&self, job: Job) {
+        let (wait_semaphores, signal_semaphores) = match job {
+            Job::Submit { ref wait_semaphores, ref signal_semaphores, .. } => {
+                (wait_semaphores.iter().map(|&(semaphore, _)| semaphore).collect::<Vec<_>>(), &signal_semaphores[..])
+            }
+            Job::Present { ref wait_semaphores, .. } => (wait_semaphores.clone(), &[][..]),
+        };
+        if self.record_signals(&wait_semaphores, signal_semaphores) {
+            // the backend needs the signals submitted before the waits
+            self.flush();
+        }
+
+        self.pending.add();
+        self.sender
+            .as_ref()
+            .unwrap()
+            .send(job)
+            .expect("Submission thread is gone");
+    }
+
+    /// Waits for the jobs of all the queues of the device.
+    pub fn flush(&self) {
+        self.pending.flush();
+    }
+
+    /// Consumes the waited signals and records the new ones.
+    /// Returns `true` if any waited signal was enqueued by another worker.
+    fn record_signals(&self, wait_semaphores: &[VkSemaphore], signal_semaphores: &[VkSemaphore]) -> bool {
+        let mut signals = self.pending.signals.lock().unwrap();
+        let mut foreign = false;
+        for semaphore in wait_semaphores {
+            if let Some(id) = signals.remove(&semaphore.key()) {
+                foreign |= id != self.id;
+            }
+        }
+        for semaphore in signal_semaphores {
+            signals.insert(semaphore.key(), self.id);
+        }
+        foreign
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        // closing the channel lets the thread finish the remaining jobs and exit
+        self.sender.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn execute(queue: &mut <B as hal::Backend>::CommandQueue, lost: &LostState, job: Job) {
+    match job {
+        Job::Submit { command_buffers, wait_semaphores, signal_semaphores, fence } => {
+            let submission = hal::queue::Submission {
+                command_buffers: command_buffers.iter().map(|cmd_buf| &cmd_buf.raw),
+                wait_semaphores: wait_semaphores
+                    .iter()
+                    .map(|&(ref semaphore, stage)| (&**semaphore, stage)),
+                signal_semaphores: signal_semaphores
+                    .iter()
+                    .map(|semaphore| &**semaphore),
+            };
+            unsafe { queue.submit(submission, fence.as_ref()); }
+        }
+        Job::Present { swapchains, wait_semaphores } => {
+            let raw_swapchains = swapchains
+                .iter()
+                .map(|&(ref swapchain, index)| (swapchain.raw.as_ref().unwrap(), index));
+            let raw_semaphores = wait_semaphores
+                .iter()
+                .map(|semaphore| &**semaphore);
+            if let Err(err) = unsafe { queue.present(raw_swapchains, raw_semaphores) } {
+                let result = conv::map_present_error(err);
+                if result == VkResult::VK_ERROR_DEVICE_LOST {
+                    lost.mark("presentation");
+                }
+                for &(ref swapchain, _) in &swapchains {
+                    *swapchain.present_error.lock().unwrap() = Some(result);
+                }
+            }
+        }
+    }
+}
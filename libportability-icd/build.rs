@@ -0,0 +1,39 @@
+//! Writes the ICD manifest next to the produced library, so that it can be
+//! used with `VK_ICD_FILENAMES=target/<profile>/portability_icd.json`.
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Vulkan version reported to the loader, should match the one of `portability-gfx`.
+const API_VERSION: &str = "1.0.66";
+
+fn main() {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    // OUT_DIR is `target/<profile>/build/<package>-<hash>/out`
+    let target_dir = out_dir.ancestors().nth(3).unwrap();
+
+    let library_path = match env::var("CARGO_CFG_TARGET_OS").unwrap().as_str() {
+        "windows" => ".\\\\portability_icd.dll",
+        "macos" | "ios" => "./libportability_icd.dylib",
+        _ => "./libportability_icd.so",
+    };
+
+    let mut file = File::create(target_dir.join("portability_icd.json")).unwrap();
+    write!(
+        file,
+        r#"{{
+   "file_format_version": "1.0.0",
+   "ICD": {{
+      "library_path": "{}",
+      "api_version": "{}"
+   }}
+}}
+"#,
+        library_path, API_VERSION,
+    )
+    .unwrap();
+
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -4,17 +4,22 @@ extern crate portability_gfx;
 
 use portability_gfx::*;
 
-use std::ffi::CStr;
-use std::mem;
-
-
 const ICD_VERSION: u32 = 5;
+/// Older loaders expect the surfaces to be created by them, not the ICD.
+const MIN_ICD_VERSION: u32 = 3;
 
 #[no_mangle]
 pub extern "C" fn vk_icdGetInstanceProcAddr(
     instance: VkInstance,
     pName: *const ::std::os::raw::c_char,
 ) -> PFN_vkVoidFunction {
+    // Loaders not negotiating the interface version are too old for us.
+    if loader_interface_version() < MIN_ICD_VERSION {
+        return None;
+    }
+    if instance.as_ref().is_some() && !instance.is_dispatchable() {
+        return None;
+    }
     gfxGetInstanceProcAddr(instance, pName)
 }
 
@@ -23,37 +28,24 @@ pub extern "C" fn vk_icdNegotiateLoaderICDInterfaceVersion(
     pSupportedVersion: *mut ::std::os::raw::c_uint,
 ) -> VkResult {
     let supported_version = unsafe { &mut *pSupportedVersion };
+    if *supported_version < MIN_ICD_VERSION {
+        return VkResult::VK_ERROR_INCOMPATIBLE_DRIVER;
+    }
     if *supported_version > ICD_VERSION {
         *supported_version = ICD_VERSION;
     }
+    set_loader_interface_version(*supported_version);
 
     VkResult::VK_SUCCESS
 }
 
 #[no_mangle]
 pub extern "C" fn vk_icdGetPhysicalDeviceProcAddr(
-    _instance: VkInstance,
+    instance: VkInstance,
     pName: *const ::std::os::raw::c_char,
 ) -> PFN_vkVoidFunction {
-    let name = unsafe { CStr::from_ptr(pName) };
-    let name = match name.to_str() {
-        Ok(name) => name,
-        Err(_) => return None,
-    };
-
-    proc_addr!{ name,
-        vkGetPhysicalDeviceFeatures, PFN_vkGetPhysicalDeviceFeatures => gfxGetPhysicalDeviceFeatures,
-        vkGetPhysicalDeviceFeatures2KHR, PFN_vkGetPhysicalDeviceFeatures2KHR => gfxGetPhysicalDeviceFeatures2KHR,
-        vkGetPhysicalDeviceProperties, PFN_vkGetPhysicalDeviceProperties => gfxGetPhysicalDeviceProperties,
-        vkGetPhysicalDeviceFormatProperties, PFN_vkGetPhysicalDeviceFormatProperties => gfxGetPhysicalDeviceFormatProperties,
-        vkGetPhysicalDeviceImageFormatProperties, PFN_vkGetPhysicalDeviceImageFormatProperties => gfxGetPhysicalDeviceImageFormatProperties,
-        vkGetPhysicalDeviceMemoryProperties, PFN_vkGetPhysicalDeviceMemoryProperties => gfxGetPhysicalDeviceMemoryProperties,
-        vkGetPhysicalDeviceQueueFamilyProperties, PFN_vkGetPhysicalDeviceQueueFamilyProperties => gfxGetPhysicalDeviceQueueFamilyProperties,
-        vkGetPhysicalDeviceSparseImageFormatProperties, PFN_vkGetPhysicalDeviceSparseImageFormatProperties => gfxGetPhysicalDeviceSparseImageFormatProperties,
-
-        vkGetPhysicalDeviceSurfaceSupportKHR, PFN_vkGetPhysicalDeviceSurfaceSupportKHR => gfxGetPhysicalDeviceSurfaceSupportKHR,
-        vkGetPhysicalDeviceSurfaceCapabilitiesKHR, PFN_vkGetPhysicalDeviceSurfaceCapabilitiesKHR => gfxGetPhysicalDeviceSurfaceCapabilitiesKHR,
-        vkGetPhysicalDeviceSurfaceFormatsKHR, PFN_vkGetPhysicalDeviceSurfaceFormatsKHR => gfxGetPhysicalDeviceSurfaceFormatsKHR,
-        vkGetPhysicalDeviceSurfacePresentModesKHR, PFN_vkGetPhysicalDeviceSurfacePresentModesKHR => gfxGetPhysicalDeviceSurfacePresentModesKHR,
+    if !instance.is_dispatchable() {
+        return None;
     }
+    physical_device_proc_addr(instance, pName)
 }
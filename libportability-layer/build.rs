@@ -0,0 +1,50 @@
+//! Writes the layer manifest next to the produced library, so that it can be
+//! used with `VK_LAYER_PATH=target/<profile>`.
+
+use std::env;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Vulkan version reported to the loader, should match the one of `portability-gfx`.
+const API_VERSION: &str = "1.0.66";
+
+fn main() {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    // OUT_DIR is `target/<profile>/build/<package>-<hash>/out`
+    let target_dir = out_dir.ancestors().nth(3).unwrap();
+
+    let library_path = match env::var("CARGO_CFG_TARGET_OS").unwrap().as_str() {
+        "windows" => ".\\\\portability_layer.dll",
+        "macos" | "ios" => "./libportability_layer.dylib",
+        _ => "./libportability_layer.so",
+    };
+
+    let mut file = File::create(target_dir.join("VkLayer_portability_subset.json")).unwrap();
+    write!(
+        file,
+        r#"{{
+   "file_format_version": "1.1.0",
+   "layer": {{
+      "name": "VK_LAYER_GFX_portability_subset",
+      "type": "GLOBAL",
+      "library_path": "{}",
+      "api_version": "{}",
+      "implementation_version": "1",
+      "description": "Portability subset enforcement",
+      "device_extensions": [
+         {{
+            "name": "VK_EXTX_portability_subset",
+            "spec_version": "1",
+            "entrypoints": []
+         }}
+      ]
+   }}
+}}
+"#,
+        library_path, API_VERSION,
+    )
+    .unwrap();
+
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,749 @@
+//! Vulkan layer advertising `VK_EXTX_portability_subset` on top of a native driver,
+//! and reporting the API usage the portability implementations can't support.
+//!
+//! Settings come from the environment:
+//!   - `GFX_LAYER_ALLOW`: comma separated portability features to consider supported.
+//!   - `GFX_LAYER_STRIDE_ALIGNMENT`: vertex binding stride alignment, 4 by default.
+//!   - `GFX_LAYER_REJECT`: fail the calls breaking the subset instead of only logging them.
+
+#![allow(non_snake_case)]
+
+extern crate ash;
+extern crate env_logger;
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate log;
+extern crate portability_subset;
+
+use ash::vk;
+use portability_subset::*;
+
+use std::collections::HashMap;
+use std::env;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use std::sync::Mutex;
+use std::{mem, ptr, slice};
+
+// The dispatchable objects are the loader ones, not the gfx handles.
+type VkInstance = *mut c_void;
+type VkPhysicalDevice = *mut c_void;
+type VkDevice = *mut c_void;
+type VkCommandBuffer = *mut c_void;
+
+type PFN_vkVoidFunction = Option<unsafe extern "C" fn()>;
+
+type GetInstanceProcAddr = unsafe extern "C" fn(VkInstance, *const c_char) -> PFN_vkVoidFunction;
+type GetDeviceProcAddr = unsafe extern "C" fn(VkDevice, *const c_char) -> PFN_vkVoidFunction;
+type CreateInstance = unsafe extern "C" fn(
+    *const vk::InstanceCreateInfo, *const vk::AllocationCallbacks, *mut VkInstance,
+) -> vk::Result;
+type DestroyInstance = unsafe extern "C" fn(VkInstance, *const vk::AllocationCallbacks);
+type EnumerateDeviceExtensionProperties = unsafe extern "C" fn(
+    VkPhysicalDevice, *const c_char, *mut u32, *mut vk::ExtensionProperties,
+) -> vk::Result;
+type GetPhysicalDeviceFeatures2 = unsafe extern "C" fn(VkPhysicalDevice, *mut vk::PhysicalDeviceFeatures2);
+type CreateDevice = unsafe extern "C" fn(
+    VkPhysicalDevice, *const vk::DeviceCreateInfo, *const vk::AllocationCallbacks, *mut VkDevice,
+) -> vk::Result;
+type DestroyDevice = unsafe extern "C" fn(VkDevice, *const vk::AllocationCallbacks);
+type CreateGraphicsPipelines = unsafe extern "C" fn(
+    VkDevice, vk::PipelineCache, u32, *const vk::GraphicsPipelineCreateInfo,
+    *const vk::AllocationCallbacks, *mut vk::Pipeline,
+) -> vk::Result;
+type CreateSampler = unsafe extern "C" fn(
+    VkDevice, *const vk::SamplerCreateInfo, *const vk::AllocationCallbacks, *mut vk::Sampler,
+) -> vk::Result;
+type CreateImageView = unsafe extern "C" fn(
+    VkDevice, *const vk::ImageViewCreateInfo, *const vk::AllocationCallbacks, *mut vk::ImageView,
+) -> vk::Result;
+type CmdSetStencilValue = unsafe extern "C" fn(VkCommandBuffer, vk::StencilFaceFlags, u32);
+
+const LAYER_NAME: &[u8] = b"VK_LAYER_GFX_portability_subset\0";
+const LAYER_DESCRIPTION: &[u8] = b"Portability subset enforcement\0";
+const LAYER_INTERFACE_VERSION: u32 = 2;
+const LAYER_LINK_INFO: u32 = 0;
+const LAYER_NEGOTIATE_INTERFACE_STRUCT: u32 = 1;
+/// Vulkan 1.0 headers the layer is written against, as in its manifest.
+const LAYER_SPEC_VERSION: u32 = (1 << 22) | 66;
+
+/// Start of every Vulkan structure, with the type kept raw
+/// as the chains may contain structures unknown to our headers.
+#[repr(C)]
+struct BaseHeader {
+    sType: u32,
+    pNext: *const c_void,
+}
+
+#[repr(C)]
+struct LayerInstanceLink {
+    pNext: *mut LayerInstanceLink,
+    pfnNextGetInstanceProcAddr: GetInstanceProcAddr,
+    pfnNextGetPhysicalDeviceProcAddr: PFN_vkVoidFunction,
+}
+
+#[repr(C)]
+struct LayerDeviceLink {
+    pNext: *mut LayerDeviceLink,
+    pfnNextGetInstanceProcAddr: GetInstanceProcAddr,
+    pfnNextGetDeviceProcAddr: GetDeviceProcAddr,
+}
+
+/// `VkLayerInstanceCreateInfo` and `VkLayerDeviceCreateInfo`,
+/// limited to the link info member of their union.
+#[repr(C)]
+struct LayerCreateInfo<L> {
+    sType: u32,
+    pNext: *const c_void,
+    function: u32,
+    pLayerInfo: *mut L,
+}
+
+#[repr(C)]
+pub struct VkNegotiateLayerInterface {
+    sType: u32,
+    pNext: *mut c_void,
+    loaderLayerInterfaceVersion: u32,
+    pfnGetInstanceProcAddr: Option<GetInstanceProcAddr>,
+    pfnGetDeviceProcAddr: Option<GetDeviceProcAddr>,
+    pfnGetPhysicalDeviceProcAddr: PFN_vkVoidFunction,
+}
+
+#[derive(Clone, Copy)]
+struct Instance {
+    handle: VkInstance,
+    get_instance_proc_addr: GetInstanceProcAddr,
+    destroy_instance: Option<DestroyInstance>,
+    enumerate_device_extension_properties: Option<EnumerateDeviceExtensionProperties>,
+    get_physical_device_features2: Option<GetPhysicalDeviceFeatures2>,
+}
+
+#[derive(Clone, Copy)]
+struct Device {
+    get_device_proc_addr: GetDeviceProcAddr,
+    destroy_device: Option<DestroyDevice>,
+    create_graphics_pipelines: Option<CreateGraphicsPipelines>,
+    create_sampler: Option<CreateSampler>,
+    create_image_view: Option<CreateImageView>,
+    cmd_set_stencil_compare_mask: Option<CmdSetStencilValue>,
+    cmd_set_stencil_write_mask: Option<CmdSetStencilValue>,
+    cmd_set_stencil_reference: Option<CmdSetStencilValue>,
+}
+
+// The instance handle is only passed back to the loader.
+unsafe impl Send for Instance {}
+
+struct Settings {
+    subset: PortabilitySubset,
+    reject: bool,
+}
+
+impl Settings {
+    fn from_env() -> Self {
+        let mut subset = PortabilitySubset::strictest();
+        if let Ok(allowed) = env::var("GFX_LAYER_ALLOW") {
+            for name in allowed.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+                if !subset.allow(name) {
+                    warn!("Unknown portability feature {}, expected one of {:?}", name, PORTABILITY_FEATURE_NAMES);
+                }
+            }
+        }
+        if let Ok(alignment) = env::var("GFX_LAYER_STRIDE_ALIGNMENT") {
+            match alignment.parse() {
+                Ok(alignment) => subset.min_vertex_input_binding_stride_alignment = alignment,
+                Err(_) => warn!("Invalid GFX_LAYER_STRIDE_ALIGNMENT {}", alignment),
+            }
+        }
+        let reject = match env::var("GFX_LAYER_REJECT") {
+            Ok(value) => value == "1" || value == "true",
+            Err(_) => false,
+        };
+        Settings { subset, reject }
+    }
+}
+
+lazy_static! {
+    static ref SETTINGS: Settings = Settings::from_env();
+    /// Keyed by the loader dispatch table, which is shared with the physical devices.
+    static ref INSTANCES: Mutex<HashMap<usize, Instance>> = Mutex::new(HashMap::new());
+    /// Keyed by the loader dispatch table, which is shared with the queues and command buffers.
+    static ref DEVICES: Mutex<HashMap<usize, Device>> = Mutex::new(HashMap::new());
+}
+
+/// Reports an API usage outside of the portability subset,
+/// evaluates to true if the call has to fail.
+macro_rules! violation {
+    ($function:expr, $($arg:tt)*) => ({
+        error!("{}: {}", $function, format!($($arg)*));
+        SETTINGS.reject
+    });
+}
+
+macro_rules! next_fn {
+    ($get_proc_addr:expr, $handle:expr, $name:expr) => (
+        unsafe {
+            mem::transmute::<PFN_vkVoidFunction, _>(
+                ($get_proc_addr)($handle, concat!($name, "\0").as_ptr() as *const c_char)
+            )
+        }
+    );
+}
+
+macro_rules! intercept {
+    ($name:expr, $($vk:ident => $layer:expr,)*) => (
+        match $name {
+            $(
+                stringify!($vk) => Some(unsafe {
+                    mem::transmute::<*const (), unsafe extern "C" fn()>($layer as *const ())
+                }),
+            )*
+            _ => None
+        }
+    );
+}
+
+fn dispatch_key(handle: *mut c_void) -> usize {
+    unsafe { *(handle as *const usize) }
+}
+
+fn instance(handle: *mut c_void) -> Instance {
+    INSTANCES.lock().unwrap()[&dispatch_key(handle)]
+}
+
+fn device(handle: *mut c_void) -> Device {
+    DEVICES.lock().unwrap()[&dispatch_key(handle)]
+}
+
+/// Finds the loader link info in a create info chain.
+unsafe fn find_link_info<L>(mut ptr: *const c_void, ty: vk::StructureType) -> Option<*mut LayerCreateInfo<L>> {
+    while let Some(header) = (ptr as *const BaseHeader).as_ref() {
+        if header.sType == ty.as_raw() as u32 {
+            let info = ptr as *mut LayerCreateInfo<L>;
+            if (*info).function == LAYER_LINK_INFO {
+                return Some(info);
+            }
+        }
+        ptr = header.pNext;
+    }
+    None
+}
+
+/// Same as `slice::from_raw_parts`, accepting null pointers for empty arrays.
+unsafe fn make_slice<'a, T>(ptr: *const T, count: u32) -> &'a [T] {
+    if ptr.is_null() {
+        &[]
+    } else {
+        slice::from_raw_parts(ptr, count as _)
+    }
+}
+
+fn copy_properties<T: Copy>(items: &[T], pPropertyCount: *mut u32, pProperties: *mut T) -> vk::Result {
+    let property_count = unsafe { &mut *pPropertyCount };
+    let num_items = items.len() as u32;
+
+    if pProperties.is_null() {
+        *property_count = num_items;
+    } else {
+        if *property_count > num_items {
+            *property_count = num_items;
+        }
+        let properties =
+            unsafe { slice::from_raw_parts_mut(pProperties, *property_count as usize) };
+        properties.copy_from_slice(&items[.. *property_count as usize]);
+
+        if *property_count < num_items {
+            return vk::Result::INCOMPLETE;
+        }
+    }
+
+    vk::Result::SUCCESS
+}
+
+fn subset_extension() -> vk::ExtensionProperties {
+    let mut extension = vk::ExtensionProperties {
+        extension_name: [0; 256],
+        spec_version: VK_EXTX_PORTABILITY_SUBSET_SPEC_VERSION,
+    };
+    for (dst, &src) in extension.extension_name.iter_mut().zip(VK_EXTX_PORTABILITY_SUBSET_EXTENSION_NAME) {
+        *dst = src as c_char;
+    }
+    extension
+}
+
+#[no_mangle]
+pub extern "C" fn vkNegotiateLoaderLayerInterfaceVersion(
+    pVersionStruct: *mut VkNegotiateLayerInterface,
+) -> vk::Result {
+    let version_struct = unsafe { &mut *pVersionStruct };
+    if version_struct.sType != LAYER_NEGOTIATE_INTERFACE_STRUCT {
+        return vk::Result::ERROR_INITIALIZATION_FAILED;
+    }
+    if version_struct.loaderLayerInterfaceVersion >= 2 {
+        version_struct.pfnGetInstanceProcAddr = Some(vkGetInstanceProcAddr as GetInstanceProcAddr);
+        version_struct.pfnGetDeviceProcAddr = Some(vkGetDeviceProcAddr as GetDeviceProcAddr);
+        version_struct.pfnGetPhysicalDeviceProcAddr = None;
+    }
+    if version_struct.loaderLayerInterfaceVersion > LAYER_INTERFACE_VERSION {
+        version_struct.loaderLayerInterfaceVersion = LAYER_INTERFACE_VERSION;
+    }
+
+    vk::Result::SUCCESS
+}
+
+#[no_mangle]
+pub extern "C" fn vkGetInstanceProcAddr(
+    instance: VkInstance,
+    pName: *const c_char,
+) -> PFN_vkVoidFunction {
+    let name = match unsafe { CStr::from_ptr(pName) }.to_str() {
+        Ok(name) => name,
+        Err(_) => return None,
+    };
+
+    let addr = intercept!{ name,
+        vkGetInstanceProcAddr => vkGetInstanceProcAddr,
+        vkCreateInstance => layerCreateInstance,
+        vkDestroyInstance => layerDestroyInstance,
+        vkEnumerateInstanceLayerProperties => vkEnumerateInstanceLayerProperties,
+        vkEnumerateDeviceLayerProperties => vkEnumerateDeviceLayerProperties,
+        vkEnumerateDeviceExtensionProperties => layerEnumerateDeviceExtensionProperties,
+        vkGetPhysicalDeviceFeatures2KHR => layerGetPhysicalDeviceFeatures2KHR,
+        vkCreateDevice => layerCreateDevice,
+    };
+    if addr.is_some() {
+        return addr;
+    }
+    let addr = device_intercept(name);
+    if addr.is_some() || instance.is_null() {
+        return addr;
+    }
+
+    unsafe { (self::instance(instance).get_instance_proc_addr)(instance, pName) }
+}
+
+#[no_mangle]
+pub extern "C" fn vkGetDeviceProcAddr(
+    device: VkDevice,
+    pName: *const c_char,
+) -> PFN_vkVoidFunction {
+    let name = match unsafe { CStr::from_ptr(pName) }.to_str() {
+        Ok(name) => name,
+        Err(_) => return None,
+    };
+
+    let addr = device_intercept(name);
+    if addr.is_some() {
+        return addr;
+    }
+
+    unsafe { (self::device(device).get_device_proc_addr)(device, pName) }
+}
+
+fn device_intercept(name: &str) -> PFN_vkVoidFunction {
+    intercept!{ name,
+        vkGetDeviceProcAddr => vkGetDeviceProcAddr,
+        vkDestroyDevice => layerDestroyDevice,
+        vkCreateGraphicsPipelines => layerCreateGraphicsPipelines,
+        vkCreateSampler => layerCreateSampler,
+        vkCreateImageView => layerCreateImageView,
+        vkCmdSetStencilCompareMask => layerCmdSetStencilCompareMask,
+        vkCmdSetStencilWriteMask => layerCmdSetStencilWriteMask,
+        vkCmdSetStencilReference => layerCmdSetStencilReference,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn vkEnumerateInstanceLayerProperties(
+    pPropertyCount: *mut u32,
+    pProperties: *mut vk::LayerProperties,
+) -> vk::Result {
+    let mut layer = vk::LayerProperties {
+        layer_name: [0; 256],
+        spec_version: LAYER_SPEC_VERSION,
+        implementation_version: 1,
+        description: [0; 256],
+    };
+    for (dst, &src) in layer.layer_name.iter_mut().zip(LAYER_NAME) {
+        *dst = src as c_char;
+    }
+    for (dst, &src) in layer.description.iter_mut().zip(LAYER_DESCRIPTION) {
+        *dst = src as c_char;
+    }
+    copy_properties(&[layer], pPropertyCount, pProperties)
+}
+
+#[no_mangle]
+pub extern "C" fn vkEnumerateDeviceLayerProperties(
+    _physicalDevice: VkPhysicalDevice,
+    pPropertyCount: *mut u32,
+    pProperties: *mut vk::LayerProperties,
+) -> vk::Result {
+    vkEnumerateInstanceLayerProperties(pPropertyCount, pProperties)
+}
+
+extern "C" fn layerCreateInstance(
+    pCreateInfo: *const vk::InstanceCreateInfo,
+    pAllocator: *const vk::AllocationCallbacks,
+    pInstance: *mut VkInstance,
+) -> vk::Result {
+    let _ = env_logger::try_init();
+
+    let link_info = match unsafe {
+        find_link_info::<LayerInstanceLink>(
+            (*pCreateInfo).p_next,
+            vk::StructureType::LOADER_INSTANCE_CREATE_INFO,
+        )
+    } {
+        Some(info) => info,
+        None => return vk::Result::ERROR_INITIALIZATION_FAILED,
+    };
+    let get_instance_proc_addr = unsafe {
+        let link = &*(*link_info).pLayerInfo;
+        // advance the chain for the next layer
+        (*link_info).pLayerInfo = link.pNext;
+        link.pfnNextGetInstanceProcAddr
+    };
+
+    let create_instance: Option<CreateInstance> =
+        next_fn!(get_instance_proc_addr, ptr::null_mut(), "vkCreateInstance");
+    let result = match create_instance {
+        Some(create_instance) => unsafe { create_instance(pCreateInfo, pAllocator, pInstance) },
+        None => vk::Result::ERROR_INITIALIZATION_FAILED,
+    };
+    if result != vk::Result::SUCCESS {
+        return result;
+    }
+
+    let handle = unsafe { *pInstance };
+    let instance = Instance {
+        handle,
+        get_instance_proc_addr,
+        destroy_instance: next_fn!(get_instance_proc_addr, handle, "vkDestroyInstance"),
+        enumerate_device_extension_properties:
+            next_fn!(get_instance_proc_addr, handle, "vkEnumerateDeviceExtensionProperties"),
+        get_physical_device_features2:
+            next_fn!(get_instance_proc_addr, handle, "vkGetPhysicalDeviceFeatures2KHR"),
+    };
+    INSTANCES.lock().unwrap().insert(dispatch_key(handle), instance);
+
+    vk::Result::SUCCESS
+}
+
+extern "C" fn layerDestroyInstance(
+    instance: VkInstance,
+    pAllocator: *const vk::AllocationCallbacks,
+) {
+    if instance.is_null() {
+        return;
+    }
+    let state = INSTANCES.lock().unwrap().remove(&dispatch_key(instance));
+    if let Some(destroy_instance) = state.and_then(|state| state.destroy_instance) {
+        unsafe { destroy_instance(instance, pAllocator) };
+    }
+}
+
+extern "C" fn layerEnumerateDeviceExtensionProperties(
+    physicalDevice: VkPhysicalDevice,
+    pLayerName: *const c_char,
+    pPropertyCount: *mut u32,
+    pProperties: *mut vk::ExtensionProperties,
+) -> vk::Result {
+    if !pLayerName.is_null() {
+        let layer_name = unsafe { CStr::from_ptr(pLayerName) };
+        if layer_name.to_bytes_with_nul() == LAYER_NAME {
+            return copy_properties(&[subset_extension()], pPropertyCount, pProperties);
+        }
+    }
+
+    let enumerate = match instance(physicalDevice).enumerate_device_extension_properties {
+        Some(enumerate) => enumerate,
+        None => return vk::Result::ERROR_INITIALIZATION_FAILED,
+    };
+    if !pLayerName.is_null() {
+        return unsafe { enumerate(physicalDevice, pLayerName, pPropertyCount, pProperties) };
+    }
+
+    // Driver extensions, with the subset one added
+    let mut count = 0;
+    let result = unsafe { enumerate(physicalDevice, ptr::null(), &mut count, ptr::null_mut()) };
+    if result != vk::Result::SUCCESS {
+        return result;
+    }
+    let mut extensions = vec![unsafe { mem::zeroed::<vk::ExtensionProperties>() }; count as usize];
+    let result = unsafe {
+        enumerate(physicalDevice, ptr::null(), &mut count, extensions.as_mut_ptr())
+    };
+    if result != vk::Result::SUCCESS && result != vk::Result::INCOMPLETE {
+        return result;
+    }
+    extensions.truncate(count as usize);
+    extensions.push(subset_extension());
+
+    copy_properties(&extensions, pPropertyCount, pProperties)
+}
+
+extern "C" fn layerGetPhysicalDeviceFeatures2KHR(
+    physicalDevice: VkPhysicalDevice,
+    pFeatures: *mut vk::PhysicalDeviceFeatures2,
+) {
+    if let Some(get_features) = instance(physicalDevice).get_physical_device_features2 {
+        unsafe { get_features(physicalDevice, pFeatures) };
+    }
+
+    let subset = &SETTINGS.subset;
+    let mut ptr = pFeatures as *const c_void;
+    while let Some(header) = unsafe { (ptr as *const BaseHeader).as_ref() } {
+        let ty = header.sType;
+        if ty == STRUCTURE_TYPE_FEATURES {
+            subset.fill_features(unsafe {
+                &mut *(ptr as *mut VkPhysicalDevicePortabilitySubsetFeaturesEXTX)
+            });
+        } else if ty == STRUCTURE_TYPE_PROPERTIES {
+            subset.fill_properties(unsafe {
+                &mut *(ptr as *mut VkPhysicalDevicePortabilitySubsetPropertiesEXTX)
+            });
+        }
+        ptr = header.pNext;
+    }
+}
+
+extern "C" fn layerCreateDevice(
+    physicalDevice: VkPhysicalDevice,
+    pCreateInfo: *const vk::DeviceCreateInfo,
+    pAllocator: *const vk::AllocationCallbacks,
+    pDevice: *mut VkDevice,
+) -> vk::Result {
+    let link_info = match unsafe {
+        find_link_info::<LayerDeviceLink>(
+            (*pCreateInfo).p_next,
+            vk::StructureType::LOADER_DEVICE_CREATE_INFO,
+        )
+    } {
+        Some(info) => info,
+        None => return vk::Result::ERROR_INITIALIZATION_FAILED,
+    };
+    let (get_instance_proc_addr, get_device_proc_addr) = unsafe {
+        let link = &*(*link_info).pLayerInfo;
+        // advance the chain for the next layer
+        (*link_info).pLayerInfo = link.pNext;
+        (link.pfnNextGetInstanceProcAddr, link.pfnNextGetDeviceProcAddr)
+    };
+
+    let create_device: Option<CreateDevice> =
+        next_fn!(get_instance_proc_addr, instance(physicalDevice).handle, "vkCreateDevice");
+    let create_device = match create_device {
+        Some(create_device) => create_device,
+        None => return vk::Result::ERROR_INITIALIZATION_FAILED,
+    };
+
+    // The subset extension is ours, the driver doesn't know it.
+    let mut create_info = unsafe { *pCreateInfo };
+    let extensions = unsafe {
+        make_slice(create_info.pp_enabled_extension_names, create_info.enabled_extension_count)
+    }
+        .iter()
+        .cloned()
+        .filter(|&name| unsafe { CStr::from_ptr(name) }.to_bytes_with_nul() != VK_EXTX_PORTABILITY_SUBSET_EXTENSION_NAME)
+        .collect::<Vec<_>>();
+    create_info.enabled_extension_count = extensions.len() as _;
+    create_info.pp_enabled_extension_names = extensions.as_ptr();
+
+    let result = unsafe { create_device(physicalDevice, &create_info, pAllocator, pDevice) };
+    if result != vk::Result::SUCCESS {
+        return result;
+    }
+
+    let handle = unsafe { *pDevice };
+    let device = Device {
+        get_device_proc_addr,
+        destroy_device: next_fn!(get_device_proc_addr, handle, "vkDestroyDevice"),
+        create_graphics_pipelines: next_fn!(get_device_proc_addr, handle, "vkCreateGraphicsPipelines"),
+        create_sampler: next_fn!(get_device_proc_addr, handle, "vkCreateSampler"),
+        create_image_view: next_fn!(get_device_proc_addr, handle, "vkCreateImageView"),
+        cmd_set_stencil_compare_mask: next_fn!(get_device_proc_addr, handle, "vkCmdSetStencilCompareMask"),
+        cmd_set_stencil_write_mask: next_fn!(get_device_proc_addr, handle, "vkCmdSetStencilWriteMask"),
+        cmd_set_stencil_reference: next_fn!(get_device_proc_addr, handle, "vkCmdSetStencilReference"),
+    };
+    DEVICES.lock().unwrap().insert(dispatch_key(handle), device);
+
+    vk::Result::SUCCESS
+}
+
+extern "C" fn layerDestroyDevice(
+    device: VkDevice,
+    pAllocator: *const vk::AllocationCallbacks,
+) {
+    if device.is_null() {
+        return;
+    }
+    let state = DEVICES.lock().unwrap().remove(&dispatch_key(device));
+    if let Some(destroy_device) = state.and_then(|state| state.destroy_device) {
+        unsafe { destroy_device(device, pAllocator) };
+    }
+}
+
+/// Checks a graphics pipeline against the subset, returns true if it has to be rejected.
+fn check_graphics_pipeline(info: &vk::GraphicsPipelineCreateInfo) -> bool {
+    let subset = &SETTINGS.subset;
+    let mut reject = false;
+
+    if let Some(input_assembly) = unsafe { info.p_input_assembly_state.as_ref() } {
+        if input_assembly.topology == vk::PrimitiveTopology::TRIANGLE_FAN
+            && !subset.triangle_fans
+        {
+            reject |= violation!("vkCreateGraphicsPipelines", "Triangle fans are not supported");
+        }
+    }
+
+    if let Some(vertex_input) = unsafe { info.p_vertex_input_state.as_ref() } {
+        let alignment = subset.min_vertex_input_binding_stride_alignment;
+        let bindings = unsafe {
+            make_slice(
+                vertex_input.p_vertex_binding_descriptions,
+                vertex_input.vertex_binding_description_count,
+            )
+        };
+        for binding in bindings {
+            if alignment > 1 && binding.stride % alignment != 0 {
+                reject |= violation!("vkCreateGraphicsPipelines",
+                    "Stride {} of binding {} is not a multiple of {}",
+                    binding.stride, binding.binding, alignment);
+            }
+        }
+    }
+
+    if let Some(depth_stencil) = unsafe { info.p_depth_stencil_state.as_ref() } {
+        if depth_stencil.stencil_test_enable == vk::TRUE && !subset.separate_stencil_mask_ref {
+            let dynamic_states = match unsafe { info.p_dynamic_state.as_ref() } {
+                Some(state) => unsafe {
+                    make_slice(state.p_dynamic_states, state.dynamic_state_count)
+                },
+                None => &[],
+            };
+            let (front, back) = (&depth_stencil.front, &depth_stencil.back);
+            let values = [
+                (vk::DynamicState::STENCIL_COMPARE_MASK, "compare mask", front.compare_mask, back.compare_mask),
+                (vk::DynamicState::STENCIL_WRITE_MASK, "write mask", front.write_mask, back.write_mask),
+                (vk::DynamicState::STENCIL_REFERENCE, "reference", front.reference, back.reference),
+            ];
+            for &(state, what, front, back) in &values {
+                if front != back && !dynamic_states.contains(&state) {
+                    reject |= violation!("vkCreateGraphicsPipelines",
+                        "Separate stencil {} for front ({}) and back ({}) faces is not supported",
+                        what, front, back);
+                }
+            }
+        }
+    }
+
+    reject
+}
+
+extern "C" fn layerCreateGraphicsPipelines(
+    device: VkDevice,
+    pipelineCache: vk::PipelineCache,
+    createInfoCount: u32,
+    pCreateInfos: *const vk::GraphicsPipelineCreateInfo,
+    pAllocator: *const vk::AllocationCallbacks,
+    pPipelines: *mut vk::Pipeline,
+) -> vk::Result {
+    let infos = unsafe { slice::from_raw_parts(pCreateInfos, createInfoCount as _) };
+    // report all the pipelines before rejecting
+    let reject = infos
+        .iter()
+        .fold(false, |reject, info| check_graphics_pipeline(info) | reject);
+    if reject {
+        unsafe { ptr::write_bytes(pPipelines, 0, createInfoCount as _) };
+        return vk::Result::ERROR_VALIDATION_FAILED_EXT;
+    }
+
+    let create_graphics_pipelines = self::device(device).create_graphics_pipelines.unwrap();
+    unsafe {
+        create_graphics_pipelines(device, pipelineCache, createInfoCount, pCreateInfos, pAllocator, pPipelines)
+    }
+}
+
+extern "C" fn layerCreateSampler(
+    device: VkDevice,
+    pCreateInfo: *const vk::SamplerCreateInfo,
+    pAllocator: *const vk::AllocationCallbacks,
+    pSampler: *mut vk::Sampler,
+) -> vk::Result {
+    let info = unsafe { &*pCreateInfo };
+    if info.mip_lod_bias != 0.0 && !SETTINGS.subset.sampler_mip_lod_bias {
+        if violation!("vkCreateSampler", "Mip LOD bias {} is not supported", info.mip_lod_bias) {
+            return vk::Result::ERROR_VALIDATION_FAILED_EXT;
+        }
+    }
+
+    let create_sampler = self::device(device).create_sampler.unwrap();
+    unsafe { create_sampler(device, pCreateInfo, pAllocator, pSampler) }
+}
+
+extern "C" fn layerCreateImageView(
+    device: VkDevice,
+    pCreateInfo: *const vk::ImageViewCreateInfo,
+    pAllocator: *const vk::AllocationCallbacks,
+    pView: *mut vk::ImageView,
+) -> vk::Result {
+    let info = unsafe { &*pCreateInfo };
+    if !SETTINGS.subset.standard_image_views {
+        let components = &info.components;
+        let identity = [
+            (components.r, vk::ComponentSwizzle::R),
+            (components.g, vk::ComponentSwizzle::G),
+            (components.b, vk::ComponentSwizzle::B),
+            (components.a, vk::ComponentSwizzle::A),
+        ]
+        .iter()
+        .all(|&(swizzle, own)| {
+            swizzle == vk::ComponentSwizzle::IDENTITY || swizzle == own
+        });
+        if !identity && violation!("vkCreateImageView", "Component swizzle {:?} is not supported", components) {
+            return vk::Result::ERROR_VALIDATION_FAILED_EXT;
+        }
+    }
+
+    let create_image_view = self::device(device).create_image_view.unwrap();
+    unsafe { create_image_view(device, pCreateInfo, pAllocator, pView) }
+}
+
+fn check_stencil_face(function: &str, faceMask: vk::StencilFaceFlags) {
+    if faceMask != vk::StencilFaceFlags::FRONT | vk::StencilFaceFlags::BACK
+        && !SETTINGS.subset.separate_stencil_mask_ref
+    {
+        // commands can't fail, the usage is only reported
+        let _ = violation!(function, "Separate stencil faces {:#x} are not supported", faceMask.as_raw());
+    }
+}
+
+extern "C" fn layerCmdSetStencilCompareMask(
+    commandBuffer: VkCommandBuffer,
+    faceMask: vk::StencilFaceFlags,
+    compareMask: u32,
+) {
+    check_stencil_face("vkCmdSetStencilCompareMask", faceMask);
+    let set_compare_mask = device(commandBuffer).cmd_set_stencil_compare_mask.unwrap();
+    unsafe { set_compare_mask(commandBuffer, faceMask, compareMask) }
+}
+
+extern "C" fn layerCmdSetStencilWriteMask(
+    commandBuffer: VkCommandBuffer,
+    faceMask: vk::StencilFaceFlags,
+    writeMask: u32,
+) {
+    check_stencil_face("vkCmdSetStencilWriteMask", faceMask);
+    let set_write_mask = device(commandBuffer).cmd_set_stencil_write_mask.unwrap();
+    unsafe { set_write_mask(commandBuffer, faceMask, writeMask) }
+}
+
+extern "C" fn layerCmdSetStencilReference(
+    commandBuffer: VkCommandBuffer,
+    faceMask: vk::StencilFaceFlags,
+    reference: u32,
+) {
+    check_stencil_face("vkCmdSetStencilReference", faceMask);
+    let set_reference = device(commandBuffer).cmd_set_stencil_reference.unwrap();
+    unsafe { set_reference(commandBuffer, faceMask, reference) }
+}
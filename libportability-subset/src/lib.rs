@@ -0,0 +1,93 @@
+//! Description of the `VK_EXTX_portability_subset` capabilities, shared
+//! between the implementation and the validation layer.
+//!
+//! The extension structures are declared with raw integer types,
+//! so that the crate doesn't depend on any Vulkan bindings.
+
+#![allow(non_snake_case)]
+
+use std::os::raw::c_void;
+
+pub const VK_EXTX_PORTABILITY_SUBSET_SPEC_VERSION: u32 = 1;
+pub const VK_EXTX_PORTABILITY_SUBSET_EXTENSION_NAME: &'static [u8; 27usize] =
+    b"VK_EXTX_portability_subset\x00";
+/// `VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_PORTABILITY_SUBSET_FEATURES_EXTX`
+pub const STRUCTURE_TYPE_FEATURES: u32 = 1000163000;
+/// `VK_STRUCTURE_TYPE_PHYSICAL_DEVICE_PORTABILITY_SUBSET_PROPERTIES_EXTX`
+pub const STRUCTURE_TYPE_PROPERTIES: u32 = 1000163001;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct VkPhysicalDevicePortabilitySubsetFeaturesEXTX {
+    pub sType: u32,
+    pub pNext: *const c_void,
+    pub triangleFans: u32,
+    pub separateStencilMaskRef: u32,
+    pub events: u32,
+    pub standardImageViews: u32,
+    pub samplerMipLodBias: u32,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct VkPhysicalDevicePortabilitySubsetPropertiesEXTX {
+    pub sType: u32,
+    pub pNext: *const c_void,
+    pub minVertexInputBindingStrideAlignment: u32,
+}
+
+/// Parts of Vulkan a portability implementation may not support.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PortabilitySubset {
+    pub triangle_fans: bool,
+    pub separate_stencil_mask_ref: bool,
+    pub sampler_mip_lod_bias: bool,
+    pub standard_image_views: bool,
+    pub min_vertex_input_binding_stride_alignment: u32,
+}
+
+/// Names of the boolean capabilities, as in `VkPhysicalDevicePortabilitySubsetFeaturesEXTX`.
+pub const PORTABILITY_FEATURE_NAMES: &[&str] = &[
+    "triangleFans",
+    "separateStencilMaskRef",
+    "samplerMipLodBias",
+    "standardImageViews",
+];
+
+impl PortabilitySubset {
+    /// The most restrictive subset, which any portability implementation supports.
+    pub fn strictest() -> Self {
+        PortabilitySubset {
+            triangle_fans: false,
+            separate_stencil_mask_ref: false,
+            sampler_mip_lod_bias: false,
+            standard_image_views: false,
+            min_vertex_input_binding_stride_alignment: 4,
+        }
+    }
+
+    /// Allows one of the `PORTABILITY_FEATURE_NAMES`, returns false for unknown names.
+    pub fn allow(&mut self, name: &str) -> bool {
+        let feature = match name {
+            "triangleFans" => &mut self.triangle_fans,
+            "separateStencilMaskRef" => &mut self.separate_stencil_mask_ref,
+            "samplerMipLodBias" => &mut self.sampler_mip_lod_bias,
+            "standardImageViews" => &mut self.standard_image_views,
+            _ => return false,
+        };
+        *feature = true;
+        true
+    }
+
+    pub fn fill_features(&self, data: &mut VkPhysicalDevicePortabilitySubsetFeaturesEXTX) {
+        let to_vk = |supported| if supported { 1 } else { 0 };
+        data.triangleFans = to_vk(self.triangle_fans);
+        data.separateStencilMaskRef = to_vk(self.separate_stencil_mask_ref);
+        data.samplerMipLodBias = to_vk(self.sampler_mip_lod_bias);
+        data.standardImageViews = to_vk(self.standard_image_views);
+    }
+
+    pub fn fill_properties(&self, data: &mut VkPhysicalDevicePortabilitySubsetPropertiesEXTX) {
+        data.minVertexInputBindingStrideAlignment = self.min_vertex_input_binding_stride_alignment;
+    }
+}